@@ -1,67 +1,666 @@
-use crate::fits::{ChannelView, DemosaicMode, FitsImage, Stretch};
+use crate::cache::{ImageCache, DEFAULT_BUDGET_BYTES};
+use crate::catalog::{self, CatalogEntry};
+use crate::config::{Config, DirView, FileFilter, StfOffset, StretchPreset, Theme};
+use crate::export::{draw_caption, thumbnail, write_png};
+use crate::fits::{
+    clipping, ChannelView, Clipping, ColorAxis, Combine, ColorOptions, DemosaicMode, FitsImage, LoadOptions, LoadTimings,
+    fwhm_grid, separation, BackgroundNoise, FrameType, Mask, Palette, PlaneStats, RadialProfile, Spectrum, Star, Stf, Stretch,
+    Wcs,
+    DEFAULT_BACKGROUND, DEFAULT_WHITE_CLIP, PROFILE_BIN,
+};
+use crate::gpu::{paint_callback, GpuStretch, GpuView};
+use crate::keymap::{Action, VIM_KEYS};
+use crate::scan::{FileMeta, HeaderScan};
+use crate::watch::{DirWatcher, DEBOUNCE};
+use eframe::glow;
 use egui::TextureHandle;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ops::RangeInclusive;
 use std::path::PathBuf;
-use std::sync::mpsc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 
 enum LoadResult {
-    Ok(Box<FitsImage>),
+    /// Decimated quick-look of a large frame; the full image follows
+    Preview(Box<FitsImage>),
+    Ok(Box<FitsImage>, LoadTimings),
     Err(String),
 }
 
+/// Zoom limits shared by the keyboard shortcuts and the zoom entry/slider.
+const ZOOM_MIN: f32 = 0.05;
+const ZOOM_MAX: f32 = 32.0;
+
+/// Smallest mip level generated (longest side, in texels).
+const MIP_MIN_SIZE: usize = 256;
+
+/// Keywords shown in the capture summary bar unless changed in Preferences.
+const DEFAULT_SUMMARY_KEYS: &str = "EXPTIME, GAIN, OFFSET, CCD-TEMP, FILTER, OBJECT, DATE-OBS";
+
+/// Lines of the on-image HUD unless changed in Preferences: `file`, `size`,
+/// `stretch` and `zoom` are the viewer's own, anything else a header keyword.
+const DEFAULT_HUD_ITEMS: &str = "file, size, stretch, zoom, OBJECT, FILTER, EXPTIME";
+
+/// Header keywords burned into the PNG export caption, one per line.
+const CAPTION_KEYS: [&str; 3] = ["OBJECT", "FILTER", "EXPTIME"];
+
+/// Maximum gap between the two presses of vim's `gg`.
+const VIM_GG_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Colour of flagged pixels in the mask overlay (its opacity is set in the menu bar).
+const MASK_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 0, 255);
+
+/// Longest side, in pixels, of the file-browser hover previews.
+const THUMBNAIL_SIZE: usize = 256;
+/// Hover previews kept before the cache is emptied.
+const THUMBNAIL_CACHE: usize = 200;
+
+/// Brightness (of 255) of the previous image while the next file loads.
+const PLACEHOLDER_DIM: u8 = 110;
+
+/// Divider and labels of the split-screen stretch comparison.
+const SPLIT_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 210, 0);
+
+/// Line, end points and label of the distance measurement.
+const MEASURE_COLOR: egui::Color32 = egui::Color32::from_rgb(0, 220, 255);
+/// Photometry apertures and their numbers
+const APERTURE_COLOR: egui::Color32 = egui::Color32::from_rgb(120, 255, 120);
+/// Radial profile: samples, fitted curve and markers
+const PROFILE_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 200, 60);
+/// FWHM tilt map cells, by how much wider their stars are than in the best cell
+const TILT_GOOD: egui::Color32 = egui::Color32::from_rgb(110, 230, 110);
+const TILT_FAIR: egui::Color32 = egui::Color32::from_rgb(240, 210, 60);
+const TILT_POOR: egui::Color32 = egui::Color32::from_rgb(255, 90, 70);
+/// Longest side of the navigator shown when zoomed in, in points
+const NAVIGATOR_SIZE: f32 = 160.0;
+const NAVIGATOR_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 220, 80);
+/// Tallest the folder tree grows before scrolling, in points
+const FOLDER_TREE_HEIGHT: f32 = 200.0;
+/// Path levels shown as breadcrumbs above the file list
+const BREADCRUMBS: usize = 4;
+/// Longest the scale bar may be, in points
+const SCALE_BAR_MAX: f32 = 160.0;
+/// Size of the radial profile plot, in points
+const PROFILE_PLOT: egui::Vec2 = egui::vec2(320.0, 180.0);
+/// Line of a one-dimensional spectrum and its hover marker
+const SPECTRUM_COLOR: egui::Color32 = egui::Color32::from_rgb(120, 200, 255);
+/// Catalogue overlay markers and names
+const CATALOG_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 140, 220);
+/// Default aperture radius and inner / outer annulus radii, in pixels
+const DEFAULT_APERTURE: [f32; 3] = [5.0, 8.0, 12.0];
+/// Default low / high percentiles (fractions) of the percentile stretch
+pub(crate) const DEFAULT_LINEAR_CLIP: (f32, f32) = (0.005, 0.995);
+/// Default knee of the logarithmic stretch
+pub(crate) const DEFAULT_LOG_KNEE: f32 = 100.0;
+/// Ranges of the stretch and colour controls in Preferences; presets edited by
+/// hand are clamped to them too.
+const WHITE_CLIP_RANGE: RangeInclusive<f32> = 0.99..=1.0;
+const LINEAR_CLIP_LOW_RANGE: RangeInclusive<f32> = 0.0..=0.5;
+const LINEAR_CLIP_HIGH_RANGE: RangeInclusive<f32> = 0.5..=1.0;
+const LOG_KNEE_RANGE: RangeInclusive<f32> = 1.0..=10000.0;
+const BACKGROUND_RANGE: RangeInclusive<f32> = 0.05..=0.5;
+const SATURATION_RANGE: RangeInclusive<f32> = 0.0..=2.0;
+
+/// How long an informational status message stays in the bottom bar.
+const STATUS_INFO_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The current image as prepared for the viewport.
+enum Rendered {
+    /// Stretched on the CPU into a texture (mip levels in `texture_mips`)
+    Texture(TextureHandle),
+    /// Stretched by the shader of `GpuStretch` while drawing
+    Gpu(GpuView),
+}
+
+/// Initial display settings given on the command line. They take precedence over
+/// the settings remembered for the directory, without replacing them.
+#[derive(Default)]
+pub struct StartView {
+    /// Stretch mode
+    pub stretch: Option<Stretch>,
+    /// Channel view of colour images
+    pub channel: Option<ChannelView>,
+}
+
+/// A CPU rendering of the displayed image, built by the render worker.
+struct RenderResult {
+    kind: RenderKind,
+    /// `image_generation` it was rendered from
+    generation: u64,
+    texture: TextureHandle,
+    mips: Vec<TextureHandle>,
+    clipping: Vec<Clipping>,
+    /// AutoStretch parameters computed for it, when none were set yet
+    stf: Option<Vec<Stf>>,
+}
+
+/// What a [`RenderJob`] is for; the latest job of each kind is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RenderKind {
+    /// The image view
+    Main = 0,
+    /// The true-linear rendering right of the split-screen divider
+    Compare = 1,
+}
+
+/// A rendering of the displayed image for the [`RenderWorker`] to build.
+struct RenderJob {
+    kind: RenderKind,
+    img: Arc<FitsImage>,
+    stretch: Stretch,
+    view: ChannelView,
+    color: ColorOptions,
+    white_clip: f32,
+    /// AutoStretch parameters to use; computed by the worker when None
+    stf: Option<Vec<Stf>>,
+    /// Applied to the STF the worker computes
+    stf_offset: StfOffset,
+    /// `image_generation` of `img`
+    generation: u64,
+}
+
+/// One long-lived thread building the CPU renderings, so a slow stretch of a
+/// large frame does not stall the UI. Only the latest job of each
+/// [`RenderKind`] counts: newer jobs replace queued ones, and a running one is
+/// abandoned before its stretch and before its texture upload once it has been
+/// superseded or cancelled.
+struct RenderWorker {
+    tx: mpsc::Sender<(u64, RenderJob)>,
+    rx: mpsc::Receiver<(u64, RenderResult)>,
+    /// Id of the latest job of each kind; the worker drops every other
+    latest: Arc<[AtomicU64; 2]>,
+    /// The latest job's result of each kind is still to come
+    busy: [bool; 2],
+    /// Results of the latest jobs not picked up yet
+    ready: [Option<RenderResult>; 2],
+}
+
+impl RenderWorker {
+    fn start(ctx: egui::Context) -> Self {
+        let (tx, jobs) = mpsc::channel::<(u64, RenderJob)>();
+        let (results, rx) = mpsc::channel();
+        let latest = Arc::new([AtomicU64::new(0), AtomicU64::new(0)]);
+        let current = Arc::clone(&latest);
+        std::thread::spawn(move || {
+            while let Ok(queued) = jobs.recv() {
+                let mut pending = [None, None];
+                for (id, job) in std::iter::once(queued).chain(jobs.try_iter()) {
+                    let kind = job.kind as usize;
+                    pending[kind] = Some((id, job));
+                }
+                for (id, job) in pending.into_iter().flatten() {
+                    let kind = job.kind as usize;
+                    let live = || current[kind].load(Ordering::Relaxed) == id;
+                    if let Some(result) = job.run(&ctx, live) {
+                        let _ = results.send((id, result));
+                        ctx.request_repaint();
+                    }
+                }
+            }
+        });
+        RenderWorker { tx, rx, latest, busy: [false; 2], ready: [None, None] }
+    }
+
+    /// Queue `job`, superseding any earlier one of its kind.
+    fn submit(&mut self, job: RenderJob) {
+        let kind = job.kind as usize;
+        let id = self.latest[kind].fetch_add(1, Ordering::Relaxed) + 1;
+        self.ready[kind] = None;
+        self.busy[kind] = self.tx.send((id, job)).is_ok();
+    }
+
+    /// Abandon the job of `kind` queued or running, if any.
+    fn cancel(&mut self, kind: RenderKind) {
+        self.latest[kind as usize].fetch_add(1, Ordering::Relaxed);
+        self.ready[kind as usize] = None;
+        self.busy[kind as usize] = false;
+    }
+
+    /// A job of `kind` is queued or running.
+    fn busy(&self, kind: RenderKind) -> bool {
+        self.busy[kind as usize]
+    }
+
+    /// The result of the latest job of `kind`, once it is ready.
+    fn poll(&mut self, kind: RenderKind) -> Option<RenderResult> {
+        for (id, result) in self.rx.try_iter() {
+            let k = result.kind as usize;
+            if id == self.latest[k].load(Ordering::Relaxed) {
+                self.busy[k] = false;
+                self.ready[k] = Some(result);
+            }
+        }
+        self.ready[kind as usize].take()
+    }
+}
+
+impl RenderJob {
+    /// Stretch and upload the rendering, unless `live` turns false first.
+    fn run(mut self, ctx: &egui::Context, live: impl Fn() -> bool) -> Option<RenderResult> {
+        if !live() {
+            return None;
+        }
+        let (img, view, color) = (&self.img, self.view, self.color);
+        let had_stf = self.stf.is_some();
+        let rgba = render_image(img, self.stretch, view, color, &mut self.stf, self.white_clip, self.stf_offset);
+        if !live() {
+            return None;
+        }
+        let colour = img.channels == 3 && view == ChannelView::Rgb && !color.night;
+        let clipping = clipping(&rgba, colour);
+        let name = match self.kind {
+            RenderKind::Main => "fits_image",
+            RenderKind::Compare => "fits_compare",
+        };
+        let color_image = egui::ColorImage::from_rgba_unmultiplied([img.width, img.height], &rgba);
+        let texture = ctx.load_texture(name, color_image, egui::TextureOptions::LINEAR);
+        let mips = load_mips(ctx, name, rgba, img.width, img.height);
+        let stf = if had_stf { None } else { self.stf };
+        Some(RenderResult { kind: self.kind, generation: self.generation, texture, mips, clipping, stf })
+    }
+}
+
+/// Hover preview of a file in the browser.
+enum Thumbnail {
+    Loading,
+    Ready(TextureHandle),
+    Failed(String),
+}
+
+/// Renders the hover previews on one background thread. It holds a single waiting
+/// request: hovering another file replaces it, so rows the mouse has left are
+/// never rendered.
+struct ThumbnailWorker {
+    /// The next file to render, and the wake-up for the thread
+    wanted: Arc<(Mutex<Option<PathBuf>>, Condvar)>,
+    rx: mpsc::Receiver<(PathBuf, Thumbnail)>,
+}
+
+impl ThumbnailWorker {
+    fn start(ctx: egui::Context) -> Self {
+        let wanted = Arc::new((Mutex::new(None::<PathBuf>), Condvar::new()));
+        let (tx, rx) = mpsc::channel();
+        let queue = Arc::clone(&wanted);
+        std::thread::spawn(move || loop {
+            let path = {
+                let (slot, wake) = &*queue;
+                let mut slot = slot.lock().unwrap_or_else(|e| e.into_inner());
+                loop {
+                    match slot.take() {
+                        Some(path) => break path,
+                        None => slot = wake.wait(slot).unwrap_or_else(|e| e.into_inner()),
+                    }
+                }
+            };
+            let thumb = match thumbnail(&path, THUMBNAIL_SIZE) {
+                Ok((rgba, w, h)) => {
+                    let image = egui::ColorImage::from_rgba_unmultiplied([w, h], &rgba);
+                    Thumbnail::Ready(ctx.load_texture("thumbnail", image, egui::TextureOptions::LINEAR))
+                }
+                Err(e) => Thumbnail::Failed(format!("{e:#}")),
+            };
+            if tx.send((path, thumb)).is_err() {
+                return;
+            }
+            ctx.request_repaint();
+        });
+        ThumbnailWorker { wanted, rx }
+    }
+
+    /// Render `path` next. Returns the request it replaced, which will not be rendered.
+    fn request(&self, path: PathBuf) -> Option<PathBuf> {
+        let (slot, wake) = &*self.wanted;
+        let replaced = slot.lock().unwrap_or_else(|e| e.into_inner()).replace(path);
+        wake.notify_one();
+        replaced
+    }
+
+    /// Forget the waiting request, if any, and return it.
+    fn cancel(&self) -> Option<PathBuf> {
+        self.wanted.0.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+
+    /// Previews finished since the last call.
+    fn poll(&self) -> Vec<(PathBuf, Thumbnail)> {
+        self.rx.try_iter().collect()
+    }
+}
+
+/// The previous image, kept on screen (dimmed, with a spinner) while the next file
+/// loads so navigating never flashes an empty viewport.
+struct Placeholder {
+    rendered: Rendered,
+    /// Full-resolution size of the image it shows
+    size: egui::Vec2,
+}
+
+/// How the file browser orders and sections its list.
+#[derive(Clone, Copy, PartialEq)]
+enum GroupBy {
+    /// By name, in one list
+    None,
+    Filter,
+    Exposure,
+}
+
+impl GroupBy {
+    const ALL: [GroupBy; 3] = [GroupBy::None, GroupBy::Filter, GroupBy::Exposure];
+
+    fn label(self) -> &'static str {
+        match self {
+            GroupBy::None => "Name",
+            GroupBy::Filter => "Filter",
+            GroupBy::Exposure => "Exposure",
+        }
+    }
+}
+
+/// Page of the Preferences dialog.
+#[derive(Clone, Copy, PartialEq)]
+enum PrefsTab {
+    General,
+    Keyboard,
+}
+
+/// One-line message shown in the bottom toolbar.
+struct StatusMessage {
+    text: String,
+    /// Errors stay until dismissed; info messages fade after `STATUS_INFO_TIMEOUT`.
+    is_error: bool,
+    shown_at: Instant,
+}
+
 pub struct FastFitsApp {
     /// egui context, stored so background threads can call request_repaint()
     ctx: egui::Context,
 
     /// Directory being browsed
     current_dir: PathBuf,
-    /// Sorted list of FITS files in current_dir
+    /// Files were given explicitly on the command line: `files` is that list, in
+    /// that order, and the directory is neither watched nor remembered
+    playlist: bool,
+    /// Sorted list of FITS files in current_dir, or the explicit file list
     files: Vec<PathBuf>,
     /// Index into `files` of the currently selected file
     selected: Option<usize>,
+    /// Indices into `files` picked with Ctrl / Shift-click for a batch delete;
+    /// the displayed file stays `selected`, the last one clicked
+    marked: HashSet<usize>,
+    /// Header metadata of each browsed file, filled in by `header_scan` and kept
+    /// across directories
+    file_meta: HashMap<PathBuf, FileMeta>,
+    /// Header-only scan of the listed files not in `file_meta` yet
+    header_scan: Option<HeaderScan>,
+    /// Browser shows only the files of this frame type
+    type_filter: Option<FrameType>,
+    /// Sections of the browser list (and the order navigation follows)
+    group_by: GroupBy,
 
     /// Currently loaded image (None if nothing loaded yet or on error)
-    image: Option<FitsImage>,
-    /// Cached egui texture for the current image/stretch/view combo
-    texture: Option<TextureHandle>,
+    image: Option<Arc<FitsImage>>,
+    /// Rendering of the current image/stretch/view combo; None = needs rebuilding
+    texture: Option<Rendered>,
+    /// Downsampled copies of `texture` (½, ¼, …), rebuilt together with it
+    texture_mips: Vec<TextureHandle>,
+    placeholder: Option<Placeholder>,
+    /// `texture` no longer matches the display settings. It stays on screen until
+    /// its replacement is built, so a settings change never shows an empty frame.
+    texture_stale: bool,
+    /// Builds the CPU rendering when the GPU path is unavailable
+    renderer: RenderWorker,
+    /// Split-screen comparison: divider position as a fraction of the image width;
+    /// None = off. Left of it shows the current stretch, right of it true linear.
+    split: Option<f32>,
+    /// Measurement tool: Some while on, with up to two clicked points in
+    /// full-resolution pixel coordinates
+    measure: Option<Vec<egui::Pos2>>,
+    /// Photometry tool: Some while on, with the clicked aperture positions in
+    /// full-resolution pixel coordinates
+    photometry: Option<Vec<egui::Pos2>>,
+    /// Radial-profile tool: Some while on, with the clicked star in full-resolution
+    /// pixel coordinates once there is one
+    profile: Option<Option<egui::Pos2>>,
+    /// Aperture radius and inner / outer annulus radii, in pixels, shared by the
+    /// photometry and radial-profile tools
+    aperture: [f32; 3],
+    /// Linear rendering (with its mips) for the right side of the split, rebuilt
+    /// together with `texture`
+    compare_texture: Option<(TextureHandle, Vec<TextureHandle>)>,
+    /// Black/white clipping of the rendered texture, per displayed channel
+    clipping: Vec<Clipping>,
     /// Error message to show instead of an image
     load_error: Option<String>,
 
     /// Receiver for in-flight background load; None when idle
     load_rx: Option<mpsc::Receiver<LoadResult>>,
+    /// Recently decoded images, so stepping back and forth doesn't re-read files
+    cache: ImageCache,
+    /// How the current image was loaded (stage timings or cache hit), for the status bar
+    load_stats: Option<String>,
 
     /// Current stretch mode
     stretch: Stretch,
+    /// Low/high percentiles (fractions) used by the percentile-clipped linear stretch
+    linear_clip: (f32, f32),
+    /// Percentile (fraction) AutoStretch clips to white
+    white_clip: f32,
+    /// Knee `k` of the logarithmic stretch
+    log_knee: f32,
+    /// Histogram equalisation of RGB uses one luminance CDF instead of one per channel
+    histeq_shared: bool,
+    /// Screen transfer function used by AutoStretch, one per displayed plane.
+    /// None = recompute automatically (new image, channel view change, or reset).
+    stf: Option<Vec<Stf>>,
+    /// STF slider adjustments, kept across images and applied to each one's
+    /// automatic STF
+    stf_offset: StfOffset,
     /// Current channel view
     channel_view: ChannelView,
+    /// Colour adjustments (saturation) for composite RGB views
+    color: ColorOptions,
+    /// Display binning factor (1 = off); see `FitsImage::bin`
+    bin_factor: usize,
+    /// `image` as displayed when that differs from the loaded image (binned by
+    /// `bin_factor` and/or background-neutralised), computed on first render
+    derived: Option<Arc<FitsImage>>,
+    /// Subtract the per-channel sky background of RGB images before stretching
+    neutralize: bool,
+    /// The image of the file shown before the current one, with its path, kept
+    /// for the difference statistics and view
+    previous: Option<(PathBuf, Arc<FitsImage>)>,
+    /// File picked to compare against instead of `previous` (the second of two
+    /// selected files), kept while browsing until the comparison is turned off
+    reference: Option<(PathBuf, Arc<FitsImage>)>,
+    /// Background load of `reference`
+    reference_rx: Option<mpsc::Receiver<(PathBuf, Result<FitsImage, String>)>>,
+    /// Display the current image combined with the reference (or previous) one
+    /// instead of the image itself
+    compare: Option<Combine>,
+    /// Bumped whenever the displayed pixels change (new image, binning), so the
+    /// GPU copy knows when to re-upload
+    image_generation: u64,
+
+    /// Shader stretch, when the OpenGL painter supports it
+    gpu: Option<Arc<Mutex<GpuStretch>>>,
+    /// Use the GPU for AutoStretch / STF display when possible (Preferences)
+    use_gpu: bool,
+    /// Stretch range of every plane of the displayed image, for the GPU path
+    /// (keyed by `image_generation`)
+    plane_ranges: Option<(u64, Vec<(f32, f32)>)>,
+    /// Black/white points the GPU-path clipping readout was computed for
+    /// (NaN for flat planes), with the image generation
+    clip_points: Option<(u64, Vec<(f32, f32)>)>,
 
     /// Zoom: None = autofit, Some(s) = explicit scale factor
     zoom: Option<f32>,
+    /// Keep channel view and zoom when moving to another file (for blink comparison)
+    lock_view: bool,
+    /// Channel view colour images open in; restored per directory
+    preferred_view: ChannelView,
+    /// Command-line settings still in effect, kept out of the directory's saved
+    /// view until the user changes them
+    start_view: StartView,
+    /// Scale factor chosen by the last autofit layout (shown in the zoom entry while fitting)
+    fit_scale: f32,
 
-    /// Result of the last delete attempt (shown briefly in the status bar)
-    delete_status: Option<String>,
+    /// Last status message (delete failure, clipboard confirmation, ...)
+    status: Option<StatusMessage>,
     /// Whether the keyboard shortcuts help popup is open
     show_help: bool,
     /// Whether the Preferences dialog is open
     show_prefs: bool,
-    /// Demosaic algorithm for Bayer images
-    demosaic_mode: DemosaicMode,
+    /// Page shown in the Preferences dialog
+    prefs_tab: PrefsTab,
+    /// File extensions being edited in Preferences, applied when the field loses focus
+    extensions_edit: String,
+    /// Catalogue CSV path being edited in Preferences, applied when the field loses focus
+    catalog_edit: String,
+    /// Action waiting for a new key in the Preferences keyboard tab
+    rebinding: Option<Action>,
+    /// Persisted settings (key bindings)
+    config: Config,
+    /// Theme and system theme the current visuals were built for
+    applied_theme: Option<(Theme, Option<eframe::Theme>)>,
+    /// Theme to return to when night vision is switched off
+    day_theme: Theme,
+    /// Query typed in the "go to file" overlay; Some while it is open
+    goto: Option<String>,
+    /// Time of a first `g` press, waiting for the second one of vim's `gg`
+    vim_pending_g: Option<Instant>,
+    /// Whether the screen transfer function window is open
+    show_stf: bool,
+    /// Whether the PNG export dialog is open
+    show_export: bool,
+    /// Overlay the flagged pixels of the file's data-quality / mask HDU
+    show_mask: bool,
+    /// Mask HDU chosen in Preferences: 0-based index or EXTNAME (empty = DQ, then MASK)
+    mask_hdu: String,
+    /// Opacity of the mask overlay (0–1)
+    mask_opacity: f32,
+    /// Flagged pixels of the current file, while the overlay is on
+    mask_texture: Option<TextureHandle>,
+    /// Number of flagged pixels in `mask_texture`
+    mask_count: usize,
+    /// Mask being read in the background, taken once the full image is shown
+    mask_rx: Option<mpsc::Receiver<Result<Mask, String>>>,
+    /// Full-resolution image pixel under the mouse, for the value readout
+    cursor: Option<(usize, usize)>,
+    /// Whether the analysis window is open
+    show_analysis: bool,
+    /// Whether the file info (data format diagnostics) window is open
+    show_file_info: bool,
+    /// Whether the headers panel (left) is shown
+    show_headers: bool,
+    /// Whether the file browser panel (right) is shown
+    show_files: bool,
+    /// Whether the folder tree is shown above the file list
+    show_folders: bool,
+    /// Subdirectories of the folders expanded in the tree, read on first expansion
+    subdirs: HashMap<PathBuf, Vec<PathBuf>>,
+    /// File last displayed in each directory left this session (keyed like
+    /// `dir_views`), reselected on coming back
+    last_selected: HashMap<PathBuf, PathBuf>,
+    /// Raw statistics per Bayer site of the current image, computed when first shown
+    cfa_stats: Option<Vec<(String, PlaneStats)>>,
+    /// Background level and noise per plane of the displayed image (for
+    /// `image_generation`), computed when the analysis window is first shown
+    background: Option<(u64, Vec<BackgroundNoise>)>,
+    /// Statistics of the current minus the previous image per plane (for
+    /// `image_generation`; None when their sizes differ), computed when first shown
+    delta_stats: Option<(u64, Option<Vec<PlaneStats>>)>,
+    /// Whether the FWHM tilt map is drawn over the image
+    show_tilt: bool,
+    /// Whether a scale bar is drawn in the corner of the image area
+    show_scale_bar: bool,
+    /// Whether catalogue objects are marked on plate-solved images
+    show_catalog: bool,
+    /// Entries of the catalogue overlay, read when it is first shown
+    catalog: Option<Vec<CatalogEntry>>,
+    /// Whether the navigator is shown while the image is larger than the view
+    show_navigator: bool,
+    /// Scroll offset of the image view requested by the navigator, applied next frame
+    scroll_to: Option<egui::Vec2>,
+    /// Stars detected in the current image (for `image_generation`), found when
+    /// first needed
+    stars: Option<(u64, Arc<Vec<Star>>)>,
+    /// Star detection running in the background, with the `image_generation` it is for
+    stars_rx: Option<(u64, mpsc::Receiver<Vec<Star>>)>,
+    /// Share of near-full-scale pixels in the current image (see
+    /// [`FitsImage::saturated_fraction`]), measured on load
+    saturated: Option<f32>,
+    /// The current image as a spectrum, plotted instead of shown when it is
+    /// one-dimensional (see [`FitsImage::spectrum`])
+    spectrum: Option<Spectrum>,
+    /// Burn an OBJECT / FILTER / EXPTIME caption into exported PNGs
+    export_caption: bool,
+    /// Presentation mode: all panels hidden, image fills the window
+    fullscreen: bool,
+    /// Decode settings: demosaic algorithm, hot-pixel correction
+    load_options: LoadOptions,
+    /// Comma-separated header keywords shown in the capture summary bar
+    summary_keys: String,
+    /// Name of the stretch preset last applied, or being typed to save one
+    preset_name: String,
+    /// Whether the HUD is drawn over the top-left of the image
+    show_hud: bool,
+    /// Comma-separated lines of the HUD (see `DEFAULT_HUD_ITEMS`)
+    hud_items: String,
+    /// Normalisation ceiling forced from Preferences instead of the BITPIX-derived one
+    /// (Some(0.0) = use the data range)
+    ceiling_override: Option<f32>,
 
     /// Filename being loaded (shown in center panel while loading)
     loading_name: Option<String>,
+
+    /// System clipboard, created on first copy and kept alive afterwards
+    clipboard: Option<arboard::Clipboard>,
+
+    /// Watcher on `current_dir`; Some while watch mode (auto-reload) is on
+    watcher: Option<DirWatcher>,
+    /// Live-follow: jump to the newest file whenever the watcher sees one arrive
+    follow_latest: bool,
+    /// Hover previews of files in the browser, rendered on first hover
+    thumbnails: HashMap<PathBuf, Thumbnail>,
+    thumbnailer: ThumbnailWorker,
+    /// Modification time of the current file when it was selected, compared
+    /// against the file again whenever the window regains focus
+    loaded_mtime: Option<SystemTime>,
+    /// The current file was rewritten since it was loaded (shown as a badge)
+    changed_on_disk: bool,
+    /// A reload from disk is in progress; its completion is announced in the status bar
+    reloading: bool,
+    /// Window focus in the previous frame, to spot it being regained
+    was_focused: bool,
 }
 
 impl FastFitsApp {
-    pub fn new(_cc: &eframe::CreationContext<'_>, start_path: PathBuf) -> Self {
-        let (current_dir, selected, files) = if start_path.is_file() {
+    /// Open `paths`: one file (browsing its directory) or directory, or with
+    /// `file_list` exactly the files in `paths`, in that order.
+    pub fn new(
+        _cc: &eframe::CreationContext<'_>,
+        paths: Vec<PathBuf>,
+        file_list: bool,
+        start_view: StartView,
+    ) -> Self {
+        let (config, config_error) = match Config::load() {
+            Ok(config) => (config, None),
+            Err(e) => (Config::default(), Some(e)),
+        };
+        let start_path = paths.first().cloned().unwrap_or_default();
+        let (current_dir, selected, files) = if file_list {
+            let dir = start_path.parent().map(PathBuf::from).unwrap_or_default();
+            (dir, Some(0), paths)
+        } else if start_path.is_file() {
             let dir = start_path
                 .parent()
                 .unwrap_or(&start_path)
                 .to_path_buf();
-            let files = collect_fits_files(&dir);
+            let files = collect_fits_files(&dir, &config.file_filter);
             let selected = files.iter().position(|f| f == &start_path);
             (dir, selected, files)
         } else {
-            let files = collect_fits_files(&start_path);
+            let files = collect_fits_files(&start_path, &config.file_filter);
             let selected = if files.is_empty() { None } else { Some(0) };
             (start_path, selected, files)
         };
@@ -69,317 +668,2586 @@ impl FastFitsApp {
         let mut app = Self {
             ctx: _cc.egui_ctx.clone(),
             current_dir,
+            playlist: file_list,
             files,
-            selected,
+            marked: HashSet::new(),
+            file_meta: HashMap::new(),
+            header_scan: None,
+            type_filter: None,
+            group_by: GroupBy::None,
+            selected: None,
             image: None,
             texture: None,
+            texture_mips: Vec::new(),
+            placeholder: None,
+            texture_stale: false,
+            renderer: RenderWorker::start(_cc.egui_ctx.clone()),
+            split: None,
+            measure: None,
+            photometry: None,
+            profile: None,
+            aperture: DEFAULT_APERTURE,
+            compare_texture: None,
+            clipping: Vec::new(),
             load_error: None,
             load_rx: None,
-            stretch: Stretch::AutoStretch,
+            cache: ImageCache::new(DEFAULT_BUDGET_BYTES),
+            load_stats: None,
+            stretch: Stretch::AUTO,
+            linear_clip: DEFAULT_LINEAR_CLIP,
+            white_clip: DEFAULT_WHITE_CLIP,
+            log_knee: DEFAULT_LOG_KNEE,
+            histeq_shared: true,
+            stf: None,
+            stf_offset: StfOffset::default(),
             channel_view: ChannelView::Rgb,
+            color: ColorOptions::default(),
+            bin_factor: 1,
+            derived: None,
+            neutralize: false,
+            previous: None,
+            reference: None,
+            reference_rx: None,
+            compare: None,
+            image_generation: 0,
+            gpu: _cc.gl.as_ref().and_then(|gl| GpuStretch::new(gl)).map(|g| Arc::new(Mutex::new(g))),
+            use_gpu: true,
+            plane_ranges: None,
+            clip_points: None,
             zoom: None,
-            delete_status: None,
+            lock_view: false,
+            preferred_view: ChannelView::Rgb,
+            start_view: StartView::default(),
+            fit_scale: 1.0,
+            status: None,
             show_help: false,
             show_prefs: false,
-            demosaic_mode: DemosaicMode::Bilinear,
+            prefs_tab: PrefsTab::General,
+            extensions_edit: String::new(),
+            catalog_edit: String::new(),
+            rebinding: None,
+            config,
+            applied_theme: None,
+            day_theme: Theme::default(),
+            vim_pending_g: None,
+            goto: None,
+            show_stf: false,
+            show_export: false,
+            show_mask: false,
+            mask_hdu: String::new(),
+            mask_opacity: 0.5,
+            mask_texture: None,
+            mask_count: 0,
+            mask_rx: None,
+            cursor: None,
+            show_analysis: false,
+            show_file_info: false,
+            show_headers: true,
+            show_files: true,
+            show_folders: false,
+            subdirs: HashMap::new(),
+            last_selected: HashMap::new(),
+            loaded_mtime: None,
+            changed_on_disk: false,
+            reloading: false,
+            was_focused: true,
+            cfa_stats: None,
+            background: None,
+            delta_stats: None,
+            show_tilt: false,
+            show_scale_bar: false,
+            show_catalog: false,
+            catalog: None,
+            show_navigator: true,
+            scroll_to: None,
+            stars: None,
+            stars_rx: None,
+            saturated: None,
+            spectrum: None,
+            export_caption: false,
+            fullscreen: false,
+            load_options: LoadOptions::default(),
+            summary_keys: DEFAULT_SUMMARY_KEYS.to_string(),
+            preset_name: String::new(),
+            show_hud: false,
+            hud_items: DEFAULT_HUD_ITEMS.to_string(),
+            ceiling_override: None,
             loading_name: None,
+            clipboard: None,
+            watcher: None,
+            follow_latest: false,
+            thumbnails: HashMap::new(),
+            thumbnailer: ThumbnailWorker::start(_cc.egui_ctx.clone()),
         };
-        app.load_selected();
+        if let Some(e) = config_error {
+            app.set_error(format!("Settings not loaded, using defaults: {e:#}"));
+        }
+        app.restore_dir_view();
+        app.scan_headers();
+        if let Some(stretch) = start_view.stretch {
+            app.stretch = stretch;
+        }
+        if let Some(view) = start_view.channel {
+            app.preferred_view = view;
+        }
+        app.start_view = start_view;
+        // Start loading in the background so the window opens right away
+        if let Some(idx) = selected {
+            app.select(idx);
+        }
         app
     }
 
-    /// Load (or reload) the currently selected file.
-    fn load_selected(&mut self) {
+    /// Cycle stretch mode: Auto → Percentile-clipped linear → true Linear → raw
+    /// (full-scale) linear → Log → histogram equalisation → Auto.
+    fn cycle_stretch(&mut self) {
+        let (low, high) = self.linear_clip;
+        self.stretch = match self.stretch {
+            Stretch::AutoStretch { .. } => Stretch::Percentile { low, high },
+            Stretch::Percentile { .. } => Stretch::Linear,
+            Stretch::Linear => Stretch::RawLinear,
+            Stretch::RawLinear => Stretch::Log { k: self.log_knee },
+            Stretch::Log { .. } => Stretch::HistEq { shared: self.histeq_shared },
+            Stretch::HistEq { .. } => Stretch::AutoStretch { white_clip: self.white_clip },
+        };
+        self.texture_stale = true;
+    }
+
+    /// Make a freshly loaded image current, applying per-session display settings.
+    fn install_image(&mut self, mut img: FitsImage) {
+        // Reset channel view based on the new image's channel count, unless it is
+        // locked and still applies to the new image
+        let view_fits = match self.channel_view {
+            ChannelView::Rgb => img.channels >= 3,
+            ChannelView::Single(c) => c < img.channels,
+        };
+        if !(self.lock_view && view_fits) {
+            self.channel_view = match self.preferred_view {
+                ChannelView::Single(c) if img.channels >= 3 && c < img.channels => ChannelView::Single(c),
+                _ if img.channels >= 3 => ChannelView::Rgb,
+                _ => ChannelView::Single(0),
+            };
+        }
+        if let Some(ceiling) = self.ceiling_override {
+            img.bitdepth_max = ceiling;
+        }
+        self.stf = None;
         self.texture = None;
-        self.load_error = None;
-        self.image = None;
+        self.renderer.cancel(RenderKind::Main);
+        self.invalidate_compare();
+        self.derived = None;
+        self.image_generation += 1;
+        self.cfa_stats = None;
+        self.saturated = img.saturated_fraction();
+        self.spectrum = img.spectrum();
+        let preview = img.decimation > 1;
+        self.image = Some(Arc::new(img));
+        if self.compare.is_some() && !preview {
+            self.check_comparable();
+        }
+    }
 
-        let Some(idx) = self.selected else { return };
-        let Some(path) = self.files.get(idx).cloned() else { return };
+    /// Where the PNG export of the current file goes: next to it, `.png` extension.
+    fn export_path(&self) -> Option<PathBuf> {
+        let path = self.files.get(self.selected?)?;
+        Some(path.with_extension("png"))
+    }
 
-        match FitsImage::load(&path, self.demosaic_mode) {
-            Ok(img) => {
-                // Reset channel view based on the new image's channel count
-                self.channel_view = if img.channels >= 3 {
-                    ChannelView::Rgb
-                } else {
-                    ChannelView::Single(0)
-                };
-                self.image = Some(img);
-            }
-            Err(e) => {
-                self.load_error = Some(format!("{e:#}"));
-            }
+    /// Save the displayed image (native resolution, current stretch/view) as a PNG,
+    /// optionally with a caption of key header values.
+    fn export_png(&mut self) {
+        let Some(path) = self.export_path() else { return };
+        let Some(mut rgba) = self.render_rgba(self.color) else { return };
+        let Some(img) = self.displayed_image() else { return };
+        let (width, height) = (img.width, img.height);
+        if self.export_caption {
+            let lines: Vec<String> = CAPTION_KEYS
+                .iter()
+                .filter_map(|&k| img.header(k).filter(|v| !v.is_empty()).map(|v| capture_field(k, v)))
+                .collect();
+            draw_caption(&mut rgba, width, height, &lines);
+        }
+        match write_png(&path, width, height, &rgba) {
+            Ok(()) => self.set_info(format!("Exported {}", path.display())),
+            Err(e) => self.set_error(format!("Export failed: {e:#}")),
         }
     }
 
-    /// Rebuild the egui texture from the current image + stretch + channel_view.
-    fn rebuild_texture(&mut self, ctx: &egui::Context) {
-        let Some(img) = &self.image else { return };
-        let rgba = img.to_rgba(self.stretch, self.channel_view);
-        let color_image = egui::ColorImage::from_rgba_unmultiplied(
-            [img.width, img.height],
-            &rgba,
-        );
-        self.texture = Some(ctx.load_texture(
-            "fits_image",
-            color_image,
-            egui::TextureOptions::LINEAR,
-        ));
+    /// Cycle display binning: off → 2×2 → 3×3 → off.
+    fn cycle_binning(&mut self) {
+        self.bin_factor = match self.bin_factor {
+            1 => 2,
+            2 => 3,
+            _ => 1,
+        };
+        self.derived = None;
+        self.image_generation += 1;
+        self.stf = None;
+        self.texture_stale = true;
     }
 
-    fn select(&mut self, idx: usize) {
-        if self.selected == Some(idx) { return; }
-        self.selected = Some(idx);
-        self.zoom = None;
-        self.image = None;
-        self.texture = None;
-        self.load_error = None;
-        self.load_rx = None; // drop any in-flight load
-
-        self.loading_name = self.files.get(idx)
-            .and_then(|p| p.file_name())
-            .map(|n| n.to_string_lossy().into_owned());
+    /// Status-bar text for the pixel at full-resolution (`x`, `y`): its displayed value
+    /// per channel and, for Bayer images, the raw sensor value.
+    fn pixel_readout(&self, x: usize, y: usize) -> Option<String> {
+        let values = self.displayed_image()?.pixel(x, y)?;
+        let mut s = format!("x {x} y {y}  ");
+        s += &match values.as_slice() {
+            [v] => format_value(*v),
+            [r, g, b, ..] => format!("R {} G {} B {}", format_value(*r), format_value(*g), format_value(*b)),
+            _ => String::new(),
+        };
+        if let Some((raw, site)) = self.image.as_ref().and_then(|img| img.raw_pixel(x, y)) {
+            s += &format!("  raw {} ({site})", format_value(raw));
+        }
+        if let Some((_, background)) = self.background.as_ref().filter(|(g, _)| *g == self.image_generation) {
+            let snr: Vec<String> =
+                background.iter().zip(&values).map(|(bg, &v)| format!("{:.1}", bg.snr(v))).collect();
+            s += &format!("  SNR {}", snr.join("/"));
+        }
+        Some(s)
+    }
 
-        let Some(path) = self.files.get(idx).cloned() else { return };
+    /// (Re)load the mask overlay of the selected file in the background when the
+    /// overlay is on.
+    fn refresh_mask(&mut self) {
+        self.mask_texture = None;
+        self.mask_rx = None;
+        if !self.show_mask {
+            return;
+        }
+        let Some(path) = self.selected.and_then(|i| self.files.get(i)).cloned() else { return };
         let (tx, rx) = mpsc::channel();
-        self.load_rx = Some(rx);
-
+        self.mask_rx = Some(rx);
         let ctx = self.ctx.clone();
-        let demosaic = self.demosaic_mode;
+        let hdu = self.mask_hdu.clone();
         std::thread::spawn(move || {
-            let result = match FitsImage::load(&path, demosaic) {
-                Ok(img) => LoadResult::Ok(Box::new(img)),
-                Err(e) => LoadResult::Err(format!("{e:#}")),
-            };
-            let _ = tx.send(result);
+            let _ = tx.send(Mask::load(&path, &hdu).map_err(|e| format!("{e:#}")));
             ctx.request_repaint();
         });
     }
 
-    fn select_next(&mut self) {
-        if self.files.is_empty() { return; }
-        let next = self.selected.map(|i| (i + 1) % self.files.len()).unwrap_or(0);
-        self.select(next);
+    /// Show a loaded mask over the full-resolution `image`, if it has the image's size.
+    fn install_mask(&mut self, mask: Mask) {
+        let Some(img) = self.image.as_deref() else { return };
+        if (mask.width, mask.height) != (img.width, img.height) {
+            let text = format!("Mask: {}×{} does not match the {}×{} image", mask.width, mask.height, img.width, img.height);
+            self.set_error(text);
+            return;
+        }
+        let pixels = mask
+            .flagged
+            .iter()
+            .map(|&f| if f { MASK_COLOR } else { egui::Color32::TRANSPARENT })
+            .collect();
+        let image = egui::ColorImage { size: [mask.width, mask.height], pixels };
+        // Crisp pixel edges when zoomed in
+        let options = egui::TextureOptions {
+            magnification: egui::TextureFilter::Nearest,
+            ..egui::TextureOptions::LINEAR
+        };
+        self.mask_count = mask.count();
+        self.mask_texture = Some(self.ctx.load_texture("mask_overlay", image, options));
     }
 
-    fn select_prev(&mut self) {
-        if self.files.is_empty() { return; }
-        let prev = self.selected.map(|i| {
-            if i == 0 { self.files.len() - 1 } else { i - 1 }
-        }).unwrap_or(0);
-        self.select(prev);
+    /// Build the derived display copy of `image` if binning or background
+    /// neutralisation is on and it is not there yet.
+    fn ensure_derived(&mut self) {
+        if self.derived.is_some() {
+            return;
+        }
+        let Some(img) = self.image.as_deref() else { return };
+        let combined = self
+            .compare
+            .zip(self.comparison_base())
+            .and_then(|(how, (_, base))| img.combine(base, how));
+        let img = combined.as_ref().unwrap_or(img);
+        let neutralize = self.neutralize && img.channels == 3;
+        if self.bin_factor == 1 && !neutralize {
+            self.derived = combined.map(Arc::new);
+            return;
+        }
+        let binned = (self.bin_factor > 1).then(|| img.bin(self.bin_factor));
+        let source = binned.as_ref().unwrap_or(img);
+        self.derived = if neutralize { Some(source.neutralize_background()) } else { binned }.map(Arc::new);
     }
 
-    /// Delete the currently selected file (trash if available, else permanent).
-    /// Auto-advances to the next file.
-    fn delete_selected(&mut self) {
-        let Some(idx) = self.selected else { return };
-        let Some(path) = self.files.get(idx).cloned() else { return };
+    /// Toggle background neutralisation of RGB images.
+    fn toggle_neutralize(&mut self) {
+        self.neutralize = !self.neutralize;
+        self.derived = None;
+        self.image_generation += 1;
+        self.stf = None;
+        self.texture_stale = true;
+    }
 
-        let result: Result<(), String> = trash::delete(&path)
-            .map_err(|e| e.to_string())
-            .or_else(|_| std::fs::remove_file(&path).map_err(|e| e.to_string()));
+    /// The image the current one is compared with: the picked reference, else
+    /// the previously viewed file.
+    fn comparison_base(&self) -> Option<&(PathBuf, Arc<FitsImage>)> {
+        self.reference.as_ref().or(self.previous.as_ref())
+    }
 
-        match result {
-            Ok(()) => {
-                self.files.remove(idx);
-                self.image = None;
-                self.texture = None;
-                self.load_error = None;
-                self.delete_status = None;
-                if self.files.is_empty() {
-                    self.selected = None;
-                } else {
-                    // Stay at same index (now pointing to next file), or step back at end
-                    let new_idx = idx.min(self.files.len() - 1);
-                    self.selected = Some(new_idx);
-                    self.load_selected();
-                }
-            }
-            Err(e) => {
-                self.delete_status = Some(format!("Delete failed: {e}"));
-            }
+    /// Cycle the comparison view: off → difference → ratio → off. With exactly
+    /// two files selected, they are compared with each other.
+    fn cycle_compare(&mut self) {
+        let next = match self.compare {
+            None => Some(Combine::Difference),
+            Some(Combine::Difference) => Some(Combine::Ratio),
+            Some(Combine::Ratio) => None,
+        };
+        match next {
+            Some(how) if self.marked.len() == 2 && self.reference.is_none() => self.compare_marked(how),
+            _ => self.set_compare(next),
         }
     }
-    /// Reload the current image (e.g. after a settings change like demosaic mode).
-    fn reload_image(&mut self) {
-        self.image = None;
-        self.texture = None;
-        self.load_rx = None;
-        if let Some(idx) = self.selected {
-            self.selected = None;
-            self.select(idx);
+
+    /// Switch the comparison view; turning it off forgets the picked reference.
+    fn set_compare(&mut self, compare: Option<Combine>) {
+        self.compare = compare;
+        if compare.is_none() {
+            self.reference = None;
+            self.reference_rx = None;
+        } else if self.reference_rx.is_none() {
+            self.check_comparable();
         }
+        self.derived = None;
+        self.image_generation += 1;
+        self.stf = None;
+        self.texture_stale = true;
     }
-}
 
-impl eframe::App for FastFitsApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Poll background load result
-        if let Some(rx) = &self.load_rx {
-            if let Ok(result) = rx.try_recv() {
-                self.load_rx = None;
-                self.loading_name = None;
-                match result {
-                    LoadResult::Ok(img) => {
-                        self.channel_view = if img.channels >= 3 {
-                            ChannelView::Rgb
-                        } else {
-                            ChannelView::Single(0)
-                        };
-                        self.image = Some(*img);
-                    }
-                    LoadResult::Err(e) => {
-                        self.load_error = Some(e);
-                    }
+    /// Compare the two selected files: the displayed one (or the first) with the other.
+    fn compare_marked(&mut self, how: Combine) {
+        let mut pair: Vec<usize> = self.marked.iter().copied().filter(|&i| i < self.files.len()).collect();
+        pair.sort_unstable();
+        let [first, second] = pair[..] else {
+            self.set_info("Select two files (Ctrl-click) to compare them");
+            return;
+        };
+        let (a, b) = if self.selected == Some(second) { (second, first) } else { (first, second) };
+        let path = self.files[b].clone();
+        self.reference = None;
+        if let Some(img) = self.cache.get(&path, self.load_options) {
+            self.reference = Some((path, Arc::new(img)));
+            self.reference_rx = None;
+        } else {
+            let (tx, rx) = mpsc::channel();
+            self.reference_rx = Some(rx);
+            let (ctx, opts) = (self.ctx.clone(), self.load_options);
+            std::thread::spawn(move || {
+                let result = FitsImage::load(&path, opts).map_err(|e| format!("{e:#}"));
+                let _ = tx.send((path, result));
+                ctx.request_repaint();
+            });
+        }
+        self.select(a);
+        self.set_compare(Some(how));
+    }
+
+    /// Report an error when the current image can't be compared with its base.
+    fn check_comparable(&mut self) {
+        let problem = match (self.comparison_base(), &self.image) {
+            (None, _) => Some("No previous file to compare with".to_string()),
+            (Some((path, base)), Some(img)) if !img.same_shape(base) => Some(format!(
+                "Can't compare: {} is {}×{}×{}, the current file {}×{}×{}",
+                path.file_name().unwrap_or_default().to_string_lossy(),
+                base.width * base.binning,
+                base.height * base.binning,
+                base.channels,
+                img.width * img.binning,
+                img.height * img.binning,
+                img.channels,
+            )),
+            _ => None,
+        };
+        if let Some(text) = problem {
+            self.set_error(text);
+        }
+    }
+
+    /// Show or hide the catalogue overlay, reading the catalogue the first time.
+    fn toggle_catalog(&mut self) {
+        self.show_catalog = !self.show_catalog;
+        if !self.show_catalog {
+            return;
+        }
+        if self.catalog.is_none() {
+            let entries = match &self.config.catalog {
+                Some(path) => catalog::load(path),
+                None => Ok(catalog::bundled()),
+            };
+            match entries {
+                Ok(entries) => self.catalog = Some(entries),
+                Err(e) => {
+                    self.show_catalog = false;
+                    self.set_error(format!("Catalogue: {e:#}"));
+                    return;
                 }
             }
         }
+        if self.image.as_ref().is_some_and(|img| img.wcs().is_none()) {
+            self.set_info("No plate solution (WCS) in this file");
+        }
+    }
 
-        // Keyboard shortcuts
-        ctx.input(|i| {
-            use egui::Key;
-            if i.key_pressed(Key::ArrowRight) || i.key_pressed(Key::ArrowDown) {
-                // handled below after borrow ends
-            }
-            if i.key_pressed(Key::ArrowLeft) || i.key_pressed(Key::ArrowUp) {
-                // handled below
-            }
-        });
-        // Re-check in a non-borrowing way
-        let go_next = ctx.input(|i| {
-            i.key_pressed(egui::Key::ArrowRight) || i.key_pressed(egui::Key::ArrowDown)
+    /// The image as displayed: the binned / neutralised copy when one is on, else the loaded image.
+    fn displayed_image(&self) -> Option<&FitsImage> {
+        self.derived.as_deref().or(self.image.as_deref())
+    }
+
+    /// Rebuild the egui texture from the current image + stretch + channel_view on
+    /// the render worker. The previous texture stays on screen meanwhile; a newer
+    /// request supersedes one still in flight. The result is picked up in `update`.
+    fn start_render(&mut self) {
+        self.ensure_derived();
+        let Some(img) = self.derived.clone().or_else(|| self.image.clone()) else { return };
+        self.renderer.submit(RenderJob {
+            kind: RenderKind::Main,
+            img,
+            stretch: self.stretch,
+            view: self.channel_view,
+            color: self.display_color(),
+            white_clip: self.white_clip,
+            stf: self.stf.clone(),
+            stf_offset: self.stf_offset,
+            generation: self.image_generation,
         });
-        let go_prev = ctx.input(|i| {
-            i.key_pressed(egui::Key::ArrowLeft) || i.key_pressed(egui::Key::ArrowUp)
+    }
+
+    /// Build the true-linear rendering shown right of the split-screen divider.
+    /// Like [`Self::start_render`], on the render worker; picked up in `update`.
+    fn start_compare_render(&mut self) {
+        self.ensure_derived();
+        let Some(img) = self.derived.clone().or_else(|| self.image.clone()) else { return };
+        self.renderer.submit(RenderJob {
+            kind: RenderKind::Compare,
+            img,
+            stretch: Stretch::Linear,
+            view: self.channel_view,
+            color: self.display_color(),
+            white_clip: self.white_clip,
+            stf: None,
+            stf_offset: StfOffset::default(),
+            generation: self.image_generation,
         });
-        let toggle_stretch = ctx.input(|i| i.key_pressed(egui::Key::S));
-        let zoom_in = ctx.input(|i| i.key_pressed(egui::Key::Plus) || i.key_pressed(egui::Key::Equals));
-        let zoom_out = ctx.input(|i| i.key_pressed(egui::Key::Minus));
-        let zoom_reset = ctx.input(|i| i.key_pressed(egui::Key::Num0));
-        let zoom_fit = ctx.input(|i| i.key_pressed(egui::Key::F));
-        let do_delete = ctx.input(|i| i.key_pressed(egui::Key::Delete));
-        let toggle_help = ctx.input(|i| i.key_pressed(egui::Key::Questionmark));
-        let toggle_prefs = ctx.input(|i| i.key_pressed(egui::Key::Comma));
-        let close_popup = ctx.input(|i| i.key_pressed(egui::Key::Escape));
+    }
 
-        let mut go_next_btn = false;
-        let mut go_prev_btn = false;
-        let mut do_delete_btn = false;
+    /// Drop the split-screen comparison rendering (and one in progress), so it
+    /// is rebuilt with the current settings.
+    fn invalidate_compare(&mut self) {
+        self.compare_texture = None;
+        self.renderer.cancel(RenderKind::Compare);
+    }
 
+    /// Key of the current directory in the per-directory view settings.
+    fn dir_key(&self) -> PathBuf {
+        std::fs::canonicalize(&self.current_dir).unwrap_or_else(|_| self.current_dir.clone())
+    }
 
-        if go_next { self.select_next(); }
-        if go_prev { self.select_prev(); }
-        if toggle_stretch {
-            self.stretch = match self.stretch {
-                Stretch::AutoStretch => Stretch::Linear,
-                Stretch::Linear => Stretch::AutoStretch,
-            };
-            self.texture = None;
+    /// Apply the stretch, channel view and colour settings last used in this
+    /// directory, or the defaults in a directory not seen before.
+    fn restore_dir_view(&mut self) {
+        if self.playlist {
+            return;
         }
-        if zoom_in {
-            let s = self.zoom.unwrap_or(1.0);
-            self.zoom = Some((s * 1.25).min(32.0));
+        let view = self.config.dir_views.get(&self.dir_key()).cloned().unwrap_or_default();
+        self.stretch = self.stretch_from_id(&view.stretch);
+        self.preferred_view = view.channel.map_or(ChannelView::Rgb, ChannelView::Single);
+        self.color.palette = match view.palette.as_str() {
+            "sho" => Palette::Sho,
+            "hoo" => Palette::Hoo,
+            _ => Palette::Rgb,
+        };
+        self.color.saturation = clamp_to(view.saturation, SATURATION_RANGE);
+    }
+
+    /// The stretch mode named `id` (see [`stretch_id`]) with the current
+    /// parameters; AutoStretch for unknown names.
+    fn stretch_from_id(&self, id: &str) -> Stretch {
+        let (low, high) = self.linear_clip;
+        match id {
+            "percentile" => Stretch::Percentile { low, high },
+            "linear" => Stretch::Linear,
+            "raw" => Stretch::RawLinear,
+            "log" => Stretch::Log { k: self.log_knee },
+            "equalised" => Stretch::HistEq { shared: self.histeq_shared },
+            _ => Stretch::AutoStretch { white_clip: self.white_clip },
         }
-        if zoom_out {
-            let s = self.zoom.unwrap_or(1.0);
-            self.zoom = Some((s / 1.25).max(0.05));
+    }
+
+    /// The current stretch and colour settings, to save as a preset.
+    fn current_preset(&self) -> StretchPreset {
+        StretchPreset {
+            stretch: stretch_id(self.stretch).to_string(),
+            white_clip: self.white_clip,
+            linear_clip: self.linear_clip,
+            log_knee: self.log_knee,
+            histeq_shared: self.histeq_shared,
+            background: self.color.background,
+            saturation: self.color.saturation,
+            linked: self.color.linked,
+            srgb: self.color.srgb,
+            stf_offset: self.stf_offset,
         }
-        if zoom_reset {
-            self.zoom = Some(1.0);
+    }
+
+    /// Switch to the settings of the saved preset `name` and re-render.
+    fn apply_preset(&mut self, name: &str) {
+        let Some(preset) = self.config.presets.get(name).cloned() else { return };
+        // The config file may have been edited by hand: keep to the ranges of the controls
+        self.white_clip = clamp_to(preset.white_clip, WHITE_CLIP_RANGE);
+        self.linear_clip = (
+            clamp_to(preset.linear_clip.0, LINEAR_CLIP_LOW_RANGE),
+            clamp_to(preset.linear_clip.1, LINEAR_CLIP_HIGH_RANGE),
+        );
+        self.log_knee = clamp_to(preset.log_knee, LOG_KNEE_RANGE);
+        self.histeq_shared = preset.histeq_shared;
+        self.color.background = preset.background.map(|b| clamp_to(b, BACKGROUND_RANGE));
+        self.color.saturation = clamp_to(preset.saturation, SATURATION_RANGE);
+        self.color.linked = preset.linked;
+        self.color.srgb = preset.srgb;
+        let offset = preset.stf_offset;
+        self.stf_offset = StfOffset {
+            shadows: offset.shadows.clamp(-1.0, 1.0),
+            midtones: offset.midtones.clamp(-1.0, 1.0),
+            highlights: offset.highlights.clamp(-1.0, 1.0),
+        };
+        self.stretch = self.stretch_from_id(&preset.stretch);
+        self.stf = None;
+        self.texture_stale = true;
+        self.invalidate_compare();
+        self.preset_name = name.to_string();
+        self.set_info(format!("Applied preset {name}"));
+    }
+
+    /// Apply the saved preset after the one last applied, in name order.
+    fn next_preset(&mut self) {
+        let names: Vec<&String> = self.config.presets.keys().collect();
+        let Some(&first) = names.first() else {
+            self.set_info("No stretch presets saved (see Preferences)");
+            return;
+        };
+        let next = names.iter().position(|n| **n == self.preset_name).and_then(|i| names.get(i + 1));
+        let name = next.copied().unwrap_or(first).clone();
+        self.apply_preset(&name);
+    }
+
+    /// Store the current view settings for this directory (dropping the entry when
+    /// they are the defaults) and save the config if that changed anything.
+    fn remember_dir_view(&mut self) -> anyhow::Result<()> {
+        if self.playlist {
+            return Ok(());
         }
-        if zoom_fit {
-            self.zoom = None;
+        if self.image.as_ref().is_some_and(|img| img.channels >= 3) {
+            self.preferred_view = self.channel_view;
         }
-        if do_delete {
-            self.delete_selected();
+        let mut view = DirView {
+            stretch: stretch_id(self.stretch).to_string(),
+            channel: match self.preferred_view {
+                ChannelView::Rgb => None,
+                ChannelView::Single(c) => Some(c),
+            },
+            palette: match self.color.palette {
+                Palette::Rgb => "rgb",
+                Palette::Sho => "sho",
+                Palette::Hoo => "hoo",
+            }
+            .to_string(),
+            saturation: self.color.saturation,
+        };
+        let key = self.dir_key();
+        let saved = self.config.dir_views.get(&key).cloned().unwrap_or_default();
+        if self.start_view.stretch.is_some() {
+            view.stretch = saved.stretch.clone();
         }
-        if toggle_help {
-            self.show_help = !self.show_help;
+        if self.start_view.channel.is_some() {
+            view.channel = saved.channel;
         }
-        if toggle_prefs {
-            self.show_prefs = !self.show_prefs;
+        if saved == view {
+            return Ok(());
         }
-        if close_popup {
-            self.show_help = false;
-            self.show_prefs = false;
+        if view == DirView::default() {
+            self.config.dir_views.remove(&key);
+        } else {
+            self.config.dir_views.insert(key, view);
         }
+        self.config.save()
+    }
 
-        // Help popup
+    /// Colour options for the on-screen image: the user's adjustments, rendered
+    /// red-only in night vision. Exports and the clipboard use `self.color`.
+    fn display_color(&self) -> ColorOptions {
+        ColorOptions { night: self.config.theme == Theme::Night, ..self.color }
+    }
+
+    /// Switch night vision on (remembering the current theme) or back off.
+    fn toggle_night_vision(&mut self) {
+        self.config.theme = if self.config.theme == Theme::Night {
+            self.day_theme
+        } else {
+            self.day_theme = self.config.theme;
+            Theme::Night
+        };
+        self.texture_stale = true;
+        self.invalidate_compare();
+        self.ctx.request_repaint();
+        if let Err(e) = self.config.save() {
+            self.set_error(format!("Saving settings failed: {e:#}"));
+        }
+    }
+
+    /// Cycle the channel view of a colour image: R → G → B → RGB → R.
+    /// Greyscale images have only one view.
+    fn cycle_channel(&mut self) {
+        let Some(channels) = self.image.as_ref().map(|img| img.channels).filter(|&c| c >= 3) else {
+            return;
+        };
+        self.channel_view = match self.channel_view {
+            ChannelView::Single(c) if c + 1 < channels => ChannelView::Single(c + 1),
+            ChannelView::Single(_) => ChannelView::Rgb,
+            ChannelView::Rgb => ChannelView::Single(0),
+        };
+        self.stf = None;
+        self.texture_stale = true;
+    }
+
+    /// Stars of the current full-resolution image. The first call starts detecting
+    /// them on a worker thread and returns None until they arrive.
+    fn stars(&mut self) -> Option<Arc<Vec<Star>>> {
+        let img = self.image.as_ref().filter(|img| img.decimation == 1)?;
+        let generation = self.image_generation;
+        if let Some((_, stars)) = self.stars.as_ref().filter(|(g, _)| *g == generation) {
+            return Some(Arc::clone(stars));
+        }
+        if self.stars_rx.as_ref().map(|(g, _)| *g) != Some(generation) {
+            let (tx, rx) = mpsc::channel();
+            self.stars_rx = Some((generation, rx));
+            let (img, ctx) = (Arc::clone(img), self.ctx.clone());
+            std::thread::spawn(move || {
+                let _ = tx.send(img.detect_stars());
+                ctx.request_repaint();
+            });
+        }
+        None
+    }
+
+    /// Turn off the tools that take clicks on the image; only one is on at a time.
+    fn stop_click_tools(&mut self) {
+        self.measure = None;
+        self.photometry = None;
+        self.profile = None;
+    }
+
+    /// Turn the measurement tool on or off.
+    fn toggle_measure(&mut self) {
+        let was_on = self.measure.is_some();
+        self.stop_click_tools();
+        if !was_on {
+            self.measure = Some(Vec::new());
+            self.set_info("Measure: click two points on the image");
+        }
+    }
+
+    /// Turn the photometry tool on or off.
+    fn toggle_photometry(&mut self) {
+        let was_on = self.photometry.is_some();
+        self.stop_click_tools();
+        if !was_on {
+            self.photometry = Some(Vec::new());
+            self.set_info("Photometry: click stars to place apertures");
+        }
+    }
+
+    /// Turn the radial-profile tool on or off.
+    fn toggle_profile(&mut self) {
+        let was_on = self.profile.is_some();
+        self.stop_click_tools();
+        if !was_on {
+            self.profile = Some(None);
+            self.set_info("Radial profile: click a star");
+        }
+    }
+
+    /// Turn the split-screen stretch comparison on (divider in the middle) or off.
+    fn toggle_split(&mut self) {
+        self.split = if self.split.is_some() { None } else { Some(0.5) };
+        self.invalidate_compare();
+    }
+
+    /// Prepare the GPU stretch of the displayed image, uploading its planes when they
+    /// changed. None when the GPU path does not apply: a stretch other than AutoStretch,
+    /// colour adjustments on the composite view, night vision (rendered red-only on
+    /// the CPU), sRGB output gamma, or an image too large for a texture.
+    fn gpu_view(&mut self, gl: &glow::Context) -> Option<GpuView> {
+        let gpu = self.gpu.clone()?;
+        let adjusted = self.channel_view == ChannelView::Rgb && self.color != ColorOptions::default();
+        let night = self.display_color().night;
+        if !self.use_gpu
+            || !matches!(self.stretch, Stretch::AutoStretch { .. })
+            || adjusted
+            || night
+            || self.color.srgb
+        {
+            return None;
+        }
+        self.ensure_derived();
+        let img = self.derived.as_ref().or(self.image.as_ref())?;
+        let mut gpu = gpu.lock().ok()?;
+        if !gpu.supports(img) {
+            return None;
+        }
+        let generation = self.image_generation;
+        gpu.upload(gl, img, generation);
+        if self.plane_ranges.as_ref().map(|(g, _)| *g) != Some(generation) {
+            let ranges = (0..img.channels).map(|c| img.plane_range(img.plane(c))).collect();
+            self.plane_ranges = Some((generation, ranges));
+        }
+        let ranges = &self.plane_ranges.as_ref()?.1;
+        let stf = self
+            .stf
+            .get_or_insert_with(|| adjusted_stf(img, self.channel_view, self.white_clip, self.color, self.stf_offset));
+
+        let planes = img.display_plane_indices(self.channel_view);
+        let mut view = GpuView { planes: [planes[0]; 3], curves: [None; 3] };
+        for i in 0..3 {
+            let k = i.min(planes.len() - 1);
+            let params = stf.get(k).or(stf.first()).copied().unwrap_or(Stf::IDENTITY);
+            let (min, max) = ranges[planes[k]];
+            view.planes[i] = planes[k];
+            view.curves[i] = params.curve(min, max, img.bitdepth_max);
+        }
+
+        // The clipping readout only depends on the black / white points, so midtone
+        // changes skip the pass over the pixels.
+        let points: Vec<_> = view.curves[..planes.len()]
+            .iter()
+            .map(|c| c.map_or((f32::NAN, f32::NAN), |c| (c.black, c.white)))
+            .collect();
+        if self.clip_points.as_ref() != Some(&(generation, points.clone())) {
+            self.clipping = planes
+                .iter()
+                .zip(&view.curves)
+                .map(|(&p, c)| c.map_or(Clipping { black: 0.0, white: 0.0 }, |c| c.clipping(img.plane(p))))
+                .collect();
+            self.clip_points = Some((generation, points));
+        }
+        Some(view)
+    }
+
+    fn select(&mut self, idx: usize) {
+        if self.selected == Some(idx) { return; }
+        let shown = self.selected.and_then(|i| self.files.get(i)).cloned();
+        self.selected = Some(idx);
+        self.reloading = false;
+        if !self.lock_view {
+            self.zoom = None;
+        }
+        self.keep_as_placeholder();
+        if let (Some(path), Some(img)) = (shown, self.image.take()) {
+            // Previews are decimated and can't be compared pixel by pixel
+            if img.decimation == 1 {
+                self.previous = Some((path, img));
+            }
+        }
+        self.image = None;
+        self.texture = None;
+        self.load_error = None;
+        self.load_rx = None; // drop any in-flight load
+        self.renderer.cancel(RenderKind::Main);
+        self.invalidate_compare();
+        self.load_stats = None;
+
+        self.loading_name = self.files.get(idx)
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().into_owned());
+
+        let Some(path) = self.files.get(idx).cloned() else { return };
+        self.loaded_mtime = path.metadata().and_then(|m| m.modified()).ok();
+        self.changed_on_disk = false;
+        self.refresh_mask();
+        if let Some(img) = self.cache.get(&path, self.load_options) {
+            self.loading_name = None;
+            self.load_stats = Some("loaded from cache".to_string());
+            self.install_image(img);
+            return;
+        }
+        let (tx, rx) = mpsc::channel();
+        self.load_rx = Some(rx);
+
+        let ctx = self.ctx.clone();
+        let opts = self.load_options;
+        std::thread::spawn(move || {
+            let preview_tx = tx.clone();
+            let preview_ctx = ctx.clone();
+            let on_preview = move |img| {
+                let _ = preview_tx.send(LoadResult::Preview(Box::new(img)));
+                preview_ctx.request_repaint();
+            };
+            let result = match FitsImage::load_progressive(&path, opts, on_preview) {
+                Ok((img, timings)) => LoadResult::Ok(Box::new(img), timings),
+                Err(e) => LoadResult::Err(format!("{e:#}")),
+            };
+            let _ = tx.send(result);
+            ctx.request_repaint();
+        });
+    }
+
+    /// Move the displayed image into `placeholder`. During rapid navigation the
+    /// last image actually shown stays there until a new one is ready.
+    fn keep_as_placeholder(&mut self) {
+        let size = self.displayed_image().map(|img| {
+            let reduction = (img.decimation * img.binning) as f32;
+            egui::vec2(img.width as f32, img.height as f32) * reduction
+        });
+        if let (Some(rendered), Some(size)) = (self.texture.take(), size) {
+            self.placeholder = Some(Placeholder { rendered, size });
+        }
+    }
+
+    /// Step to the next file; at the last one this wraps to the first, or stays
+    /// put with a status message when wrapping is turned off in Preferences.
+    fn select_next(&mut self) {
+        let order = self.shown_files();
+        if order.is_empty() { return; }
+        let pos = self.selected.and_then(|s| order.iter().position(|&i| i == s));
+        if pos == Some(order.len() - 1) && !self.config.wrap_navigation {
+            self.set_info("End of list");
+            return;
+        }
+        let next = pos.map(|p| (p + 1) % order.len()).unwrap_or(0);
+        self.select(order[next]);
+    }
+
+    fn select_prev(&mut self) {
+        let order = self.shown_files();
+        if order.is_empty() { return; }
+        let pos = self.selected.and_then(|s| order.iter().position(|&i| i == s));
+        if pos == Some(0) && !self.config.wrap_navigation {
+            self.set_info("Start of list");
+            return;
+        }
+        let prev = pos.map(|p| {
+            if p == 0 { order.len() - 1 } else { p - 1 }
+        }).unwrap_or(0);
+        self.select(order[prev]);
+    }
+
+    /// Indices into `files` listed in the browser, in browser order: those of
+    /// the frame type being filtered for (files not scanned yet are left out),
+    /// else all of them, grouped. Navigation steps through this list.
+    fn shown_files(&self) -> Vec<usize> {
+        self.file_groups().into_iter().flat_map(|(_, files)| files).collect()
+    }
+
+    /// The browser's sections: a heading and the indices into `files` under it,
+    /// by name within each. Filters sort by name and exposures by length, with
+    /// files lacking the keyword (or not scanned yet) last. Without grouping
+    /// there is one section with an empty heading.
+    fn file_groups(&self) -> Vec<(String, Vec<usize>)> {
+        let meta = |i: usize| self.file_meta.get(&self.files[i]);
+        let shown = (0..self.files.len())
+            .filter(|&i| self.type_filter.is_none_or(|kind| meta(i).is_some_and(|m| m.frame_type == kind)));
+        // (missing, exposure in ms, lower-case filter) sorts the sections
+        let mut groups: BTreeMap<(bool, i64, String), (String, Vec<usize>)> = BTreeMap::new();
+        for i in shown {
+            let (key, heading) = match self.group_by {
+                GroupBy::None => ((false, 0, String::new()), String::new()),
+                GroupBy::Filter => match meta(i).and_then(|m| m.filter.as_deref()) {
+                    Some(f) => ((false, 0, f.to_lowercase()), f.to_string()),
+                    None => ((true, 0, String::new()), "No filter".to_string()),
+                },
+                GroupBy::Exposure => match meta(i).and_then(|m| m.exptime) {
+                    Some(t) => ((false, (t * 1000.0).round() as i64, String::new()), format!("{} s", (t * 100.0).round() / 100.0)),
+                    None => ((true, 0, String::new()), "No exposure time".to_string()),
+                },
+            };
+            groups.entry(key).or_insert_with(|| (heading, Vec::new())).1.push(i);
+        }
+        groups.into_values().collect()
+    }
+
+    /// The HUD's lines for the current image, per `hud_items`; header keywords
+    /// the file lacks are skipped.
+    fn hud_lines(&self) -> Vec<String> {
+        let Some(img) = self.image.as_deref() else { return Vec::new() };
+        self.hud_items
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .filter_map(|item| match item.to_ascii_lowercase().as_str() {
+                "file" => self.selected
+                    .and_then(|i| self.files.get(i))
+                    .and_then(|p| p.file_name())
+                    .map(|n| n.to_string_lossy().into_owned()),
+                "size" if img.channels > 1 => Some(format!("{} × {} × {}", img.width, img.height, img.channels)),
+                "size" => Some(format!("{} × {}", img.width, img.height)),
+                "stretch" => Some(format!("Stretch {}", stretch_label(self.stretch))),
+                "zoom" => Some(format!("Zoom {:.0}%", self.zoom.unwrap_or(self.fit_scale) * 100.0)),
+                _ => {
+                    let key = item.to_uppercase();
+                    img.header(&key).filter(|v| !v.is_empty()).map(|v| capture_field(&key, v))
+                }
+            })
+            .collect()
+    }
+
+    /// Step the browser grouping: by name, filter, exposure.
+    fn cycle_group_by(&mut self) {
+        let i = GroupBy::ALL.iter().position(|&g| g == self.group_by).unwrap_or(0);
+        self.group_by = GroupBy::ALL[(i + 1) % GroupBy::ALL.len()];
+        self.set_info(format!("Files grouped by {}", self.group_by.label().to_lowercase()));
+    }
+
+    /// Read the header metadata of the listed files not seen yet, in the background.
+    /// Replaces a scan still running for a previous listing.
+    fn scan_headers(&mut self) {
+        let paths: Vec<PathBuf> = self.files.iter().filter(|p| !self.file_meta.contains_key(*p)).cloned().collect();
+        self.header_scan = (!paths.is_empty()).then(|| HeaderScan::start(paths, self.ctx.clone()));
+    }
+
+    /// Step the browser's frame-type filter through all files and each type
+    /// present in the listing.
+    fn cycle_type_filter(&mut self) {
+        let present: HashSet<FrameType> = self.files.iter().filter_map(|p| Some(self.file_meta.get(p)?.frame_type)).collect();
+        let mut choices = std::iter::once(None).chain(FrameType::ALL.into_iter().filter(|k| present.contains(k)).map(Some));
+        self.type_filter = match self.type_filter {
+            None => choices.nth(1).flatten(),
+            Some(kind) => choices.skip_while(|&c| c != Some(kind)).nth(1).flatten(),
+        };
+        match self.type_filter {
+            Some(kind) => self.set_info(format!("Showing {} frames", kind.label().to_lowercase())),
+            None => self.set_info("Showing all frames"),
+        }
+    }
+
+    /// Delete the currently selected file (trash if available, else permanent).
+    /// Auto-advances to the next file.
+    fn delete_selected(&mut self) {
+        if !self.marked.is_empty() {
+            self.delete_marked();
+            return;
+        }
+        let Some(idx) = self.selected else { return };
+        let Some(path) = self.files.get(idx).cloned() else { return };
+
+        match trash_or_remove(&path) {
+            Ok(()) => {
+                self.cache.remove(&path);
+                self.files.remove(idx);
+                self.image = None;
+                self.texture = None;
+                self.load_error = None;
+                self.status = None;
+                if self.files.is_empty() {
+                    self.selected = None;
+                } else {
+                    // Stay at same index (now pointing to next file), or step back at end
+                    let new_idx = idx.min(self.files.len() - 1);
+                    self.selected = None;
+                    self.select(new_idx);
+                }
+            }
+            Err(e) => {
+                self.set_error(format!("Delete failed: {e}"));
+            }
+        }
+    }
+
+    /// Delete every marked file. The displayed file stays up if it was not among
+    /// them, else the file now in the slot of the first one deleted is shown.
+    fn delete_marked(&mut self) {
+        let mut marked: Vec<usize> = self.marked.drain().filter(|&i| i < self.files.len()).collect();
+        marked.sort_unstable();
+        let current = self.selected.and_then(|i| self.files.get(i).cloned());
+        let (mut deleted, mut failed) = (0, Vec::new());
+        for &idx in marked.iter().rev() {
+            let path = self.files[idx].clone();
+            match trash_or_remove(&path) {
+                Ok(()) => {
+                    self.cache.remove(&path);
+                    self.files.remove(idx);
+                    deleted += 1;
+                }
+                Err(e) => failed.push(format!("{}: {e}", path.display())),
+            }
+        }
+        match current.and_then(|c| self.files.iter().position(|f| *f == c)) {
+            Some(i) => self.selected = Some(i),
+            None => {
+                self.image = None;
+                self.texture = None;
+                self.load_error = None;
+                self.selected = None;
+                if let (Some(&first), false) = (marked.first(), self.files.is_empty()) {
+                    self.select(first.min(self.files.len() - 1));
+                }
+            }
+        }
+        match failed.first() {
+            Some(first) => self.set_error(format!("Deleted {deleted}, {} failed: {first}", failed.len())),
+            None => self.set_info(format!("Deleted {deleted} files")),
+        }
+    }
+
+    /// Handle a click on file `idx` of the browser: Ctrl / ⌘ toggles it in the
+    /// marked set, Shift marks the range from the displayed file, a plain click
+    /// clears the marks. The clicked file is displayed in every case.
+    fn click_file(&mut self, idx: usize, modifiers: egui::Modifiers) {
+        if modifiers.shift {
+            // The range as listed, leaving out files hidden by the type filter
+            let order = self.shown_files();
+            let pos = |i| order.iter().position(|&o| o == i);
+            let anchor = self.selected.and_then(pos).or(pos(idx));
+            match anchor.zip(pos(idx)) {
+                Some((a, b)) => self.marked.extend(&order[a.min(b)..=a.max(b)]),
+                None => { self.marked.insert(idx); }
+            }
+        } else if modifiers.command {
+            if self.marked.is_empty() {
+                self.marked.extend(self.selected);
+            }
+            if !self.marked.remove(&idx) {
+                self.marked.insert(idx);
+            }
+        } else {
+            self.marked.clear();
+        }
+        self.select(idx);
+    }
+
+    /// Start rendering the hover preview of `path` in the background, unless it
+    /// is already there or on its way.
+    fn request_thumbnail(&mut self, path: &std::path::Path) {
+        if self.thumbnails.contains_key(path) {
+            return;
+        }
+        if self.thumbnails.len() >= THUMBNAIL_CACHE {
+            self.thumbnails.retain(|_, t| matches!(t, Thumbnail::Loading));
+        }
+        self.thumbnails.insert(path.to_path_buf(), Thumbnail::Loading);
+        if let Some(replaced) = self.thumbnailer.request(path.to_path_buf()) {
+            // Requested again if the mouse comes back
+            self.thumbnails.remove(&replaced);
+        }
+    }
+
+    /// Open the OS file manager on the folder holding `path`, with it selected.
+    fn reveal_in_file_manager(&mut self, path: &std::path::Path) {
+        if !path.exists() {
+            self.set_error(format!("{} no longer exists", path.display()));
+            return;
+        }
+        if let Err(e) = opener::reveal(absolute_path(path)) {
+            self.set_error(format!("Reveal failed: {e}"));
+        }
+    }
+
+    /// Show an informational message in the status bar; it clears itself after a few seconds.
+    fn set_info(&mut self, text: impl Into<String>) {
+        self.status = Some(StatusMessage { text: text.into(), is_error: false, shown_at: Instant::now() });
+        self.ctx.request_repaint_after(STATUS_INFO_TIMEOUT);
+    }
+
+    /// Show an error in the status bar; it stays until dismissed.
+    fn set_error(&mut self, text: impl Into<String>) {
+        self.status = Some(StatusMessage { text: text.into(), is_error: true, shown_at: Instant::now() });
+    }
+
+    /// Render the current image with the active stretch and channel view.
+    /// AutoStretch goes through the STF, computing it first if needed, so
+    /// tweaked black/mid/white points are honoured everywhere the image is output.
+    fn render_rgba(&mut self, color: ColorOptions) -> Option<Vec<u8>> {
+        self.ensure_derived();
+        let img = self.derived.as_deref().or(self.image.as_deref())?;
+        Some(render_image(img, self.stretch, self.channel_view, color, &mut self.stf, self.white_clip, self.stf_offset))
+    }
+
+    /// Copy the currently displayed image (native resolution, current stretch/view)
+    /// to the system clipboard.
+    fn copy_to_clipboard(&mut self) {
+        let Some(rgba) = self.render_rgba(self.color) else { return };
+        let Some(img) = self.displayed_image() else { return };
+        let data = arboard::ImageData {
+            width: img.width,
+            height: img.height,
+            bytes: rgba.into(),
+        };
+        // Keep the clipboard handle alive: on X11 the contents vanish when it is dropped.
+        let clipboard = match self.clipboard.take() {
+            Some(cb) => Ok(cb),
+            None => arboard::Clipboard::new(),
+        };
+        let result = clipboard.and_then(|mut cb| {
+            let r = cb.set_image(data);
+            self.clipboard = Some(cb);
+            r
+        });
+        match result {
+            Ok(()) => self.set_info("Image copied to clipboard"),
+            Err(e) => self.set_error(format!("Copy failed: {e}")),
+        }
+    }
+
+    /// Turn watch mode on or off. While on, the browser list follows files being
+    /// added/removed and the displayed file is reloaded when it is rewritten.
+    fn set_watch(&mut self, on: bool) {
+        if !on {
+            self.watcher = None;
+            self.follow_latest = false;
+            self.set_info("Stopped watching directory");
+            return;
+        }
+        if self.playlist {
+            self.set_error("Watch mode needs a directory, not a list of files");
+            return;
+        }
+        match DirWatcher::new(&self.current_dir, self.ctx.clone()) {
+            Ok(w) => {
+                self.watcher = Some(w);
+                self.set_info("Watching directory for changes");
+            }
+            Err(e) => self.set_error(format!("Watch failed: {e:#}")),
+        }
+    }
+
+    /// Re-root the browser at `dir`: list its files (leaving a command-line file
+    /// list), restore its view settings and show its first file. A running
+    /// watcher moves along.
+    fn open_dir(&mut self, dir: PathBuf) {
+        let remembered = self.remember_dir_view();
+        self.start_view = StartView::default();
+        if let Some(path) = self.selected.and_then(|i| self.files.get(i)).filter(|_| !self.playlist) {
+            self.last_selected.insert(self.dir_key(), path.clone());
+        }
+        self.current_dir = dir;
+        self.playlist = false;
+        self.files = collect_fits_files(&self.current_dir, &self.config.file_filter);
+        self.marked.clear();
+        self.type_filter = None;
+        self.scan_headers();
+        self.subdirs.clear();
+        self.restore_dir_view();
+        self.selected = None;
+        self.image = None;
+        self.texture = None;
+        self.load_error = None;
+        self.load_rx = None;
+        self.changed_on_disk = false;
+        if self.watcher.is_some() {
+            self.set_watch(true);
+        }
+        if self.files.is_empty() {
+            self.set_info(format!("No FITS files in {}", self.current_dir.display()));
+        } else {
+            let last = self.last_selected.get(&self.dir_key());
+            // By name: the directory may have been reached by a different (relative) path
+            let idx = last.and_then(|p| self.files.iter().position(|f| f.file_name() == p.file_name()));
+            self.select(idx.unwrap_or(0));
+        }
+        if let Err(e) = remembered {
+            self.set_error(format!("Saving settings failed: {e:#}"));
+        }
+    }
+
+    /// The directory one level up from the browser's: the parent of
+    /// `current_dir`, or for a command-line file list the files' own directory.
+    fn parent_dir(&self) -> Option<PathBuf> {
+        let dir = absolute_path(&self.current_dir);
+        if self.playlist {
+            Some(dir)
+        } else {
+            dir.parent().map(PathBuf::from)
+        }
+    }
+
+    /// React to a debounced batch of changed FITS paths from the directory watcher.
+    fn apply_fs_changes(&mut self, changed: &BTreeSet<PathBuf>) {
+        let current = self.selected.and_then(|i| self.files.get(i).cloned());
+        self.files = collect_fits_files(&self.current_dir, &self.config.file_filter);
+        self.marked.clear();
+        // The watcher reports absolute paths while `files` may be relative: compare names.
+        self.thumbnails.retain(|p, _| !changed.iter().any(|c| c.file_name() == p.file_name()));
+        self.file_meta.retain(|p, _| !changed.iter().any(|c| c.file_name() == p.file_name()));
+        self.scan_headers();
+
+        // Live-follow: jump to the newest file whenever something lands (not on removals).
+        if self.follow_latest && changed.iter().any(|p| p.exists()) {
+            if let Some(newest) = newest_file(&self.files) {
+                if current.as_ref() != Some(&self.files[newest]) {
+                    self.selected = None; // indices shifted; make select() load unconditionally
+                    self.select(newest);
+                    return;
+                }
+            }
+        }
+
+        let Some(current) = current else {
+            if !self.files.is_empty() {
+                self.select(0);
+            }
+            return;
+        };
+        // The watcher reports absolute paths while `files` may be relative: compare names.
+        let was_changed = changed.iter().any(|p| p.file_name() == current.file_name());
+        match self.files.iter().position(|f| *f == current) {
+            Some(i) => {
+                self.selected = Some(i);
+                if was_changed {
+                    // mtime resolution can be coarse; don't trust the cache for a rewrite
+                    self.cache.remove(&current);
+                    let zoom = self.zoom;
+                    self.reload_image();
+                    self.zoom = zoom;
+                }
+            }
+            None => {
+                // The displayed file disappeared: show whatever now sits in its slot.
+                let old_idx = self.selected.unwrap_or(0);
+                self.selected = None;
+                self.image = None;
+                self.texture = None;
+                self.load_rx = None;
+                if !self.files.is_empty() {
+                    self.select(old_idx.min(self.files.len() - 1));
+                }
+            }
+        }
+    }
+
+    /// Turn live-follow on or off. Following needs the directory watcher, so it is
+    /// started if necessary; the newest file is shown straight away.
+    fn set_follow(&mut self, on: bool) {
+        self.follow_latest = on;
+        if !on {
+            self.set_info("Live-follow off");
+            return;
+        }
+        if self.watcher.is_none() {
+            self.set_watch(true);
+            if self.watcher.is_none() {
+                self.follow_latest = false;
+                return;
+            }
+        }
+        if let Some(newest) = newest_file(&self.files) {
+            self.select(newest);
+        }
+        self.set_info("Live-follow on: showing the newest file as it arrives");
+    }
+
+    /// Enter or leave presentation mode: hides all panels and makes the window full-screen.
+    fn set_fullscreen(&mut self, ctx: &egui::Context, on: bool) {
+        self.fullscreen = on;
+        ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(on));
+    }
+
+    /// Flag the current file if it was rewritten since it was loaded. A light
+    /// alternative to watch mode, run when the window regains focus.
+    fn check_changed_on_disk(&mut self) {
+        let Some(path) = self.selected.and_then(|i| self.files.get(i)) else { return };
+        let mtime = path.metadata().and_then(|m| m.modified()).ok();
+        if mtime.is_some() && mtime != self.loaded_mtime {
+            self.changed_on_disk = true;
+        }
+    }
+
+    /// Read the current file from disk again, bypassing the cache, at the same zoom.
+    fn reload_from_disk(&mut self) {
+        let Some(path) = self.selected.and_then(|i| self.files.get(i)).cloned() else { return };
+        self.cache.remove(&path);
+        let zoom = self.zoom;
+        self.reload_image();
+        self.zoom = zoom;
+        self.reloading = self.load_rx.is_some();
+    }
+
+    /// Reload the current image (e.g. after a settings change like demosaic mode).
+    fn reload_image(&mut self) {
+        self.image = None;
+        self.texture = None;
+        self.load_rx = None;
+        if let Some(idx) = self.selected {
+            self.selected = None;
+            self.select(idx);
+        }
+    }
+}
+
+impl eframe::App for FastFitsApp {
+    fn on_exit(&mut self, gl: Option<&glow::Context>) {
+        // Nowhere left to report a failed save
+        let _ = self.remember_dir_view();
+        if let (Some(gpu), Some(gl)) = (&self.gpu, gl) {
+            if let Ok(mut gpu) = gpu.lock() {
+                gpu.destroy(gl);
+            }
+        }
+    }
+
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let theme = (self.config.theme, frame.info().system_theme);
+        if self.applied_theme != Some(theme) {
+            ctx.set_visuals(match self.config.theme {
+                Theme::System => theme.1.unwrap_or(eframe::Theme::Dark).egui_visuals(),
+                Theme::Dark => egui::Visuals::dark(),
+                Theme::Light => egui::Visuals::light(),
+                Theme::Night => night_visuals(),
+            });
+            self.applied_theme = Some(theme);
+        }
+
+        // A command-line setting the user has changed is theirs to keep
+        if self.start_view.stretch.is_some_and(|s| s != self.stretch) {
+            self.start_view.stretch = None;
+        }
+        let colour = self.image.as_ref().is_some_and(|img| img.channels >= 3);
+        if colour && self.start_view.channel.is_some_and(|c| c != self.channel_view) {
+            self.start_view.channel = None;
+        }
+
+        // Poll background load results: an optional quick preview, then the full image
+        while let Some(result) = self.load_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            match result {
+                LoadResult::Preview(img) => self.install_image(*img),
+                LoadResult::Ok(img, timings) => {
+                    self.load_rx = None;
+                    self.loading_name = None;
+                    self.load_stats = Some(timings.summary(img.is_bayer));
+                    if let Some(path) = self.selected.and_then(|i| self.files.get(i)).cloned() {
+                        self.cache.insert(path.clone(), self.load_options, (*img).clone());
+                        if std::mem::take(&mut self.reloading) {
+                            self.set_info(format!("Reloaded {}", path.file_name().unwrap_or_default().to_string_lossy()));
+                        }
+                    }
+                    self.install_image(*img);
+                }
+                LoadResult::Err(e) => {
+                    self.load_rx = None;
+                    self.reloading = false;
+                    self.loading_name = None;
+                    self.placeholder = None;
+                    self.load_error = Some(e);
+                }
+            }
+        }
+
+        // A mask is checked against the full image, so it waits for the preview to be replaced
+        if self.image.as_ref().is_some_and(|img| img.decimation == 1) {
+            if let Some(result) = self.mask_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+                self.mask_rx = None;
+                match result {
+                    Ok(mask) => self.install_mask(mask),
+                    Err(e) => self.set_error(format!("Mask: {e}")),
+                }
+            }
+        }
+
+        // Install a finished rendering, unless the image or settings moved on since
+        if let Some(result) = self.renderer.poll(RenderKind::Main) {
+            if result.generation == self.image_generation && !self.texture_stale && self.image.is_some() {
+                if self.stf.is_none() {
+                    self.stf = result.stf;
+                }
+                self.clipping = result.clipping;
+                self.texture = Some(Rendered::Texture(result.texture));
+                self.texture_mips = result.mips;
+            }
+        }
+        if let Some(result) = self.renderer.poll(RenderKind::Compare) {
+            if result.generation == self.image_generation && self.image.is_some() {
+                self.compare_texture = Some((result.texture, result.mips));
+            }
+        }
+
+        if let Some((path, result)) = self.reference_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            self.reference_rx = None;
+            match result {
+                Ok(img) => {
+                    self.reference = Some((path, Arc::new(img)));
+                    self.set_compare(self.compare);
+                }
+                Err(e) => self.set_error(format!("Compare: {e}")),
+            }
+        }
+
+        if let Some(scan) = &self.header_scan {
+            self.file_meta.extend(scan.poll());
+        }
+
+        // Detected stars, unless the image changed in the meantime
+        if let Some((generation, stars)) = self.stars_rx.as_ref().and_then(|(g, rx)| Some((*g, rx.try_recv().ok()?))) {
+            self.stars_rx = None;
+            if generation == self.image_generation {
+                self.stars = Some((generation, Arc::new(stars)));
+            }
+        }
+
+        for (path, thumb) in self.thumbnailer.poll() {
+            // Dropped in the meantime (file rewritten): render it afresh on next hover
+            if self.thumbnails.contains_key(&path) {
+                self.thumbnails.insert(path, thumb);
+            }
+        }
+
+        let focused = ctx.input(|i| i.focused);
+        if focused && !self.was_focused && self.watcher.is_none() {
+            self.check_changed_on_disk();
+        }
+        self.was_focused = focused;
+
+        // Poll the directory watcher
+        if let Some(watcher) = &mut self.watcher {
+            if let Some(changed) = watcher.poll(&self.config.file_filter) {
+                self.apply_fs_changes(&changed);
+            } else if watcher.is_settling() {
+                ctx.request_repaint_after(DEBOUNCE);
+            }
+        }
+
+        // Keyboard shortcuts
+        ctx.input(|i| {
+            use egui::Key;
+            if i.key_pressed(Key::ArrowRight) || i.key_pressed(Key::ArrowDown) {
+                // handled below after borrow ends
+            }
+            if i.key_pressed(Key::ArrowLeft) || i.key_pressed(Key::ArrowUp) {
+                // handled below
+            }
+        });
+        // Re-check in a non-borrowing way.
+        // Single-key shortcuts are ignored while a text field (e.g. the zoom entry) has focus.
+        let keys_enabled = !ctx.wants_keyboard_input();
+        // While rebinding in Preferences, the next key press is captured instead.
+        let mut close_popup = ctx.input(|i| i.key_pressed(egui::Key::Escape));
+        let mut keys_enabled = keys_enabled;
+        if let Some(action) = self.rebinding {
+            let pressed = ctx.input(|i| {
+                i.events.iter().find_map(|e| match e {
+                    egui::Event::Key { key, pressed: true, .. } => Some(*key),
+                    _ => None,
+                })
+            });
+            if let Some(key) = pressed {
+                if key != egui::Key::Escape {
+                    self.config.keymap.set(action, vec![key]);
+                    if let Err(e) = self.config.save() {
+                        self.set_error(format!("Saving settings failed: {e:#}"));
+                    }
+                }
+                self.rebinding = None;
+            }
+            keys_enabled = false;
+            close_popup = false;
+        }
+        let keymap = &self.config.keymap;
+        let reserved: &[egui::Key] = if self.config.vim_keys { &VIM_KEYS } else { &[] };
+        let pressed = |action| keys_enabled && ctx.input(|i| keymap.pressed(i, action, reserved));
+        let mut go_next = pressed(Action::NextFile);
+        let mut go_prev = pressed(Action::PrevFile);
+        let mut go_first = pressed(Action::FirstFile);
+        let mut go_last = pressed(Action::LastFile);
+        let open_goto = pressed(Action::GoTo);
+        let toggle_stretch = pressed(Action::CycleStretch);
+        let cycle_channel = pressed(Action::CycleChannel);
+        let zoom_in = pressed(Action::ZoomIn);
+        let zoom_out = pressed(Action::ZoomOut);
+        let zoom_reset = pressed(Action::ZoomReset);
+        let zoom_fit = pressed(Action::ZoomFit);
+        let do_delete = pressed(Action::Delete);
+        let toggle_help = pressed(Action::Help);
+        let toggle_prefs = pressed(Action::Prefs);
+        let toggle_stf = pressed(Action::Stf);
+        let toggle_export = pressed(Action::Export);
+        let toggle_analysis = pressed(Action::Analysis);
+        let toggle_mask = pressed(Action::Mask);
+        let cycle_bin = pressed(Action::CycleBinning);
+        let toggle_fullscreen = pressed(Action::Fullscreen);
+        let toggle_watch = pressed(Action::Watch);
+        let toggle_follow = pressed(Action::Follow);
+        let toggle_night = pressed(Action::NightVision);
+        let toggle_measure = pressed(Action::Measure);
+        let toggle_photometry = pressed(Action::Photometry);
+        let toggle_profile = pressed(Action::Profile);
+        let toggle_tilt = pressed(Action::TiltMap);
+        let toggle_file_info = pressed(Action::FileInfo);
+        let toggle_scale_bar = pressed(Action::ScaleBar);
+        let toggle_catalog = pressed(Action::Catalog);
+        let cycle_type_filter = pressed(Action::FrameFilter);
+        let cycle_group_by = pressed(Action::GroupBy);
+        let toggle_hud = pressed(Action::Hud);
+        let next_preset = pressed(Action::NextPreset);
+        let toggle_navigator = pressed(Action::Navigator);
+        let reload = pressed(Action::Reload);
+        let go_up = pressed(Action::ParentDir);
+        let toggle_folders = pressed(Action::Folders);
+        let toggle_headers = pressed(Action::HeadersPanel);
+        let toggle_files = pressed(Action::FilesPanel);
+        let cycle_compare = pressed(Action::Difference);
+        // Ctrl+C arrives as a Copy event rather than a key press.
+        let do_copy = keys_enabled && ctx.input(|i| i.events.iter().any(|e| matches!(e, egui::Event::Copy)));
+
+        // Vim-style navigation: h/k previous, j/l next, gg first, G last.
+        if self.config.vim_keys && keys_enabled {
+            let presses: Vec<(egui::Key, bool)> = ctx.input(|i| {
+                i.events.iter().filter_map(|e| match e {
+                    egui::Event::Key { key, pressed: true, modifiers, .. } => Some((*key, modifiers.shift)),
+                    _ => None,
+                }).collect()
+            });
+            for (key, shift) in presses {
+                match key {
+                    egui::Key::H | egui::Key::K => go_prev = true,
+                    egui::Key::J | egui::Key::L => go_next = true,
+                    egui::Key::G if shift => go_last = true,
+                    egui::Key::G => {
+                        if self.vim_pending_g.take().is_some_and(|t| t.elapsed() < VIM_GG_TIMEOUT) {
+                            go_first = true;
+                        } else {
+                            self.vim_pending_g = Some(Instant::now());
+                        }
+                        continue;
+                    }
+                    _ => {}
+                }
+                self.vim_pending_g = None;
+            }
+        }
+
+        let mut go_next_btn = false;
+        let mut go_prev_btn = false;
+        let mut do_delete_btn = false;
+
+
+        if go_next { self.select_next(); }
+        if go_prev { self.select_prev(); }
+        if open_goto { self.goto = Some(String::new()); }
+        if go_first {
+            if let Some(&first) = self.shown_files().first() { self.select(first); }
+        }
+        if go_last {
+            if let Some(&last) = self.shown_files().last() { self.select(last); }
+        }
+        if toggle_stretch {
+            self.cycle_stretch();
+        }
+        if cycle_channel {
+            self.cycle_channel();
+        }
+        if zoom_in {
+            let s = self.zoom.unwrap_or(self.fit_scale);
+            self.zoom = Some((s * 1.25).min(ZOOM_MAX));
+        }
+        if zoom_out {
+            let s = self.zoom.unwrap_or(self.fit_scale);
+            self.zoom = Some((s / 1.25).max(ZOOM_MIN));
+        }
+        if zoom_reset {
+            self.zoom = Some(1.0);
+        }
+        if zoom_fit {
+            self.zoom = None;
+        }
+        if do_delete {
+            self.delete_selected();
+        }
+        if toggle_help {
+            self.show_help = !self.show_help;
+        }
+        if toggle_prefs {
+            self.show_prefs = !self.show_prefs;
+        }
+        if toggle_stf {
+            self.show_stf = !self.show_stf;
+        }
+        if toggle_export {
+            self.show_export = !self.show_export;
+        }
+        if toggle_analysis {
+            self.show_analysis = !self.show_analysis;
+        }
+        if toggle_mask {
+            self.show_mask = !self.show_mask;
+            self.refresh_mask();
+        }
+        if cycle_bin {
+            self.cycle_binning();
+        }
+        if do_copy {
+            self.copy_to_clipboard();
+        }
+        if toggle_watch {
+            self.set_watch(self.watcher.is_none());
+        }
+        if toggle_follow {
+            self.set_follow(!self.follow_latest);
+        }
+        if toggle_fullscreen {
+            self.set_fullscreen(ctx, !self.fullscreen);
+        }
+        if toggle_night {
+            self.toggle_night_vision();
+        }
+        if toggle_measure {
+            self.toggle_measure();
+        }
+        if toggle_photometry {
+            self.toggle_photometry();
+        }
+        if toggle_profile {
+            self.toggle_profile();
+        }
+        if toggle_tilt {
+            self.show_tilt = !self.show_tilt;
+        }
+        if toggle_scale_bar {
+            self.show_scale_bar = !self.show_scale_bar;
+        }
+        if toggle_catalog {
+            self.toggle_catalog();
+        }
+        if cycle_type_filter {
+            self.cycle_type_filter();
+        }
+        if toggle_hud {
+            self.show_hud = !self.show_hud;
+        }
+        if next_preset {
+            self.next_preset();
+        }
+        if cycle_group_by {
+            self.cycle_group_by();
+        }
+        if toggle_navigator {
+            self.show_navigator = !self.show_navigator;
+        }
+        if toggle_file_info {
+            self.show_file_info = !self.show_file_info;
+        }
+        if reload {
+            self.reload_from_disk();
+        }
+        if toggle_folders {
+            self.show_folders = !self.show_folders;
+        }
+        if toggle_headers {
+            self.show_headers = !self.show_headers;
+        }
+        if toggle_files {
+            self.show_files = !self.show_files;
+        }
+        if cycle_compare {
+            self.cycle_compare();
+        }
+        if let Some(dir) = self.parent_dir().filter(|_| go_up) {
+            self.open_dir(dir);
+        }
+        if close_popup {
+            let any_popup = self.show_help
+                || self.show_prefs
+                || self.show_stf
+                || self.show_export
+                || self.show_analysis
+                || self.show_file_info;
+            if !any_popup && self.fullscreen {
+                self.set_fullscreen(ctx, false);
+            }
+            self.show_help = false;
+            self.show_prefs = false;
+            self.show_stf = false;
+            self.show_export = false;
+            self.show_analysis = false;
+            self.show_file_info = false;
+            self.stop_click_tools();
+            self.goto = None;
+        }
+
+        // Go-to overlay: a 1-based number or part of a file name
+        if let Some(mut query) = self.goto.take() {
+            let target = goto_target(&self.files, &query);
+            let mut confirm = false;
+            egui::Window::new("Go to file")
+                .collapsible(false)
+                .resizable(false)
+                .title_bar(false)
+                .anchor(egui::Align2::CENTER_TOP, [0.0, 48.0])
+                .show(ctx, |ui| {
+                    let edit = ui.add(
+                        egui::TextEdit::singleline(&mut query)
+                            .hint_text("number or file name")
+                            .desired_width(300.0),
+                    );
+                    edit.request_focus();
+                    confirm = edit.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    let text = match target {
+                        Some(i) => format!(
+                            "→ {}  ({} / {})",
+                            self.files[i].file_name().unwrap_or_default().to_string_lossy(),
+                            i + 1,
+                            self.files.len()
+                        ),
+                        None => "no match".to_string(),
+                    };
+                    ui.label(egui::RichText::new(text).weak());
+                });
+            if confirm {
+                if let Some(i) = target {
+                    self.select(i);
+                }
+            } else {
+                self.goto = Some(query);
+            }
+        }
+
+        // Help popup
         if self.show_help {
             egui::Window::new("Keyboard shortcuts")
                 .collapsible(false)
                 .resizable(false)
-                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui| {
+                    egui::Grid::new("help_grid").striped(true).show(ui, |ui| {
+                        let keymap = &self.config.keymap;
+                        let mut rows: Vec<(String, &str)> = Action::ALL
+                            .iter()
+                            .map(|&a| (keymap.describe(a), a.label()))
+                            .collect();
+                        if self.config.vim_keys {
+                            rows.push(("h / k  ·  j / l".to_string(), "Previous / next file (vim)"));
+                            rows.push(("gg  ·  G".to_string(), "First / last file (vim)"));
+                        }
+                        rows.push(("Ctrl+C".to_string(), "Copy image to clipboard"));
+                        rows.push(("Escape".to_string(), "Close popup / leave full-screen"));
+                        for (key, desc) in rows {
+                            ui.label(egui::RichText::new(key).monospace().strong());
+                            ui.label(desc);
+                            ui.end_row();
+                        }
+                    });
+                    ui.separator();
+                    if ui.button("Close  [?]").clicked() {
+                        self.show_help = false;
+                    }
+                });
+        }
+
+        // Screen transfer function: linked black / mid / white point nudges on top of AutoStretch
+        if self.show_stf {
+            let mut open = true;
+            egui::Window::new("Screen Transfer Function")
+                .open(&mut open)
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    if !matches!(self.stretch, Stretch::AutoStretch { .. }) {
+                        ui.label("The STF adjusts AutoStretch.");
+                        if ui.button("Switch to AutoStretch").clicked() {
+                            self.stretch = Stretch::AutoStretch { white_clip: self.white_clip };
+                            self.texture_stale = true;
+                        }
+                        return;
+                    }
+                    let Some(img) = self.derived.as_ref().or(self.image.as_ref()) else {
+                        ui.label("(no file loaded)");
+                        return;
+                    };
+                    let stf = self.stf.get_or_insert_with(|| {
+                        adjusted_stf(img, self.channel_view, self.white_clip, self.color, self.stf_offset)
+                    });
+                    // Sliders show the mean over channels; moving one shifts every
+                    // channel by the same amount, preserving the colour balance.
+                    let mean = |f: fn(&Stf) -> f32| stf.iter().map(f).sum::<f32>() / stf.len().max(1) as f32;
+                    let (mut shadows, mut midtones, mut highlights) =
+                        (mean(|s| s.shadows), mean(|s| s.midtones), mean(|s| s.highlights));
+                    let (old_s, old_m, old_h) = (shadows, midtones, highlights);
+                    let mut changed = false;
+                    egui::Grid::new("stf_grid").show(ui, |ui| {
+                        ui.label("Shadows");
+                        changed |= ui.add(egui::Slider::new(&mut shadows, 0.0..=1.0)
+                            .logarithmic(true).max_decimals(5))
+                            .on_hover_text("Black point (fraction of full scale)")
+                            .changed();
+                        ui.end_row();
+                        ui.label("Midtones");
+                        changed |= ui.add(egui::Slider::new(&mut midtones, 0.0001..=0.9999)
+                            .logarithmic(true).max_decimals(5))
+                            .on_hover_text("MTF balance: lower = brighter, 0.5 = linear")
+                            .changed();
+                        ui.end_row();
+                        ui.label("Highlights");
+                        changed |= ui.add(egui::Slider::new(&mut highlights, 0.0..=1.0)
+                            .max_decimals(4))
+                            .on_hover_text("White point (fraction of full scale)")
+                            .changed();
+                        ui.end_row();
+                    });
+                    if changed {
+                        let step =
+                            StfOffset { shadows: shadows - old_s, midtones: midtones - old_m, highlights: highlights - old_h };
+                        shift_stf(stf, step);
+                        self.stf_offset.shadows += step.shadows;
+                        self.stf_offset.midtones += step.midtones;
+                        self.stf_offset.highlights += step.highlights;
+                        self.texture_stale = true;
+                    }
+                    ui.separator();
+                    if ui.button("Reset to auto").on_hover_text("Drop the adjustments and recompute the automatic STF").clicked() {
+                        self.stf = None;
+                        self.stf_offset = StfOffset::default();
+                        self.texture_stale = true;
+                    }
+                });
+            if !open {
+                self.show_stf = false;
+            }
+        }
+
+        // Analysis: raw statistics per Bayer site
+        if self.show_analysis {
+            let mut open = true;
+            egui::Window::new("Analysis")
+                .open(&mut open)
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    let Some(img) = self.derived.as_ref().or(self.image.as_ref()) else {
+                        ui.label("(no file loaded)");
+                        return;
+                    };
+                    let generation = self.image_generation;
+                    if self.background.as_ref().map(|(g, _)| *g) != Some(generation) {
+                        self.background = Some((generation, img.background_noise()));
+                    }
+                    if let Some((_, background)) = &self.background {
+                        ui.label(egui::RichText::new("Background (median, σ = 1.4826 × MAD)").strong())
+                            .on_hover_text("Sky level and noise of the displayed image.\nPoint at a star to read its SNR in the status bar.");
+                        egui::Grid::new("background_grid").striped(true).show(ui, |ui| {
+                            for h in ["Plane", "Level", "Noise σ"] {
+                                ui.label(egui::RichText::new(h).strong());
+                            }
+                            ui.end_row();
+                            for (c, bg) in background.iter().enumerate() {
+                                let label = match (background.len(), c) {
+                                    (1, _) => "Mono",
+                                    (_, 0) => "R",
+                                    (_, 1) => "G",
+                                    (_, 2) => "B",
+                                    _ => "?",
+                                };
+                                ui.label(label);
+                                ui.monospace(format_value(bg.level));
+                                ui.monospace(format_value(bg.sigma));
+                                ui.end_row();
+                            }
+                        });
+                        ui.separator();
+                    }
+                    let base = self.reference.as_ref().or(self.previous.as_ref());
+                    if let (Some(img), Some((path, base))) = (&self.image, base) {
+                        if self.delta_stats.as_ref().map(|(g, _)| *g) != Some(generation) {
+                            self.delta_stats = Some((generation, img.difference_stats(base)));
+                        }
+                        let name = path.file_name().unwrap_or_default().to_string_lossy();
+                        match self.delta_stats.as_ref().and_then(|(_, s)| s.as_ref()) {
+                            Some(delta) => {
+                                ui.label(egui::RichText::new(format!("Difference from {name}")).strong())
+                                    .on_hover_text("Current minus previous (or selected) file, pixel by pixel, in raw values.\nPress Y to view the difference image.");
+                                egui::Grid::new("delta_grid").striped(true).show(ui, |ui| {
+                                    for h in ["Plane", "Mean", "σ"] {
+                                        ui.label(egui::RichText::new(h).strong());
+                                    }
+                                    ui.end_row();
+                                    for c in img.display_plane_indices(self.channel_view) {
+                                        let Some(s) = delta.get(c) else { continue };
+                                        ui.label(match (delta.len(), c) {
+                                            (1, _) => "Mono",
+                                            (_, 0) => "R",
+                                            (_, 1) => "G",
+                                            (_, 2) => "B",
+                                            _ => "?",
+                                        });
+                                        ui.monospace(format_value(s.mean as f32));
+                                        ui.monospace(format_value(s.stddev as f32));
+                                        ui.end_row();
+                                    }
+                                });
+                            }
+                            None => {
+                                ui.label(format!("No difference: {name} has a different size."));
+                            }
+                        }
+                        ui.separator();
+                    }
+                    let Some(img) = &self.image else { return };
+                    if self.cfa_stats.is_none() {
+                        self.cfa_stats = img.cfa_stats();
+                    }
+                    let Some(stats) = &self.cfa_stats else {
+                        ui.label("Bayer channel statistics need an undebayered colour (CFA) image.");
+                        return;
+                    };
+                    ui.label(egui::RichText::new("Bayer sites (ADU before debayering)").strong());
+                    egui::Grid::new("cfa_stats_grid").striped(true).show(ui, |ui| {
+                        for h in ["Site", "Mean", "σ", "Min", "Max"] {
+                            ui.label(egui::RichText::new(h).strong());
+                        }
+                        ui.end_row();
+                        for (label, s) in stats {
+                            ui.label(label);
+                            ui.monospace(format!("{:.1}", s.mean));
+                            ui.monospace(format!("{:.1}", s.stddev));
+                            ui.monospace(format!("{}", s.min));
+                            ui.monospace(format!("{}", s.max));
+                            ui.end_row();
+                        }
+                    });
+                });
+            if !open {
+                self.show_analysis = false;
+            }
+        }
+
+        // How the current file's data was stored and interpreted, for debugging odd renderings
+        if self.show_file_info {
+            let mut open = true;
+            egui::Window::new("File info")
+                .open(&mut open)
+                .collapsible(false)
+                .resizable(false)
                 .show(ctx, |ui| {
-                    egui::Grid::new("help_grid").striped(true).show(ui, |ui| {
-                        let rows: &[(&str, &str)] = &[
-                            ("← / →  or  ↑ / ↓", "Previous / next file"),
-                            ("Delete",             "Move current file to trash"),
-                            ("S",                  "Toggle stretch (Auto ↔ Linear)"),
-                            ("+  /  -",            "Zoom in / out"),
-                            ("0",                  "Zoom to 1:1 (100 %)"),
-                            ("F",                  "Zoom to fit"),
-                            ("?",                  "Show / hide this help"),
-                            (",",                  "Show / hide Preferences"),
-                        ];
-                        for (key, desc) in rows {
-                            ui.label(egui::RichText::new(*key).monospace().strong());
-                            ui.label(*desc);
+                    let path = self.selected.and_then(|i| self.files.get(i));
+                    let (Some(path), Some(img)) = (path, &self.image) else {
+                        ui.label("(no file loaded)");
+                        return;
+                    };
+                    let rows = file_info(path, img);
+                    egui::Grid::new("file_info_grid").striped(true).show(ui, |ui| {
+                        for (key, value) in &rows {
+                            ui.label(*key);
+                            ui.monospace(value);
                             ui.end_row();
                         }
                     });
                     ui.separator();
-                    if ui.button("Close  [?]").clicked() {
-                        self.show_help = false;
+                    if ui.button("Copy as text").on_hover_text("For pasting into a bug report").clicked() {
+                        let text: Vec<String> = rows.iter().map(|(k, v)| format!("{k}: {v}")).collect();
+                        ui.output_mut(|o| o.copied_text = text.join("\n"));
+                    }
+                });
+            if !open {
+                self.show_file_info = false;
+            }
+        }
+
+        // Aperture photometry of the clicked stars
+        if self.photometry.is_some() {
+            let mut open = true;
+            egui::Window::new("Photometry")
+                .open(&mut open)
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    aperture_sliders(ui, &mut self.aperture);
+                    ui.separator();
+                    let Some(img) = &self.image else {
+                        ui.label("(no file loaded)");
+                        return;
+                    };
+                    if img.decimation > 1 {
+                        ui.label("(loading full resolution…)");
+                        return;
+                    }
+                    let Some(points) = &mut self.photometry else { return };
+                    if points.is_empty() {
+                        ui.label("Click a star to place an aperture.");
+                        return;
+                    }
+                    let [radius, inner, outer] = self.aperture;
+                    let results: Vec<_> =
+                        points.iter().map(|p| img.aperture_photometry(p.x, p.y, radius, (inner, outer))).collect();
+                    let reference = results.first().copied().flatten().and_then(|p| p.magnitude());
+                    let mut remove = None;
+                    egui::Grid::new("photometry_grid").striped(true).show(ui, |ui| {
+                        for h in ["#", "x", "y", "Flux", "Mag", "Δ #1", "SNR", ""] {
+                            ui.label(egui::RichText::new(h).strong());
+                        }
+                        ui.end_row();
+                        for (i, result) in results.iter().enumerate() {
+                            ui.label(format!("{}", i + 1));
+                            match result {
+                                Some(p) => {
+                                    ui.monospace(format!("{:.1}", p.x));
+                                    ui.monospace(format!("{:.1}", p.y));
+                                    ui.monospace(format_value(p.flux as f32));
+                                    let mag = p.magnitude();
+                                    ui.monospace(mag.map_or("–".to_string(), |m| format!("{m:.3}")));
+                                    let delta = mag.zip(reference).map(|(m, r)| m - r);
+                                    ui.monospace(delta.map_or("–".to_string(), |d| format!("{d:+.3}")));
+                                    ui.monospace(format!("{:.1}", p.snr()));
+                                }
+                                None => {
+                                    ui.label("(outside the image)");
+                                    for _ in 0..5 {
+                                        ui.label("");
+                                    }
+                                }
+                            }
+                            if ui.small_button("✖").on_hover_text("Remove this aperture").clicked() {
+                                remove = Some(i);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                    if let Some(i) = remove {
+                        points.remove(i);
+                    }
+                    if ui.button("Clear").clicked() {
+                        points.clear();
+                    }
+                });
+            if !open {
+                self.photometry = None;
+            }
+        }
+
+        // Radial profile of the clicked star, for focusing
+        if let Some(clicked) = self.profile {
+            let mut open = true;
+            egui::Window::new("Radial profile")
+                .open(&mut open)
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    aperture_sliders(ui, &mut self.aperture);
+                    ui.separator();
+                    let Some(img) = &self.image else {
+                        ui.label("(no file loaded)");
+                        return;
+                    };
+                    if img.decimation > 1 {
+                        ui.label("(loading full resolution…)");
+                        return;
+                    }
+                    let Some(p) = clicked else {
+                        ui.label("Click a star.");
+                        return;
+                    };
+                    let [radius, inner, outer] = self.aperture;
+                    let Some(profile) = img.radial_profile(p.x, p.y, radius, (inner, outer)) else {
+                        ui.label("(outside the image)");
+                        return;
+                    };
+                    let px = |v: Option<f32>| v.map_or("–".to_string(), |v| format!("{v:.2} px"));
+                    egui::Grid::new("profile_grid").show(ui, |ui| {
+                        ui.label("FWHM");
+                        ui.monospace(px(profile.fwhm)).on_hover_text("Full width at half maximum of the fitted Gaussian");
+                        ui.end_row();
+                        ui.label("HFR");
+                        ui.monospace(px(profile.hfr)).on_hover_text("Half-flux radius: encloses half the flux in the aperture");
+                        ui.end_row();
+                        ui.label("Centre");
+                        ui.monospace(format!("{:.1}, {:.1}", profile.photometry.x, profile.photometry.y));
+                        ui.end_row();
+                    });
+                    draw_profile(ui, &profile, radius);
+                });
+            if !open {
+                self.profile = None;
+            }
+        }
+
+        // PNG export dialog
+        if self.show_export {
+            let mut open = true;
+            let mut do_export = false;
+            egui::Window::new("Export PNG")
+                .open(&mut open)
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    let Some(path) = self.export_path().filter(|_| self.image.is_some()) else {
+                        ui.label("(no file loaded)");
+                        return;
+                    };
+                    ui.label(format!("Saves the image as displayed to\n{}", path.display()));
+                    ui.checkbox(&mut self.export_caption, "Caption with OBJECT / FILTER / EXPTIME")
+                        .on_hover_text("Burn the header values into the bottom-left corner");
+                    ui.separator();
+                    if ui.button("Export  [Enter]").clicked() || ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                        do_export = true;
                     }
                 });
+            if do_export {
+                self.export_png();
+                open = false;
+            }
+            if !open {
+                self.show_export = false;
+            }
         }
 
         // Preferences dialog
+        if !self.show_prefs {
+            self.rebinding = None;
+        }
         if self.show_prefs {
             let mut reload = false;
+            let mut reload_mask = false;
+            let mut reload_catalog = false;
+            let mut toggle_watch_pref = None;
+            let mut toggle_follow_pref = None;
+            let mut save_config = false;
+            let mut refilter = false;
             egui::Window::new("Preferences")
                 .collapsible(false)
                 .resizable(false)
                 .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
                 .show(ctx, |ui| {
-                    let is_bayer = self.image.as_ref().map_or(false, |img| img.is_bayer);
-                    if is_bayer {
-                        ui.label("Demosaic algorithm");
+                    ui.horizontal(|ui| {
+                        ui.selectable_value(&mut self.prefs_tab, PrefsTab::General, "General");
+                        ui.selectable_value(&mut self.prefs_tab, PrefsTab::Keyboard, "Keyboard");
+                    });
+                    ui.separator();
+                    if self.prefs_tab == PrefsTab::Keyboard {
+                        egui::Grid::new("keymap_grid").striped(true).show(ui, |ui| {
+                            for action in Action::ALL {
+                                ui.label(action.label());
+                                let text = if self.rebinding == Some(action) {
+                                    "Press a key…".to_string()
+                                } else {
+                                    self.config.keymap.describe(action)
+                                };
+                                if ui.button(egui::RichText::new(text).monospace())
+                                    .on_hover_text("Click, then press the new key (Escape cancels)")
+                                    .clicked()
+                                {
+                                    self.rebinding = Some(action);
+                                }
+                                ui.end_row();
+                            }
+                        });
+                        if ui.checkbox(&mut self.config.vim_keys, "Vim-style navigation")
+                            .on_hover_text("h / k previous, j / l next, gg first, G last file.\nOverrides other bindings on H, J, K, L and G.")
+                            .changed()
+                        {
+                            save_config = true;
+                        }
+                        if ui.checkbox(&mut self.config.wrap_navigation, "Wrap navigation")
+                            .on_hover_text("Next at the last file goes to the first and previous at the first to the last.\nOff: stop at the ends of the list.")
+                            .changed()
+                        {
+                            save_config = true;
+                        }
+                        if ui.button("Restore defaults").clicked() {
+                            self.config.keymap = Default::default();
+                            self.rebinding = None;
+                            save_config = true;
+                        }
+                    } else {
                         ui.horizontal(|ui| {
-                            if ui.selectable_label(
-                                self.demosaic_mode == DemosaicMode::Bilinear, "Bilinear"
-                            ).clicked() && self.demosaic_mode != DemosaicMode::Bilinear {
-                                self.demosaic_mode = DemosaicMode::Bilinear;
-                                reload = true;
+                            ui.label("Theme");
+                            for theme in Theme::ALL {
+                                if ui.selectable_label(self.config.theme == theme, theme.label()).clicked()
+                                    && self.config.theme != theme
+                                {
+                                    // Night vision renders the image red-only: switch between GPU and texture display
+                                    self.config.theme = theme;
+                                    self.texture_stale = true;
+                                    self.invalidate_compare();
+                                    save_config = true;
+                                }
+                            }
+                        }).response.on_hover_text(format!(
+                            "Night: dim red UI and red-only image to keep your eyes dark-adapted [{}]",
+                            self.config.keymap.describe(Action::NightVision)
+                        ));
+                        if ui.checkbox(&mut self.color.srgb, "sRGB output gamma")
+                            .on_hover_text("Treat the stretched values as linear light and encode them for the screen\nwith the sRGB curve, as Siril and PixInsight do: brighter midtones.\nOff shows the stretched values as they are.")
+                            .changed()
+                        {
+                            self.texture_stale = true;
+                            self.invalidate_compare();
+                        }
+                        ui.separator();
+                        ui.label("Stretch presets").on_hover_text(
+                            "The stretch mode and its parameters, background levels, saturation,\nlinked stretch and sRGB gamma, saved by name",
+                        );
+                        ui.horizontal(|ui| {
+                            let mut apply = None;
+                            egui::ComboBox::from_id_source("stretch_presets")
+                                .selected_text(if self.config.presets.is_empty() { "(none saved)" } else { "Apply…" })
+                                .show_ui(ui, |ui| {
+                                    for name in self.config.presets.keys() {
+                                        if ui.selectable_label(*name == self.preset_name, name).clicked() {
+                                            apply = Some(name.clone());
+                                        }
+                                    }
+                                })
+                                .response
+                                .on_hover_text(format!(
+                                    "Apply a saved preset  [{} for the next one]",
+                                    self.config.keymap.describe(Action::NextPreset)
+                                ));
+                            if let Some(name) = apply {
+                                self.apply_preset(&name);
+                            }
+                            ui.add(egui::TextEdit::singleline(&mut self.preset_name).hint_text("name").desired_width(120.0));
+                            let name = self.preset_name.trim().to_string();
+                            if ui.add_enabled(!name.is_empty(), egui::Button::new("Save current"))
+                                .on_hover_text("Save the current settings under this name, replacing a preset of the same name")
+                                .clicked()
+                            {
+                                self.config.presets.insert(name.clone(), self.current_preset());
+                                self.set_info(format!("Saved preset {name}"));
+                                save_config = true;
+                            }
+                            if ui.add_enabled(self.config.presets.contains_key(&name), egui::Button::new("Delete")).clicked() {
+                                self.config.presets.remove(&name);
+                                save_config = true;
+                            }
+                        });
+                        ui.separator();
+                        let is_color = self.image.as_ref().is_some_and(|img| img.channels >= 3);
+                        if is_color {
+                            ui.label("Saturation");
+                            ui.horizontal(|ui| {
+                                if ui.add(egui::Slider::new(&mut self.color.saturation, SATURATION_RANGE).max_decimals(2))
+                                    .on_hover_text("Colour saturation of the composite RGB view (1 = unchanged)")
+                                    .changed()
+                                {
+                                    self.texture_stale = true;
+                                }
+                                if ui.small_button("Reset").clicked() && self.color.saturation != 1.0 {
+                                    self.color.saturation = 1.0;
+                                    self.texture_stale = true;
+                                }
+                            });
+                            if ui.checkbox(&mut self.color.linked, "Linked RGB stretch")
+                                .on_hover_text("One curve for all three channels, from their mean: keeps real colour\ndifferences (and casts). Off stretches each channel on its own,\nneutralising the background.")
+                                .changed()
+                            {
+                                self.stf = None;
+                                self.texture_stale = true;
+                            }
+                            ui.label("AutoStretch background")
+                                .on_hover_text("Output level each channel's sky is stretched to. Equal levels give a\nneutral sky; a higher red than blue one a warmer, a lower a cooler one.");
+                            let mut changed = false;
+                            egui::Grid::new("background_targets").show(ui, |ui| {
+                                for (label, target) in ["R", "G", "B"].into_iter().zip(&mut self.color.background) {
+                                    ui.label(label);
+                                    changed |= ui.add(egui::Slider::new(target, BACKGROUND_RANGE).max_decimals(2)).changed();
+                                    ui.end_row();
+                                }
+                            });
+                            if ui.small_button("Neutral").on_hover_text("Back to equal levels (0.20)").clicked() {
+                                self.color.background = [DEFAULT_BACKGROUND; 3];
+                                changed = true;
+                            }
+                            if changed {
+                                self.stf = None;
+                                self.texture_stale = true;
+                            }
+                            ui.separator();
+                        }
+                        let is_bayer = self.image.as_ref().map_or(false, |img| img.is_bayer);
+                        if is_bayer {
+                            ui.label("Demosaic algorithm");
+                            ui.horizontal(|ui| {
+                                if ui.selectable_label(
+                                    self.load_options.demosaic == DemosaicMode::Bilinear, "Bilinear"
+                                ).clicked() && self.load_options.demosaic != DemosaicMode::Bilinear {
+                                    self.load_options.demosaic = DemosaicMode::Bilinear;
+                                    reload = true;
+                                }
+                                if ui.selectable_label(
+                                    self.load_options.demosaic == DemosaicMode::Cubic, "Cubic"
+                                ).clicked() && self.load_options.demosaic != DemosaicMode::Cubic {
+                                    self.load_options.demosaic = DemosaicMode::Cubic;
+                                    reload = true;
+                                }
+                            });
+                            ui.separator();
+                        }
+                        let is_cube = self.image.as_ref().is_some_and(|img| img.header("NAXIS") == Some("3"));
+                        if is_cube || self.load_options.color_axis != ColorAxis::Auto {
+                            ui.label("Colour axis of 3-D images")
+                                .on_hover_text("Which axis holds the channels. Auto reads CTYPE1 / CTYPE3, else takes\na first axis of length 3 for pixel-interleaved RGB.");
+                            ui.horizontal(|ui| {
+                                for (axis, label) in [
+                                    (ColorAxis::Auto, "Auto"),
+                                    (ColorAxis::Last, "NAXIS3 (planes)"),
+                                    (ColorAxis::First, "NAXIS1 (interleaved)"),
+                                ] {
+                                    if ui.selectable_label(self.load_options.color_axis == axis, label).clicked()
+                                        && self.load_options.color_axis != axis
+                                    {
+                                        self.load_options.color_axis = axis;
+                                        reload = true;
+                                    }
+                                }
+                            });
+                            ui.separator();
+                        }
+                        let mut hot_on = self.load_options.hot_pixel_sigma.is_some();
+                        let mut sigma = self.load_options.hot_pixel_sigma.unwrap_or(8.0);
+                        ui.horizontal(|ui| {
+                            let toggled = ui.checkbox(&mut hot_on, "Hot-pixel correction")
+                                .on_hover_text("Display only: replace pixels far above the median of their\nneighbours (same CFA colour for Bayer data). The file is not modified.")
+                                .changed();
+                            let resp = ui.add_enabled(
+                                hot_on,
+                                egui::DragValue::new(&mut sigma).range(2.0..=50.0).speed(0.1).suffix(" σ"),
+                            ).on_hover_text("Detection threshold in noise sigmas (lower = more aggressive)");
+                            if toggled || resp.changed() {
+                                self.load_options.hot_pixel_sigma = hot_on.then_some(sigma);
                             }
-                            if ui.selectable_label(
-                                self.demosaic_mode == DemosaicMode::Cubic, "Cubic"
-                            ).clicked() && self.demosaic_mode != DemosaicMode::Cubic {
-                                self.demosaic_mode = DemosaicMode::Cubic;
+                            // Reloading is expensive: wait until a drag is released.
+                            if toggled || resp.drag_stopped() || (resp.changed() && !resp.dragged()) {
                                 reload = true;
                             }
                         });
                         ui.separator();
+                        ui.label("Percentile stretch clip points")
+                            .on_hover_text("Low / high percentiles mapped to black / white by the Percentile stretch");
+                        ui.horizontal(|ui| {
+                            let (mut lo, mut hi) = (self.linear_clip.0 * 100.0, self.linear_clip.1 * 100.0);
+                            let lo_resp = ui.add(
+                                egui::DragValue::new(&mut lo).range(percent(LINEAR_CLIP_LOW_RANGE)).speed(0.05).suffix(" %"),
+                            ).on_hover_text("Low percentile (black point)");
+                            ui.label("–");
+                            let hi_resp = ui.add(
+                                egui::DragValue::new(&mut hi).range(percent(LINEAR_CLIP_HIGH_RANGE)).speed(0.05).suffix(" %"),
+                            ).on_hover_text("High percentile (white point)");
+                            if lo_resp.changed() || hi_resp.changed() {
+                                self.linear_clip = (lo / 100.0, hi / 100.0);
+                                if let Stretch::Percentile { .. } = self.stretch {
+                                    self.stretch = Stretch::Percentile { low: lo / 100.0, high: hi / 100.0 };
+                                    self.texture_stale = true;
+                                }
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("AutoStretch white clip");
+                            let mut pct = self.white_clip * 100.0;
+                            let resp = ui.add(
+                                egui::DragValue::new(&mut pct).range(percent(WHITE_CLIP_RANGE)).speed(0.001).max_decimals(3).suffix(" %"),
+                            ).on_hover_text("Percentile mapped to white. Raise towards 100 % to keep bright cores\n(planetary nebulae, galaxy nuclei) from blowing out.");
+                            if resp.changed() {
+                                self.white_clip = pct / 100.0;
+                                if let Stretch::AutoStretch { .. } = self.stretch {
+                                    self.stretch = Stretch::AutoStretch { white_clip: self.white_clip };
+                                }
+                                self.stf = None;
+                                self.texture_stale = true;
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Log stretch knee k");
+                            let resp = ui.add(
+                                egui::DragValue::new(&mut self.log_knee).range(LOG_KNEE_RANGE).speed(1.0),
+                            ).on_hover_text("ln(1 + k·x) / ln(1 + k): larger k brightens faint detail more");
+                            if resp.changed() {
+                                if let Stretch::Log { .. } = self.stretch {
+                                    self.stretch = Stretch::Log { k: self.log_knee };
+                                    self.texture_stale = true;
+                                }
+                            }
+                        });
+                        if ui.checkbox(&mut self.histeq_shared, "Equalise RGB with one luminance curve")
+                            .on_hover_text("Histogram equalisation keeps the colour balance instead of equalising each channel separately")
+                            .changed()
+                        {
+                            if let Stretch::HistEq { .. } = self.stretch {
+                                self.stretch = Stretch::HistEq { shared: self.histeq_shared };
+                                self.texture_stale = true;
+                            }
+                        }
+                        ui.separator();
+                        ui.label("Normalisation ceiling")
+                            .on_hover_text("Full-scale value used to anchor autostretch.\nAuto derives it from BITPIX / BSCALE / BZERO.");
+                        ui.horizontal(|ui| {
+                            let choices: &[(Option<f32>, &str, &str)] = &[
+                                (None,           "Auto",   "Derive from BITPIX / BSCALE / BZERO"),
+                                (Some(255.0),    "8-bit",  "Full scale 255"),
+                                (Some(4095.0),   "12-bit", "Full scale 4095"),
+                                (Some(16383.0),  "14-bit", "Full scale 16383"),
+                                (Some(65535.0),  "16-bit", "Full scale 65535"),
+                                (Some(0.0),      "Data",   "Use the image's own min/max"),
+                            ];
+                            for &(value, label, tip) in choices {
+                                if ui.selectable_label(self.ceiling_override == value, label)
+                                    .on_hover_text(tip)
+                                    .clicked() && self.ceiling_override != value
+                                {
+                                    self.ceiling_override = value;
+                                    reload = true;
+                                }
+                            }
+                        });
+                        ui.separator();
+                        ui.label("Summary bar keywords")
+                            .on_hover_text("Header keywords shown above the image, comma-separated.\nMissing keywords are skipped.");
+                        ui.horizontal(|ui| {
+                            ui.add(egui::TextEdit::singleline(&mut self.summary_keys).desired_width(320.0));
+                            if ui.small_button("Reset").clicked() {
+                                self.summary_keys = DEFAULT_SUMMARY_KEYS.to_string();
+                            }
+                        });
+                        ui.label("HUD lines")
+                            .on_hover_text(format!(
+                                "Shown over the image with {}, comma-separated: file, size, stretch and zoom,\nor any header keyword. Missing keywords are skipped.",
+                                self.config.keymap.describe(Action::Hud)
+                            ));
+                        ui.horizontal(|ui| {
+                            ui.add(egui::TextEdit::singleline(&mut self.hud_items).desired_width(320.0));
+                            if ui.small_button("Reset").clicked() {
+                                self.hud_items = DEFAULT_HUD_ITEMS.to_string();
+                            }
+                        });
+                        ui.separator();
+                        if self.gpu.is_some() {
+                            if ui.checkbox(&mut self.use_gpu, "Stretch on the GPU")
+                                .on_hover_text("Apply AutoStretch / STF in a shader so slider changes are instant\non large frames. Turn off if the image looks wrong.")
+                                .changed()
+                            {
+                                self.texture_stale = true;
+                            }
+                            ui.separator();
+                        }
+                        ui.label("Mask HDU").on_hover_text(format!(
+                            "Data-quality / mask extension overlaid with [{}]:\na 0-based HDU number or an EXTNAME.\nEmpty tries DQ, then MASK.",
+                            self.config.keymap.describe(Action::Mask)
+                        ));
+                        ui.horizontal(|ui| {
+                            let edit = ui.add(
+                                egui::TextEdit::singleline(&mut self.mask_hdu)
+                                    .hint_text("DQ / MASK")
+                                    .desired_width(120.0),
+                            );
+                            if edit.lost_focus() {
+                                reload_mask = true;
+                            }
+                        });
+                        ui.separator();
+                        ui.checkbox(&mut self.lock_view, "Lock view across files")
+                            .on_hover_text("Keep the channel view and zoom when switching files,\nfor blink-comparing subs");
+                        ui.separator();
+                        let mut watching = self.watcher.is_some();
+                        let label = format!("Watch directory and auto-reload  [{}]", self.config.keymap.describe(Action::Watch));
+                        if ui.checkbox(&mut watching, label)
+                            .on_hover_text("Reload the displayed file when it changes on disk and\nrefresh the file list when files are added or removed")
+                            .changed()
+                        {
+                            toggle_watch_pref = Some(watching);
+                        }
+                        let mut following = self.follow_latest;
+                        let label = format!("Live-follow newest file  [{}]", self.config.keymap.describe(Action::Follow));
+                        if ui.checkbox(&mut following, label)
+                            .on_hover_text("Jump to the newest FITS file as soon as it appears\n(turns on watch mode)")
+                            .changed()
+                        {
+                            toggle_follow_pref = Some(following);
+                        }
+                        ui.separator();
+                        ui.label("File extensions")
+                            .on_hover_text("Files listed in the browser, comma-separated.\nEmpty restores fits, fit, fts, fz.");
+                        ui.horizontal(|ui| {
+                            let filter = &mut self.config.file_filter;
+                            let edit = ui.add_enabled(
+                                !filter.all_files,
+                                egui::TextEdit::singleline(&mut self.extensions_edit).desired_width(200.0),
+                            );
+                            if !edit.has_focus() && !edit.lost_focus() {
+                                self.extensions_edit = filter.extensions.join(", ");
+                            }
+                            if edit.lost_focus() {
+                                filter.set_extensions(&self.extensions_edit);
+                                self.extensions_edit = filter.extensions.join(", ");
+                                refilter = true;
+                            }
+                            if ui.checkbox(&mut filter.all_files, "All files")
+                                .on_hover_text("List every file, for directories with nonstandard naming")
+                                .changed()
+                            {
+                                refilter = true;
+                            }
+                        });
+                        ui.separator();
+                        ui.label("Catalogue overlay")
+                            .on_hover_text("CSV of name,ra,dec lines (degrees, or h:m:s and d:m:s) marked on\nplate-solved images. Empty uses the bundled bright stars and Messier objects.");
+                        let edit = ui.add(
+                            egui::TextEdit::singleline(&mut self.catalog_edit)
+                                .hint_text("bundled")
+                                .desired_width(300.0),
+                        );
+                        if !edit.has_focus() && !edit.lost_focus() {
+                            self.catalog_edit =
+                                self.config.catalog.as_ref().map(|p| p.display().to_string()).unwrap_or_default();
+                        }
+                        if edit.lost_focus() {
+                            let path = self.catalog_edit.trim();
+                            let catalog = (!path.is_empty()).then(|| PathBuf::from(path));
+                            if catalog != self.config.catalog {
+                                self.config.catalog = catalog;
+                                self.catalog = None;
+                                if self.show_catalog {
+                                    // Read the new one now, reporting errors right away
+                                    self.show_catalog = false;
+                                    reload_catalog = true;
+                                }
+                                save_config = true;
+                            }
+                        }
                     }
+                    ui.separator();
                     if ui.button("Close  [,]").clicked() {
                         self.show_prefs = false;
                     }
                 });
             if reload { self.reload_image(); }
+            if reload_mask { self.refresh_mask(); }
+            if reload_catalog { self.toggle_catalog(); }
+            if refilter {
+                if !self.playlist {
+                    self.apply_fs_changes(&BTreeSet::new());
+                }
+                save_config = true;
+            }
+            if save_config {
+                if let Err(e) = self.config.save() {
+                    self.set_error(format!("Saving settings failed: {e:#}"));
+                }
+            }
+            if let Some(on) = toggle_watch_pref { self.set_watch(on); }
+            if let Some(on) = toggle_follow_pref { self.set_follow(on); }
         }
 
         // Ensure texture is built
-        if self.image.is_some() && self.texture.is_none() {
-            self.rebuild_texture(ctx);
+        let rendering = self.renderer.busy(RenderKind::Main);
+        if self.image.is_some() && ((self.texture.is_none() && !rendering) || self.texture_stale) {
+            self.texture_stale = false;
+            self.invalidate_compare();
+            match frame.gl().cloned().and_then(|gl| self.gpu_view(&gl)) {
+                Some(view) => {
+                    self.renderer.cancel(RenderKind::Main);
+                    self.texture = Some(Rendered::Gpu(view));
+                    self.texture_mips.clear();
+                }
+                None => self.start_render(),
+            }
+        }
+        if self.texture.is_some() || (self.loading_name.is_none() && !self.renderer.busy(RenderKind::Main)) {
+            self.placeholder = None;
+        }
+        if self.split.is_some() && self.image.is_some() && self.compare_texture.is_none()
+            && !self.renderer.busy(RenderKind::Compare)
+        {
+            self.start_compare_render();
         }
 
         // Bottom toolbar: navigation + delete buttons + error status
         let has_files = !self.files.is_empty();
         let btn_size = egui::vec2(100.0, 32.0);
-        egui::TopBottomPanel::bottom("nav_bar").show(ctx, |ui| {
+        let show_panels = !self.fullscreen;
+        egui::TopBottomPanel::bottom("nav_bar").show_animated(ctx, show_panels, |ui| {
             ui.add_space(4.0);
             ui.horizontal(|ui| {
                 // Centre the three buttons by offsetting with half the remaining space.
@@ -404,19 +3272,48 @@ impl eframe::App for FastFitsApp {
 
                 ui.separator();
 
+                let del_label = match self.marked.len() {
+                    0 => "Delete".to_string(),
+                    n => format!("Delete {n}"),
+                };
                 let del_btn = ui.add_enabled(
-                    self.selected.is_some(),
-                    egui::Button::new("Delete").min_size(btn_size),
-                ).on_hover_text("Move file to trash  [Del]");
+                    self.selected.is_some() || !self.marked.is_empty(),
+                    egui::Button::new(del_label).min_size(btn_size),
+                ).on_hover_text(format!(
+                    "Move file to trash, or all selected files  [{}]",
+                    self.config.keymap.describe(Action::Delete)
+                ));
                 if del_btn.clicked() { do_delete_btn = true; }
 
-                if let Some(msg) = &self.delete_status.clone() {
+                if self.status.as_ref().is_some_and(|m| {
+                    !m.is_error && m.shown_at.elapsed() >= STATUS_INFO_TIMEOUT
+                }) {
+                    self.status = None;
+                }
+                if let Some(msg) = &self.status {
                     ui.separator();
-                    ui.label(egui::RichText::new(msg).color(egui::Color32::RED));
+                    let color = if msg.is_error { egui::Color32::RED } else { ui.visuals().text_color() };
+                    ui.label(egui::RichText::new(&msg.text).color(color));
                     if ui.small_button("x").clicked() {
-                        self.delete_status = None;
+                        self.status = None;
                     }
                 }
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if let Some(stats) = &self.load_stats {
+                        ui.label(egui::RichText::new(stats).weak());
+                    }
+                    if let Some(readout) = self.cursor.and_then(|(x, y)| self.pixel_readout(x, y)) {
+                        ui.separator();
+                        ui.monospace(readout).on_hover_text(
+                            "Pixel under the cursor: x, y and the displayed values.\nFor colour (CFA) images the raw sensor value of the Bayer site is shown too.",
+                        );
+                    }
+                    if self.image.is_some() && !self.clipping.is_empty() {
+                        ui.separator();
+                        clipping_label(ui, &self.clipping);
+                    }
+                });
             });
             ui.add_space(4.0);
         });
@@ -426,47 +3323,212 @@ impl eframe::App for FastFitsApp {
         if do_delete_btn { self.delete_selected(); }
 
         // Menu bar
-        egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
+        let mut reveal = None;
+        let mut reload_clicked = false;
+        let mut delete_marked = false;
+        let mut compare_marked = None;
+        let mut open_dir = None;
+        egui::TopBottomPanel::top("menu_bar").show_animated(ctx, show_panels, |ui| {
             egui::menu::bar(ui, |ui| {
                 ui.label(egui::RichText::new("fastfits").strong());
                 ui.separator();
                 if let Some(idx) = self.selected {
                     if let Some(f) = self.files.get(idx) {
-                        ui.label(f.file_name().unwrap_or_default().to_string_lossy().as_ref());
+                        ui.label(egui::RichText::new(format!("{} / {}", idx + 1, self.files.len())).monospace())
+                            .on_hover_text("Position in the file list  [/ to go to]");
+                        let name = f.file_name().unwrap_or_default().to_string_lossy();
+                        let response = ui
+                            .add(egui::Label::new(name.as_ref()).sense(egui::Sense::click()))
+                            .on_hover_ui(|ui| {
+                                ui.label(absolute_path(f).display().to_string());
+                                ui.weak("Right-click to reveal in the file manager");
+                            });
+                        file_context_menu(&response, f, &mut reveal);
+                    }
+                }
+                if self.changed_on_disk {
+                    let badge = egui::RichText::new("⟳ Changed on disk").color(egui::Color32::from_rgb(255, 160, 60));
+                    if ui.button(badge)
+                        .on_hover_text(format!(
+                            "The file was rewritten since it was loaded; click to reload  [{}]",
+                            self.config.keymap.describe(Action::Reload)
+                        ))
+                        .clicked()
+                    {
+                        reload_clicked = true;
+                    }
+                }
+                if let Some(img) = self.displayed_image() {
+                    ui.separator();
+                    ui.label(egui::RichText::new(img.summary()).weak())
+                        .on_hover_text("Dimensions, channels, bit depth");
+                }
+                if let Some(f) = self.saturated.filter(|&f| f > SATURATION_WARN) {
+                    let text = format!("⚠ {:.1}% saturated", f * 100.0);
+                    ui.label(egui::RichText::new(text).color(egui::Color32::from_rgb(255, 160, 60)))
+                        .on_hover_text("Share of pixels at or above 98 % of full scale: likely overexposed");
+                }
+                if self.watcher.is_some() {
+                    ui.separator();
+                    let keymap = &self.config.keymap;
+                    let (text, tip) = if self.follow_latest {
+                        ("● Following", format!("Showing the newest file as it arrives  [{}]", keymap.describe(Action::Follow)))
+                    } else {
+                        ("● Watching", format!("Auto-reloading on disk changes  [{}]", keymap.describe(Action::Watch)))
+                    };
+                    ui.label(egui::RichText::new(text).color(egui::Color32::LIGHT_GREEN))
+                        .on_hover_text(tip);
+                }
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    // Help button
+                    let tip = format!("Show keyboard shortcuts  [{}]", self.config.keymap.describe(Action::Help));
+                    if ui.button("?").on_hover_text(tip).clicked() {
+                        self.show_help = !self.show_help;
+                    }
+                    // Prefs button
+                    let tip = format!("Preferences  [{}]", self.config.keymap.describe(Action::Prefs));
+                    if ui.button("Prefs").on_hover_text(tip).clicked() {
+                        self.show_prefs = !self.show_prefs;
+                    }
+                    ui.separator();
+
+                    // Stretch toggle
+                    let key = self.config.keymap.describe(Action::CycleStretch);
+                    if ui.selectable_label(true, stretch_label(self.stretch))
+                        .on_hover_text(format!(
+                            "Cycle stretch mode (Auto → Percentile → Linear → Raw → Log → Equalised)  [{key}]"
+                        ))
+                        .clicked()
+                    {
+                        self.cycle_stretch();
+                    }
+                    ui.label("Stretch:").on_hover_text(format!("Cycle stretch mode  [{key}]"));
+                    ui.separator();
+
+                    // Binning toggle
+                    let bin_label = format!("Bin {0}×{0}", self.bin_factor);
+                    if ui.selectable_label(self.bin_factor > 1, bin_label)
+                        .on_hover_text("Average N×N pixel blocks to judge noise (off → 2×2 → 3×3)  [B]")
+                        .clicked()
+                    {
+                        self.cycle_binning();
+                    }
+                    if self.image.as_ref().is_some_and(|img| img.channels == 3)
+                        && ui.selectable_label(self.neutralize, "Neutral bg")
+                            .on_hover_text("Subtract each channel's sky background (median) so light-pollution\ngradients don't tint the background. Display only; offsets, no scaling.")
+                            .clicked()
+                    {
+                        self.toggle_neutralize();
+                    }
+                    let compare_label = match self.compare {
+                        Some(Combine::Ratio) => "Ratio",
+                        _ => "Diff",
+                    };
+                    if ui.selectable_label(self.compare.is_some(), compare_label)
+                        .on_hover_text(format!(
+                            "Show the current file minus (then divided by) the previously viewed one,\nor two selected files, to spot drift between calibration frames  [{}]",
+                            self.config.keymap.describe(Action::Difference)
+                        ))
+                        .clicked()
+                    {
+                        self.cycle_compare();
+                    }
+                    if ui.selectable_label(self.split.is_some(), "Split")
+                        .on_hover_text("Compare the current stretch (left) with true linear (right);\ndrag the divider to move it")
+                        .clicked()
+                    {
+                        self.toggle_split();
+                    }
+                    if ui.selectable_label(self.measure.is_some(), "Measure")
+                        .on_hover_text(format!(
+                            "Click two points for their distance in pixels and, on plate-solved\nframes, on the sky with the position angle  [{}]",
+                            self.config.keymap.describe(Action::Measure)
+                        ))
+                        .clicked()
+                    {
+                        self.toggle_measure();
+                    }
+                    if ui.selectable_label(self.photometry.is_some(), "Photometry")
+                        .on_hover_text(format!(
+                            "Click stars to place apertures and compare their background-subtracted\nflux and instrumental magnitude  [{}]",
+                            self.config.keymap.describe(Action::Photometry)
+                        ))
+                        .clicked()
+                    {
+                        self.toggle_photometry();
+                    }
+                    if ui.selectable_label(self.profile.is_some(), "Profile")
+                        .on_hover_text(format!(
+                            "Click a star for its radial profile, FWHM and half-flux radius  [{}]",
+                            self.config.keymap.describe(Action::Profile)
+                        ))
+                        .clicked()
+                    {
+                        self.toggle_profile();
                     }
-                }
-                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                    // Help button
-                    if ui.button("?").on_hover_text("Show keyboard shortcuts  [?]").clicked() {
-                        self.show_help = !self.show_help;
+                    if ui.selectable_label(self.show_file_info, "Info")
+                        .on_hover_text(format!(
+                            "How the file's pixels are stored and were interpreted (BITPIX, BSCALE,\nBZERO, byte order)  [{}]",
+                            self.config.keymap.describe(Action::FileInfo)
+                        ))
+                        .clicked()
+                    {
+                        self.show_file_info = !self.show_file_info;
                     }
-                    // Prefs button
-                    if ui.button("Prefs").on_hover_text("Preferences  [,]").clicked() {
-                        self.show_prefs = !self.show_prefs;
+                    if ui.selectable_label(self.show_scale_bar, "Scale")
+                        .on_hover_text(format!(
+                            "Scale bar from the plate scale in the header (WCS, or pixel size\nand focal length), else in pixels  [{}]",
+                            self.config.keymap.describe(Action::ScaleBar)
+                        ))
+                        .clicked()
+                    {
+                        self.show_scale_bar = !self.show_scale_bar;
+                    }
+                    if ui.selectable_label(self.show_catalog, "Catalog")
+                        .on_hover_text(format!(
+                            "Mark bright stars and Messier objects (or your own catalogue, see\nPreferences) on plate-solved images, to check the solution  [{}]",
+                            self.config.keymap.describe(Action::Catalog)
+                        ))
+                        .clicked()
+                    {
+                        self.toggle_catalog();
+                    }
+                    if ui.selectable_label(self.show_tilt, "Tilt")
+                        .on_hover_text(format!(
+                            "Median star FWHM in a 3 × 3 grid of the frame, to spot sensor tilt\nor field curvature  [{}]",
+                            self.config.keymap.describe(Action::TiltMap)
+                        ))
+                        .clicked()
+                    {
+                        self.show_tilt = !self.show_tilt;
                     }
                     ui.separator();
 
-                    // Stretch toggle
-                    let stretch_label = match self.stretch {
-                        Stretch::AutoStretch => "Auto",
-                        Stretch::Linear => "Linear",
+                    // Data-quality mask overlay
+                    if self.show_mask && self.mask_texture.is_some() {
+                        ui.add(egui::Slider::new(&mut self.mask_opacity, 0.0..=1.0).show_value(false))
+                            .on_hover_text("Mask overlay opacity");
+                    }
+                    let mask_label = match (&self.mask_texture, self.show_mask) {
+                        (Some(_), true) => format!("Mask ({})", self.mask_count),
+                        _ => "Mask".to_string(),
                     };
-                    if ui.selectable_label(true, stretch_label)
-                        .on_hover_text("Toggle stretch mode  [S]")
+                    if ui.selectable_label(self.show_mask, mask_label)
+                        .on_hover_text(format!(
+                            "Overlay pixels flagged in the data-quality / mask HDU  [{}]",
+                            self.config.keymap.describe(Action::Mask)
+                        ))
                         .clicked()
                     {
-                        self.stretch = match self.stretch {
-                            Stretch::AutoStretch => Stretch::Linear,
-                            Stretch::Linear => Stretch::AutoStretch,
-                        };
-                        self.texture = None;
+                        self.show_mask = !self.show_mask;
+                        self.refresh_mask();
                     }
-                    ui.label("Stretch:").on_hover_text("Toggle stretch mode  [S]");
                     ui.separator();
 
                     // Channel selector (only for multi-channel images)
                     if let Some(img) = &self.image {
                         if img.channels >= 3 {
+                            let key = self.config.keymap.describe(Action::CycleChannel);
                             for ch in (0..img.channels).rev() {
                                 let label = match ch { 0 => "R", 1 => "G", 2 => "B", _ => "?" };
                                 let tip = match ch {
@@ -476,41 +3538,104 @@ impl eframe::App for FastFitsApp {
                                     _ => "Show channel",
                                 };
                                 if ui.selectable_label(self.channel_view == ChannelView::Single(ch), label)
-                                    .on_hover_text(tip)
+                                    .on_hover_text(format!("{tip}  [{key} cycles]"))
                                     .clicked()
                                 {
                                     self.channel_view = ChannelView::Single(ch);
-                                    self.texture = None;
+                                    self.stf = None;
+                                    self.texture_stale = true;
                                 }
                             }
                             if ui.selectable_label(self.channel_view == ChannelView::Rgb, "RGB")
-                                .on_hover_text("Show composite RGB")
+                                .on_hover_text(format!("Show composite RGB  [{key} cycles]"))
                                 .clicked()
                             {
                                 self.channel_view = ChannelView::Rgb;
-                                self.texture = None;
+                                self.stf = None;
+                                self.texture_stale = true;
                             }
                             ui.label("Channel:");
                             ui.separator();
+
+                            // Narrowband palette for the composite view
+                            let palettes = [
+                                (Palette::Hoo, "HOO", "Bicolour: Hα → R, OIII → G+B\n(planes taken as Hα, OIII, SII)"),
+                                (Palette::Sho, "SHO", "Hubble palette: SII → R, Hα → G, OIII → B\n(planes taken as Hα, OIII, SII)"),
+                                (Palette::Rgb, "RGB", "Planes shown as R, G, B"),
+                            ];
+                            for (palette, label, tip) in palettes {
+                                if ui.selectable_label(self.color.palette == palette, label)
+                                    .on_hover_text(tip)
+                                    .clicked()
+                                {
+                                    self.color.palette = palette;
+                                    if self.channel_view != ChannelView::Rgb {
+                                        self.channel_view = ChannelView::Rgb;
+                                        self.stf = None;
+                                    }
+                                    self.texture_stale = true;
+                                }
+                            }
+                            ui.label("Palette:");
+                            ui.separator();
                         }
                     }
 
-                    // Zoom info
-                    let zoom_str = match self.zoom {
-                        None => "Fit".to_string(),
-                        Some(s) => format!("{:.0}%", s * 100.0),
-                    };
-                    ui.label(zoom_str).on_hover_text("Zoom  [+] [-] [0=1:1] [F=fit]");
+                    // Zoom: fit toggle, exact percentage entry and a logarithmic slider.
+                    // While fitting, the entry shows the effective autofit scale.
+                    if ui.selectable_label(self.zoom.is_none(), "Fit")
+                        .on_hover_text("Zoom to fit  [F]")
+                        .clicked()
+                    {
+                        self.zoom = None;
+                    }
+                    let mut pct = self.zoom.unwrap_or(self.fit_scale) * 100.0;
+                    let field = ui.add(
+                        egui::DragValue::new(&mut pct)
+                            .range(ZOOM_MIN * 100.0..=ZOOM_MAX * 100.0)
+                            .max_decimals(0)
+                            .suffix("%"),
+                    ).on_hover_text("Type an exact zoom percentage, or drag");
+                    let slider = ui.scope(|ui| {
+                        ui.spacing_mut().slider_width = 100.0;
+                        ui.add(
+                            egui::Slider::new(&mut pct, ZOOM_MIN * 100.0..=ZOOM_MAX * 100.0)
+                                .logarithmic(true)
+                                .show_value(false),
+                        )
+                    }).inner.on_hover_text("Zoom  [+] [-] [0=1:1] [F=fit]");
+                    if field.changed() || slider.changed() {
+                        self.zoom = Some((pct / 100.0).clamp(ZOOM_MIN, ZOOM_MAX));
+                    }
                     ui.label("Zoom:").on_hover_text("Zoom  [+] [-] [0=1:1] [F=fit]");
                 });
             });
         });
 
+        // Capture summary bar: key acquisition parameters at a glance
+        let summary: Vec<String> = self.image.as_ref().map_or_else(Vec::new, |img| {
+            self.summary_keys
+                .split(',')
+                .map(|k| k.trim().to_uppercase())
+                .filter_map(|k| img.header(&k).filter(|v| !v.is_empty()).map(|v| capture_field(&k, v)))
+                .collect()
+        });
+        egui::TopBottomPanel::top("capture_bar").show_animated(ctx, show_panels && !summary.is_empty(), |ui| {
+            ui.horizontal_wrapped(|ui| {
+                for (i, field) in summary.iter().enumerate() {
+                    if i > 0 {
+                        ui.separator();
+                    }
+                    ui.label(egui::RichText::new(field).monospace());
+                }
+            });
+        });
+
         // Left panel: FITS headers
         egui::SidePanel::left("headers_panel")
             .resizable(true)
             .default_width(220.0)
-            .show(ctx, |ui| {
+            .show_animated(ctx, show_panels && self.show_headers, |ui| {
                 ui.heading("Headers");
                 ui.separator();
                 egui::ScrollArea::vertical().show(ui, |ui| {
@@ -531,97 +3656,1105 @@ impl eframe::App for FastFitsApp {
         egui::SidePanel::right("file_browser")
             .resizable(true)
             .default_width(220.0)
-            .show(ctx, |ui| {
+            .show_animated(ctx, show_panels && self.show_files, |ui| {
                 ui.heading("Files");
                 ui.separator();
-                let dir_label = self
-                    .current_dir
-                    .file_name()
-                    .unwrap_or(self.current_dir.as_os_str())
-                    .to_string_lossy()
-                    .to_string();
-                ui.small(dir_label);
+                ui.horizontal_wrapped(|ui| {
+                    ui.spacing_mut().item_spacing.x = 2.0;
+                    let parent = self.parent_dir();
+                    let up = ui
+                        .add_enabled(parent.is_some(), egui::Button::new("⬆").small())
+                        .on_hover_text(format!(
+                            "Browse the parent directory  [{}]",
+                            self.config.keymap.describe(Action::ParentDir)
+                        ));
+                    if up.clicked() {
+                        open_dir = parent;
+                    }
+                    if ui.add(egui::SelectableLabel::new(self.show_folders, "📁"))
+                        .on_hover_text(format!(
+                            "Show / hide the folders below this directory  [{}]",
+                            self.config.keymap.describe(Action::Folders)
+                        ))
+                        .clicked()
+                    {
+                        self.show_folders = !self.show_folders;
+                    }
+                    if self.playlist {
+                        ui.small(format!("{} files from the command line", self.files.len()));
+                        return;
+                    }
+                    // Breadcrumbs: the last few levels of the path, each one clickable
+                    let dir = absolute_path(&self.current_dir);
+                    let levels: Vec<&std::path::Path> = dir.ancestors().collect();
+                    if levels.len() > BREADCRUMBS {
+                        ui.small("…");
+                    }
+                    for (i, level) in levels.iter().take(BREADCRUMBS).enumerate().rev() {
+                        let name = level.file_name().map_or_else(
+                            || level.display().to_string(),
+                            |n| n.to_string_lossy().into_owned(),
+                        );
+                        if i == 0 {
+                            ui.label(egui::RichText::new(name).small().strong());
+                        } else {
+                            if ui.small_button(name).on_hover_text(level.display().to_string()).clicked() {
+                                open_dir = Some(level.to_path_buf());
+                            }
+                            ui.small("›");
+                        }
+                    }
+                });
+                if self.show_folders && !self.playlist {
+                    ui.separator();
+                    let root = absolute_path(&self.current_dir);
+                    egui::ScrollArea::vertical()
+                        .id_source("folder_tree")
+                        .max_height(FOLDER_TREE_HEIGHT)
+                        .auto_shrink([false, true])
+                        .show(ui, |ui| {
+                            if subdirectories(&root, &mut self.subdirs).is_empty() {
+                                ui.weak("(no subfolders)");
+                            }
+                            folder_tree(ui, &root, &mut self.subdirs, &mut open_dir);
+                        });
+                }
+                if !self.marked.is_empty() {
+                    ui.horizontal(|ui| {
+                        if ui.button(format!("Delete {} selected", self.marked.len()))
+                            .on_hover_text(format!(
+                                "Move the selected files to the trash  [{}]",
+                                self.config.keymap.describe(Action::Delete)
+                            ))
+                            .clicked()
+                        {
+                            delete_marked = true;
+                        }
+                        if ui.small_button("Clear").on_hover_text("Deselect all but the displayed file").clicked() {
+                            self.marked.clear();
+                        }
+                    });
+                    if self.marked.len() == 2 {
+                        ui.horizontal(|ui| {
+                            ui.label("Compare:");
+                            let tip = format!(
+                                "Displayed file minus the other selected one  [{}]",
+                                self.config.keymap.describe(Action::Difference)
+                            );
+                            if ui.button("A − B").on_hover_text(tip).clicked() {
+                                compare_marked = Some(Combine::Difference);
+                            }
+                            if ui.button("A / B").on_hover_text("Displayed file divided by the other selected one").clicked() {
+                                compare_marked = Some(Combine::Ratio);
+                            }
+                        });
+                    }
+                }
+                // Frame-type filter, once the header scan has found some typed frames
+                let mut counts: BTreeMap<FrameType, usize> = BTreeMap::new();
+                for meta in self.files.iter().filter_map(|p| self.file_meta.get(p)) {
+                    *counts.entry(meta.frame_type).or_default() += 1;
+                }
+                if self.type_filter.is_some() || counts.keys().any(|&k| k != FrameType::Unknown) {
+                    ui.horizontal_wrapped(|ui| {
+                        ui.spacing_mut().item_spacing.x = 2.0;
+                        let hint = format!("Show only one frame type  [{}]", self.config.keymap.describe(Action::FrameFilter));
+                        if ui.selectable_label(self.type_filter.is_none(), "All").on_hover_text(&hint).clicked() {
+                            self.type_filter = None;
+                        }
+                        for kind in FrameType::ALL {
+                            let Some(&count) = counts.get(&kind) else { continue };
+                            let text = egui::RichText::new(format!("{} {count}", kind.label())).color(frame_color(kind, ui));
+                            if ui.selectable_label(self.type_filter == Some(kind), text).on_hover_text(&hint).clicked() {
+                                self.type_filter = Some(kind);
+                            }
+                        }
+                    });
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Group by");
+                    egui::ComboBox::from_id_source("group_by")
+                        .selected_text(self.group_by.label())
+                        .show_ui(ui, |ui| {
+                            for group in GroupBy::ALL {
+                                ui.selectable_value(&mut self.group_by, group, group.label());
+                            }
+                        })
+                        .response
+                        .on_hover_text(format!(
+                            "Section the list by the files' FILTER or EXPTIME; navigation follows the list  [{}]",
+                            self.config.keymap.describe(Action::GroupBy)
+                        ));
+                });
                 ui.separator();
 
+                let mut hovered = None;
                 egui::ScrollArea::vertical().show(ui, |ui| {
                     let mut clicked = None;
-                    for (i, path) in self.files.iter().enumerate() {
-                        let name = path
-                            .file_name()
-                            .unwrap_or_default()
-                            .to_string_lossy()
-                            .to_string();
-                        let is_selected = self.selected == Some(i);
-                        if ui.selectable_label(is_selected, &name)
-                            .on_hover_text("Open file  [←/→ to navigate]  [Del to trash]")
-                            .clicked()
-                        {
-                            clicked = Some(i);
+                    let groups = self.file_groups();
+                    if let (true, Some(kind)) = (groups.is_empty(), self.type_filter) {
+                        ui.weak(format!("(no {} frames)", kind.label().to_lowercase()));
+                    }
+                    for (heading, files) in groups {
+                        if self.group_by != GroupBy::None {
+                            let count = if files.len() == 1 { "1 file".to_string() } else { format!("{} files", files.len()) };
+                            ui.add_space(4.0);
+                            ui.label(egui::RichText::new(format!("{heading} ({count})")).strong());
+                        }
+                        for i in files {
+                            let path = &self.files[i];
+                            let name = path
+                                .file_name()
+                                .unwrap_or_default()
+                                .to_string_lossy()
+                                .to_string();
+                            let is_selected = self.selected == Some(i) || self.marked.contains(&i);
+                            let meta = self.file_meta.get(path);
+                            let response = ui.horizontal(|ui| {
+                                if let Some(kind) = meta.map(|m| m.frame_type) {
+                                    ui.label(egui::RichText::new(kind.label()).small().color(frame_color(kind, ui)));
+                                }
+                                let response = ui.selectable_label(is_selected, &name);
+                                if let Some(summary) = meta.map(FileMeta::summary).filter(|s| !s.is_empty()) {
+                                    ui.label(egui::RichText::new(summary).small().weak());
+                                }
+                                response
+                            }).inner.on_hover_ui(|ui| {
+                                hovered = Some(path.clone());
+                                match self.thumbnails.get(path) {
+                                    Some(Thumbnail::Ready(texture)) => {
+                                        ui.image((texture.id(), texture.size_vec2()));
+                                    }
+                                    Some(Thumbnail::Failed(e)) => {
+                                        ui.colored_label(ui.visuals().error_fg_color, e);
+                                    }
+                                    _ => {
+                                        ui.horizontal(|ui| {
+                                            ui.spinner();
+                                            ui.weak("Rendering preview…");
+                                        });
+                                    }
+                                }
+                                ui.label(
+                                    "Open file  [←/→ to navigate]  [Del to trash]\nCtrl-click or Shift-click to select several for deleting\nRight-click to reveal in the file manager",
+                                );
+                            });
+                            if response.clicked() {
+                                clicked = Some((i, ui.input(|i| i.modifiers)));
+                            }
+                            file_context_menu(&response, path, &mut reveal);
                         }
                     }
-                    if let Some(i) = clicked {
-                        self.select(i);
+                    if let Some((i, modifiers)) = clicked {
+                        self.click_file(i, modifiers);
                     }
                 });
+                if let Some(path) = hovered {
+                    self.request_thumbnail(&path);
+                } else if let Some(dropped) = self.thumbnailer.cancel() {
+                    self.thumbnails.remove(&dropped);
+                }
             });
 
-        // Center panel: image viewport
-        egui::CentralPanel::default().show(ctx, |ui| {
+        if let Some(path) = reveal {
+            self.reveal_in_file_manager(&path);
+        }
+        if reload_clicked {
+            self.reload_from_disk();
+        }
+        if let Some(how) = compare_marked {
+            self.compare_marked(how);
+        }
+        if delete_marked {
+            self.delete_marked();
+        }
+        if let Some(dir) = open_dir {
+            self.open_dir(dir);
+        }
+
+        // Center panel: image viewport (edge-to-edge on black in full-screen mode)
+        let central = if self.fullscreen {
+            egui::CentralPanel::default()
+                .frame(egui::Frame::none().fill(egui::Color32::BLACK))
+        } else {
+            egui::CentralPanel::default()
+        };
+        central.show(ctx, |ui| {
             if let Some(err) = &self.load_error {
                 ui.centered_and_justified(|ui| {
                     ui.label(egui::RichText::new(err).color(egui::Color32::RED));
                 });
                 return;
             }
+            if let Some(spectrum) = self.spectrum.as_ref().filter(|_| self.image.is_some()) {
+                draw_spectrum(ui, spectrum);
+                return;
+            }
 
-            let Some(texture) = &self.texture else {
-                ui.centered_and_justified(|ui| {
-                    if let Some(name) = &self.loading_name {
-                        ui.label(format!("Loading {}…", name));
-                    } else {
-                        ui.label("No file selected");
+            self.cursor = None;
+            let stars = if self.show_tilt { self.stars() } else { None };
+            let tilt = stars
+                .zip(self.image.as_deref())
+                .map(|(stars, img)| (fwhm_grid(&stars, img.width, img.height), stars.len()));
+            let plate_scale = self.image.as_ref().filter(|_| self.show_scale_bar).map(|img| img.pixel_scale());
+            let catalog = self.catalog.as_deref().filter(|_| self.show_catalog);
+            let busy = match &self.loading_name {
+                Some(name) => Some(format!("Loading {name}…")),
+                None if self.renderer.busy(RenderKind::Main) || self.renderer.busy(RenderKind::Compare) => Some("Rendering…".to_string()),
+                None => self.stars_rx.as_ref().filter(|_| self.show_tilt).map(|_| "Detecting stars…".to_string()),
+            };
+            let hud = if self.show_hud { self.hud_lines() } else { Vec::new() };
+            let Some(rendered) = &self.texture else {
+                if let (Some(placeholder), Some(busy)) = (&self.placeholder, &busy) {
+                    let rect = ui.available_rect_before_wrap();
+                    let scale = self.zoom.unwrap_or(
+                        (rect.width() / placeholder.size.x).min(rect.height() / placeholder.size.y),
+                    );
+                    let image_rect = egui::Rect::from_center_size(rect.center(), placeholder.size * scale);
+                    match (&placeholder.rendered, &self.gpu) {
+                        (Rendered::Texture(texture), _) => {
+                            egui::Image::new((texture.id(), image_rect.size()))
+                                .tint(egui::Color32::from_gray(PLACEHOLDER_DIM))
+                                .paint_at(ui, image_rect);
+                        }
+                        (Rendered::Gpu(view), Some(gpu)) => {
+                            ui.painter().add(paint_callback(gpu, image_rect, *view));
+                            let dim = egui::Color32::from_black_alpha(255 - PLACEHOLDER_DIM);
+                            ui.painter().rect_filled(image_rect, 0.0, dim);
+                        }
+                        (Rendered::Gpu(_), None) => {}
                     }
+                    let overlay = egui::Rect::from_center_size(rect.center(), egui::vec2(rect.width(), 60.0));
+                    ui.allocate_ui_at_rect(overlay, |ui| {
+                        ui.vertical_centered(|ui| {
+                            ui.spinner();
+                            ui.label(busy);
+                        });
+                    });
+                    return;
+                }
+                ui.centered_and_justified(|ui| {
+                    ui.label(busy.as_deref().unwrap_or("No file selected"));
                 });
                 return;
             };
 
-            let img_size = texture.size_vec2();
+            // Size in full-resolution pixels: a decimated preview or binned texture is
+            // scaled up to match.
+            let Some((width, height, reduction)) =
+                self.displayed_image().map(|img| (img.width, img.height, img.decimation * img.binning))
+            else {
+                return;
+            };
+            let texels = egui::vec2(width as f32, height as f32);
+            let img_size = texels * reduction as f32;
             let available = ui.available_size();
 
             let display_size = match self.zoom {
                 None => {
                     // Autofit: scale to fill available area while preserving aspect ratio
                     let scale = (available.x / img_size.x).min(available.y / img_size.y);
+                    self.fit_scale = scale;
                     img_size * scale
                 }
                 Some(s) => img_size * s,
             };
 
-            egui::ScrollArea::both().show(ui, |ui| {
-                ui.image((texture.id(), display_size));
+            let mask = self.mask_texture.as_ref().filter(|_| self.show_mask);
+            let mask_tint = egui::Color32::from_white_alpha((self.mask_opacity * 255.0) as u8);
+            let mips = &self.texture_mips;
+            let gpu = &self.gpu;
+            let compare = self.compare_texture.as_ref().zip(self.split);
+            let stretch_name = stretch_label(self.stretch);
+            let mut split = self.split;
+            let mut measure = self.measure.take();
+            let wcs = measure.as_ref().and(self.image.as_ref()).and_then(|img| img.wcs());
+            let catalog_wcs = catalog.and(self.image.as_ref()).and_then(|img| img.wcs());
+            let mut apertures = self.photometry.take();
+            let mut profile = self.profile.take();
+            let aperture = self.aperture;
+            // Pick the mip level closest to one texel per screen pixel (never coarser).
+            let texels_per_px = texels.x / (display_size.x * ctx.pixels_per_point());
+            let level = if texels_per_px >= 2.0 { texels_per_px.log2().floor() as usize } else { 0 };
+            let pick = |texture: &TextureHandle, mips: &[TextureHandle]| -> egui::TextureId {
+                match level {
+                    0 => texture.id(),
+                    l => mips.get(l - 1).or(mips.last()).unwrap_or(texture).id(),
+                }
+            };
+            let mut scroll_area = egui::ScrollArea::both();
+            if let Some(offset) = self.scroll_to.take() {
+                scroll_area = scroll_area.scroll_offset(offset);
+            }
+            let scrolled = scroll_area.show(ui, |ui| {
+                let response = match (rendered, gpu) {
+                    (Rendered::Texture(texture), _) => ui.image((pick(texture, mips), display_size)),
+                    (Rendered::Gpu(view), Some(gpu)) => {
+                        let (rect, response) = ui.allocate_exact_size(display_size, egui::Sense::hover());
+                        ui.painter().add(paint_callback(gpu, rect, *view));
+                        response
+                    }
+                    (Rendered::Gpu(_), None) => ui.allocate_exact_size(display_size, egui::Sense::hover()).1,
+                };
+                if let Some(((texture, compare_mips), fraction)) = compare {
+                    let rect = response.rect;
+                    let x = rect.left() + fraction * rect.width();
+                    let right = egui::Rect::from_min_max(egui::pos2(x, rect.top()), rect.max);
+                    let uv = egui::Rect::from_min_max(egui::pos2(fraction, 0.0), egui::pos2(1.0, 1.0));
+                    ui.painter().image(pick(texture, compare_mips), right, uv, egui::Color32::WHITE);
+                    ui.painter().vline(x, rect.y_range(), egui::Stroke::new(2.0, SPLIT_COLOR));
+                    // Labels at the top of the visible part of the image
+                    let top = rect.top().max(ui.clip_rect().top()) + 4.0;
+                    let font = egui::FontId::proportional(14.0);
+                    let painter = ui.painter();
+                    painter.text(egui::pos2(x - 6.0, top), egui::Align2::RIGHT_TOP, &stretch_name, font.clone(), SPLIT_COLOR);
+                    painter.text(egui::pos2(x + 6.0, top), egui::Align2::LEFT_TOP, "Linear", font, SPLIT_COLOR);
+                    let handle_rect = egui::Rect::from_x_y_ranges(x - 6.0..=x + 6.0, rect.y_range());
+                    let handle = ui
+                        .interact(handle_rect, ui.id().with("split_divider"), egui::Sense::drag())
+                        .on_hover_cursor(egui::CursorIcon::ResizeHorizontal);
+                    if let Some(pos) = handle.interact_pointer_pos().filter(|_| handle.dragged()) {
+                        split = Some(((pos.x - rect.left()) / rect.width()).clamp(0.0, 1.0));
+                    }
+                }
+                if let Some(mask) = mask {
+                    let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                    ui.painter().image(mask.id(), response.rect, uv, mask_tint);
+                }
+                if let Some((grid, count)) = &tilt {
+                    draw_tilt_map(ui.painter(), response.rect, ui.clip_rect(), grid, *count);
+                }
+                if let Some((entries, wcs)) = catalog.zip(catalog_wcs) {
+                    draw_catalog(ui.painter(), response.rect.min, display_size / img_size, entries, &wcs, img_size);
+                }
+                let rect = response.rect;
+                // Only clicks on the image itself, not on windows floating over it
+                let click = ui
+                    .input(|i| i.pointer.primary_clicked().then(|| i.pointer.interact_pos()).flatten())
+                    .filter(|&p| {
+                        rect.contains(p) && ui.clip_rect().contains(p) && ui.ctx().layer_id_at(p) == Some(ui.layer_id())
+                    })
+                    .map(|p| ((p - rect.min) / display_size * img_size).to_pos2());
+                if (measure.is_some() || apertures.is_some() || profile.is_some()) && response.hovered() {
+                    ui.ctx().set_cursor_icon(egui::CursorIcon::Crosshair);
+                }
+                if let Some(points) = &mut measure {
+                    if let Some(pos) = click {
+                        if points.len() == 2 {
+                            points.clear();
+                        }
+                        points.push(pos);
+                    }
+                    draw_measurement(ui.painter(), rect.min, display_size / img_size, points, wcs);
+                }
+                if let Some(points) = &mut apertures {
+                    points.extend(click);
+                    draw_apertures(ui.painter(), rect.min, display_size / img_size, points, aperture);
+                }
+                if let Some(star) = &mut profile {
+                    if click.is_some() {
+                        *star = click;
+                    }
+                    if let Some(p) = *star {
+                        let centre = rect.min + p.to_vec2() * (display_size / img_size);
+                        let r = aperture[0] * display_size.x / img_size.x;
+                        ui.painter().circle_stroke(centre, r, egui::Stroke::new(1.5, PROFILE_COLOR));
+                    }
+                }
+                response
             });
+            let (response, viewport, offset) = (scrolled.inner, scrolled.inner_rect, scrolled.state.offset);
+            self.split = split;
+            self.measure = measure;
+            self.photometry = apertures;
+            self.profile = profile;
+            if let Some(pos) = response.hover_pos() {
+                let rel = (pos - response.rect.min) / display_size * img_size;
+                if rel.x >= 0.0 && rel.y >= 0.0 && rel.x < img_size.x && rel.y < img_size.y {
+                    self.cursor = Some((rel.x as usize, rel.y as usize));
+                }
+            }
+            // Navigator: the whole image with the visible part outlined; click or
+            // drag in it to pan
+            let zoomed_in = display_size.x > viewport.width() + 1.0 || display_size.y > viewport.height() + 1.0;
+            if self.show_navigator && zoomed_in {
+                let size = img_size * (NAVIGATOR_SIZE / img_size.max_elem());
+                let nav = egui::Rect::from_min_size(ui.max_rect().right_bottom() - size - egui::vec2(12.0, 12.0), size);
+                let painter = ui.painter();
+                painter.rect_filled(nav.expand(2.0), 2.0, egui::Color32::from_black_alpha(200));
+                let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                match (rendered, gpu) {
+                    (Rendered::Texture(texture), _) => {
+                        painter.image(mips.last().unwrap_or(texture).id(), nav, uv, egui::Color32::WHITE);
+                    }
+                    (Rendered::Gpu(view), Some(gpu)) => {
+                        painter.add(paint_callback(gpu, nav, *view));
+                    }
+                    (Rendered::Gpu(_), None) => {}
+                }
+                let to_nav = nav.size() / display_size;
+                let visible = egui::Rect::from_min_size(nav.min + offset * to_nav, viewport.size() * to_nav).intersect(nav);
+                painter.rect_stroke(visible, 0.0, egui::Stroke::new(1.5, NAVIGATOR_COLOR));
+                let pan = ui
+                    .interact(nav, ui.id().with("navigator"), egui::Sense::click_and_drag())
+                    .on_hover_cursor(egui::CursorIcon::Move);
+                if let Some(pos) = pan.interact_pointer_pos().filter(|_| pan.clicked() || pan.dragged()) {
+                    // Centre the viewport on the point clicked
+                    let centre = (pos - nav.min) / to_nav;
+                    let max = (display_size - viewport.size()).max(egui::Vec2::ZERO);
+                    self.scroll_to = Some((centre - viewport.size() / 2.0).clamp(egui::Vec2::ZERO, max));
+                    ctx.request_repaint();
+                }
+            }
+            if let Some(arcsec_per_pixel) = plate_scale {
+                let corner = ui.max_rect().left_bottom() + egui::vec2(12.0, -12.0);
+                draw_scale_bar(ui.painter(), corner, display_size.x / img_size.x, arcsec_per_pixel);
+            }
+            let mut corner = ui.max_rect().left_top() + egui::vec2(8.0, 8.0);
+            if !hud.is_empty() {
+                corner.y = draw_hud(ui.painter(), corner, &hud).bottom() + 8.0;
+            }
+            // The previous rendering stays up while the new one is built
+            if let Some(busy) = busy {
+                ui.allocate_ui_at_rect(egui::Rect::from_min_size(corner, egui::vec2(160.0, 20.0)), |ui| {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label(busy);
+                    });
+                });
+            }
         });
 
+        // A setting changed after the texture was built this frame: swap it next frame
+        if self.texture_stale {
+            ctx.request_repaint();
+        }
+    }
+}
+
+/// Draw the measurement points (full-resolution pixel coordinates) on the image
+/// at `origin`, `scale` screen points per pixel: the line between them and a label
+/// with the distance in pixels and, with a `wcs`, on the sky with position angle.
+fn draw_measurement(painter: &egui::Painter, origin: egui::Pos2, scale: egui::Vec2, points: &[egui::Pos2], wcs: Option<Wcs>) {
+    let screen = |p: egui::Pos2| origin + p.to_vec2() * scale;
+    for &p in points {
+        painter.circle_stroke(screen(p), 4.0, egui::Stroke::new(1.5, MEASURE_COLOR));
+    }
+    let [a, b] = points else { return };
+    painter.line_segment([screen(*a), screen(*b)], egui::Stroke::new(1.5, MEASURE_COLOR));
+    let mut text = format!("{:.1} px", a.distance(*b));
+    if let Some(wcs) = wcs {
+        // Point coordinates run from the pixel's corner, WCS ones from its centre
+        let world = |p: egui::Pos2| wcs.pixel_to_world(p.x as f64 - 0.5, p.y as f64 - 0.5);
+        let (sep, pa) = separation(world(*a), world(*b));
+        text += &format!("\n{}  PA {pa:.1}°", format_angle(sep));
+    }
+    let galley = painter.layout_no_wrap(text, egui::FontId::proportional(14.0), MEASURE_COLOR);
+    let mid = screen(egui::pos2((a.x + b.x) / 2.0, (a.y + b.y) / 2.0)) + egui::vec2(8.0, 8.0);
+    let rect = egui::Rect::from_min_size(mid, galley.size()).expand(3.0);
+    painter.rect_filled(rect, 3.0, egui::Color32::from_black_alpha(180));
+    painter.galley(mid, galley, MEASURE_COLOR);
+}
+
+/// Sliders for the aperture radius and the inner / outer annulus radii, keeping the
+/// annulus outside the aperture and at least a pixel wide.
+fn aperture_sliders(ui: &mut egui::Ui, aperture: &mut [f32; 3]) {
+    let [radius, inner, outer] = aperture;
+    egui::Grid::new("aperture_radii_grid").show(ui, |ui| {
+        ui.label("Aperture");
+        ui.add(egui::Slider::new(radius, 1.0..=30.0).suffix(" px"));
+        ui.end_row();
+        ui.label("Annulus inner");
+        ui.add(egui::Slider::new(inner, 1.0..=50.0).suffix(" px"));
+        ui.end_row();
+        ui.label("Annulus outer");
+        ui.add(egui::Slider::new(outer, 2.0..=60.0).suffix(" px"));
+        ui.end_row();
+    });
+    *inner = inner.max(*radius);
+    *outer = outer.max(*inner + 1.0);
+}
+
+/// Plot the ring means of `profile` against radius with the fitted Gaussian, and
+/// mark the half-flux radius and half the FWHM; the aperture `radius` is shaded.
+fn draw_profile(ui: &mut egui::Ui, profile: &RadialProfile, radius: f32) {
+    let (response, painter) = ui.allocate_painter(PROFILE_PLOT, egui::Sense::hover());
+    let rect = response.rect;
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+    let r_max = profile.bins.last().map_or(1.0, |b| b.0 + PROFILE_BIN).max(1.0);
+    let top = profile.bins.iter().map(|b| b.1).fold(0.0f32, f32::max).max(f32::MIN_POSITIVE);
+    let bottom = profile.bins.iter().map(|b| b.1).fold(0.0f32, f32::min);
+    let to_screen = |r: f32, v: f32| {
+        let plot = rect.shrink(6.0);
+        egui::pos2(plot.left() + r / r_max * plot.width(), plot.bottom() - (v - bottom) / (top - bottom) * plot.height())
+    };
+    let faint = ui.visuals().weak_text_color();
+    painter.line_segment([to_screen(0.0, 0.0), to_screen(r_max, 0.0)], egui::Stroke::new(1.0, faint));
+    let aperture = egui::Rect::from_x_y_ranges(rect.left()..=to_screen(radius.min(r_max), 0.0).x, rect.y_range());
+    painter.rect_filled(aperture, 0.0, PROFILE_COLOR.gamma_multiply(0.08));
+    if let Some((peak, sigma)) = profile.gaussian {
+        let curve = (0..=100)
+            .map(|i| {
+                let r = i as f32 / 100.0 * r_max;
+                to_screen(r, (peak * (-r * r / (2.0 * sigma * sigma)).exp()).max(bottom))
+            })
+            .collect();
+        painter.add(egui::Shape::line(curve, egui::Stroke::new(1.5, PROFILE_COLOR)));
+    }
+    for &(r, v) in &profile.bins {
+        painter.circle_filled(to_screen(r, v), 2.0, ui.visuals().text_color());
+    }
+    let font = egui::FontId::proportional(12.0);
+    for (r, label) in [(profile.hfr, "HFR"), (profile.fwhm.map(|f| f / 2.0), "FWHM/2")] {
+        let Some(r) = r.filter(|&r| r < r_max) else { continue };
+        let x = to_screen(r, 0.0).x;
+        painter.vline(x, rect.y_range(), egui::Stroke::new(1.0, faint));
+        painter.text(egui::pos2(x + 3.0, rect.top() + 2.0), egui::Align2::LEFT_TOP, label, font.clone(), faint);
+    }
+    painter.text(rect.right_bottom() - egui::vec2(4.0, 4.0), egui::Align2::RIGHT_BOTTOM, format!("{r_max:.0} px"), font, faint);
+}
+
+/// Plot `spectrum` over the whole of `ui`: flux against wavelength, with the
+/// sample under the pointer read out in the top-right corner.
+fn draw_spectrum(ui: &mut egui::Ui, spectrum: &Spectrum) {
+    let (response, painter) = ui.allocate_painter(ui.available_size(), egui::Sense::hover());
+    let rect = response.rect;
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+    let samples: Vec<(f64, f32)> = spectrum
+        .wavelength
+        .iter()
+        .copied()
+        .zip(spectrum.flux.iter().copied())
+        .filter(|(_, f)| f.is_finite())
+        .collect();
+    let (Some(&(first, _)), Some(&(last, _))) = (samples.first(), samples.last()) else { return };
+    let (mut bottom, mut top) =
+        samples.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &(_, f)| (lo.min(f), hi.max(f)));
+    let pad = ((top - bottom) * 0.05).max(f32::EPSILON);
+    (bottom, top) = (bottom - pad, top + pad);
+    let plot = rect.shrink2(egui::vec2(60.0, 24.0));
+    let to_screen = |w: f64, f: f32| {
+        let x = if last == first { 0.5 } else { ((w - first) / (last - first)) as f32 };
+        egui::pos2(plot.left() + x * plot.width(), plot.bottom() - (f - bottom) / (top - bottom) * plot.height())
+    };
+    let faint = ui.visuals().weak_text_color();
+    painter.rect_stroke(plot, 0.0, egui::Stroke::new(1.0, faint));
+    // Gaps where the data is NaN break the line
+    let mut segment = Vec::new();
+    for (w, f) in spectrum.wavelength.iter().zip(&spectrum.flux) {
+        if f.is_finite() {
+            segment.push(to_screen(*w, *f));
+        } else if !segment.is_empty() {
+            painter.add(egui::Shape::line(std::mem::take(&mut segment), egui::Stroke::new(1.5, SPECTRUM_COLOR)));
+        }
+    }
+    painter.add(egui::Shape::line(segment, egui::Stroke::new(1.5, SPECTRUM_COLOR)));
+
+    let font = egui::FontId::proportional(12.0);
+    let below = plot.bottom() + 4.0;
+    painter.text(egui::pos2(plot.left(), below), egui::Align2::LEFT_TOP, format_value(first as f32), font.clone(), faint);
+    painter.text(egui::pos2(plot.right(), below), egui::Align2::RIGHT_TOP, format_value(last as f32), font.clone(), faint);
+    painter.text(egui::pos2(plot.center().x, below), egui::Align2::CENTER_TOP, &spectrum.axis, font.clone(), faint);
+    for (f, align) in [(top, egui::Align2::RIGHT_TOP), (bottom, egui::Align2::RIGHT_BOTTOM)] {
+        let pos = egui::pos2(plot.left() - 4.0, to_screen(first, f).y);
+        painter.text(pos, align, format_value(f), font.clone(), faint);
+    }
+    if let Some(pointer) = response.hover_pos().filter(|p| plot.x_range().contains(p.x)) {
+        let w = first + (pointer.x - plot.left()) as f64 / plot.width() as f64 * (last - first);
+        let nearest = samples.iter().min_by(|a, b| (a.0 - w).abs().total_cmp(&(b.0 - w).abs()));
+        if let Some(&(w, f)) = nearest {
+            let at = to_screen(w, f);
+            painter.vline(at.x, plot.y_range(), egui::Stroke::new(1.0, faint));
+            painter.circle_filled(at, 3.0, SPECTRUM_COLOR);
+            let text = format!("{}  {}", format_value(w as f32), format_value(f));
+            let corner = plot.right_top() + egui::vec2(-4.0, 4.0);
+            painter.text(corner, egui::Align2::RIGHT_TOP, text, font, ui.visuals().text_color());
+        }
+    }
+}
+
+/// Colour of a frame-type tag in the file browser.
+fn frame_color(kind: FrameType, ui: &egui::Ui) -> egui::Color32 {
+    match kind {
+        FrameType::Light => egui::Color32::from_rgb(110, 200, 110),
+        FrameType::Dark => egui::Color32::from_rgb(160, 130, 220),
+        FrameType::Flat => egui::Color32::from_rgb(230, 190, 80),
+        FrameType::Bias => egui::Color32::from_rgb(100, 170, 255),
+        FrameType::Unknown => ui.visuals().weak_text_color(),
+    }
+}
+
+/// Circle and name every catalogue entry that falls on the image (`size`
+/// full-resolution pixels, shown at `scale` points per pixel from `origin`).
+fn draw_catalog(
+    painter: &egui::Painter,
+    origin: egui::Pos2,
+    scale: egui::Vec2,
+    entries: &[CatalogEntry],
+    wcs: &Wcs,
+    size: egui::Vec2,
+) {
+    let font = egui::FontId::proportional(12.0);
+    for entry in entries {
+        let Some((x, y)) = wcs.world_to_pixel(entry.ra, entry.dec) else { continue };
+        // Pixel centres are at +0.5 on screen, as for the measurement tool
+        let p = egui::vec2(x as f32 + 0.5, y as f32 + 0.5);
+        if p.x < 0.0 || p.y < 0.0 || p.x > size.x || p.y > size.y {
+            continue;
+        }
+        let centre = origin + p * scale;
+        painter.circle_stroke(centre, 8.0, egui::Stroke::new(1.5, CATALOG_COLOR));
+        painter.text(centre + egui::vec2(9.0, -9.0), egui::Align2::LEFT_BOTTOM, &entry.name, font.clone(), CATALOG_COLOR);
+    }
+}
+
+/// Draw the HUD's lines on a translucent backdrop with its top-left at `corner`;
+/// returns the area covered.
+fn draw_hud(painter: &egui::Painter, corner: egui::Pos2, lines: &[String]) -> egui::Rect {
+    let galley = painter.layout_no_wrap(lines.join("\n"), egui::FontId::monospace(13.0), egui::Color32::WHITE);
+    let rect = egui::Rect::from_min_size(corner, galley.size() + egui::vec2(12.0, 12.0));
+    painter.rect_filled(rect, 4.0, egui::Color32::from_black_alpha(150));
+    painter.galley(rect.min + egui::vec2(6.0, 6.0), galley, egui::Color32::WHITE);
+    rect
+}
+
+/// Draw a scale bar with its bottom-left end at `corner`, for an image shown at
+/// `scale` screen points per full-resolution pixel: a round angle when the
+/// plate scale is known, else a round number of pixels.
+fn draw_scale_bar(painter: &egui::Painter, corner: egui::Pos2, scale: f32, arcsec_per_pixel: Option<f64>) {
+    const ANGLES: [f64; 17] = [
+        1.0, 2.0, 5.0, 10.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 900.0, 1800.0, 3600.0, 7200.0, 18000.0,
+        36000.0, 72000.0,
+    ];
+    // Longest round length that fits, in pixels, with its label
+    let fits = |pixels: f64| pixels * scale as f64 <= SCALE_BAR_MAX as f64;
+    let (pixels, label) = match arcsec_per_pixel {
+        Some(arcsec) => {
+            let angle = ANGLES.into_iter().rev().find(|a| fits(a / arcsec)).unwrap_or(ANGLES[0]);
+            let label = if angle < 60.0 {
+                format!("{angle:.0} arcsec")
+            } else if angle < 3600.0 {
+                format!("{:.0} arcmin", angle / 60.0)
+            } else {
+                format!("{:.0}°", angle / 3600.0)
+            };
+            (angle / arcsec, label)
+        }
+        None => {
+            let round = (0..9).flat_map(|e| [1.0, 2.0, 5.0].map(|m| m * 10f64.powi(e)));
+            let pixels = round.take_while(|&n| fits(n)).last().unwrap_or(1.0);
+            (pixels, format!("{pixels:.0} px"))
+        }
+    };
+    let length = pixels as f32 * scale;
+    let (left, right) = (corner, corner + egui::vec2(length, 0.0));
+    let galley = painter.layout_no_wrap(label, egui::FontId::proportional(14.0), egui::Color32::WHITE);
+    let text_pos = egui::pos2(corner.x, corner.y - 8.0 - galley.size().y);
+    let backdrop = egui::Rect::from_min_max(text_pos, right).union(egui::Rect::from_min_size(text_pos, galley.size()));
+    painter.rect_filled(backdrop.expand(4.0), 3.0, egui::Color32::from_black_alpha(160));
+    let stroke = egui::Stroke::new(2.0, egui::Color32::WHITE);
+    painter.line_segment([left, right], stroke);
+    for end in [left, right] {
+        painter.line_segment([end, end - egui::vec2(0.0, 5.0)], stroke);
+    }
+    painter.galley(text_pos, galley, egui::Color32::WHITE);
+}
+
+/// Draw the 3 × 3 FWHM grid over the image in `rect`: each cell's median FWHM,
+/// coloured by how much it exceeds the best cell, and a summary of the spread
+/// between the corners at the top of the visible part (`clip`).
+fn draw_tilt_map(painter: &egui::Painter, rect: egui::Rect, clip: egui::Rect, grid: &[[Option<f32>; 3]; 3], stars: usize) {
+    let line = egui::Stroke::new(1.0, egui::Color32::from_white_alpha(90));
+    for i in 1..3 {
+        painter.vline(rect.left() + rect.width() * i as f32 / 3.0, rect.y_range(), line);
+        painter.hline(rect.x_range(), rect.top() + rect.height() * i as f32 / 3.0, line);
+    }
+    let best = grid.iter().flatten().flatten().copied().fold(f32::INFINITY, f32::min);
+    let font = egui::FontId::proportional(16.0);
+    for (row, cells) in grid.iter().enumerate() {
+        for (col, fwhm) in cells.iter().enumerate() {
+            let centre = rect.min + rect.size() * egui::vec2((col as f32 + 0.5) / 3.0, (row as f32 + 0.5) / 3.0);
+            let (text, color) = match fwhm {
+                Some(f) if f / best <= 1.1 => (format!("{f:.2}"), TILT_GOOD),
+                Some(f) if f / best <= 1.25 => (format!("{f:.2}"), TILT_FAIR),
+                Some(f) => (format!("{f:.2}"), TILT_POOR),
+                None => ("–".to_string(), egui::Color32::GRAY),
+            };
+            let galley = painter.layout_no_wrap(text, font.clone(), color);
+            let pos = centre - galley.size() / 2.0;
+            painter.rect_filled(egui::Rect::from_min_size(pos, galley.size()).expand(4.0), 3.0, egui::Color32::from_black_alpha(170));
+            painter.galley(pos, galley, color);
+        }
+    }
+    let corners: Vec<f32> = [grid[0][0], grid[0][2], grid[2][0], grid[2][2]].into_iter().flatten().collect();
+    let mut summary = format!("FWHM (px) from {stars} stars");
+    if corners.len() == 4 {
+        let (lo, hi) = corners.iter().fold((f32::INFINITY, 0.0f32), |(lo, hi), &f| (lo.min(f), hi.max(f)));
+        summary += &format!("  ·  corners {lo:.2}–{hi:.2} ({:+.0} %)", (hi / lo - 1.0) * 100.0);
+    }
+    let top = egui::pos2(rect.center().x, rect.top().max(clip.top()) + 6.0);
+    let galley = painter.layout_no_wrap(summary, egui::FontId::proportional(14.0), egui::Color32::WHITE);
+    let pos = top - egui::vec2(galley.size().x / 2.0, 0.0);
+    painter.rect_filled(egui::Rect::from_min_size(pos, galley.size()).expand(3.0), 3.0, egui::Color32::from_black_alpha(180));
+    painter.galley(pos, galley, egui::Color32::WHITE);
+}
+
+/// Draw the photometry apertures (full-resolution pixel coordinates) on the image
+/// at `origin`, `scale` screen points per pixel: the aperture circle, the annulus
+/// in thin lines and the aperture's number.
+fn draw_apertures(painter: &egui::Painter, origin: egui::Pos2, scale: egui::Vec2, points: &[egui::Pos2], radii: [f32; 3]) {
+    let [radius, inner, outer] = radii;
+    let thin = egui::Stroke::new(1.0, APERTURE_COLOR.gamma_multiply(0.6));
+    for (i, &p) in points.iter().enumerate() {
+        let centre = origin + p.to_vec2() * scale;
+        painter.circle_stroke(centre, radius * scale.x, egui::Stroke::new(1.5, APERTURE_COLOR));
+        painter.circle_stroke(centre, inner * scale.x, thin);
+        painter.circle_stroke(centre, outer * scale.x, thin);
+        let label = centre + egui::vec2(radius * scale.x + 3.0, -(radius * scale.x + 3.0));
+        painter.text(label, egui::Align2::LEFT_BOTTOM, format!("{}", i + 1), egui::FontId::proportional(14.0), APERTURE_COLOR);
+    }
+}
+
+/// An angle given in degrees, as `1° 02′ 03.4″`, `2′ 03.4″` or `12.3″`.
+fn format_angle(deg: f64) -> String {
+    let arcsec = deg * 3600.0;
+    if arcsec < 60.0 {
+        format!("{arcsec:.1}″")
+    } else if arcsec < 3600.0 {
+        format!("{:.0}′ {:04.1}″", (arcsec / 60.0).floor(), arcsec % 60.0)
+    } else {
+        let (d, m) = ((arcsec / 3600.0).floor(), (arcsec % 3600.0 / 60.0).floor());
+        format!("{d:.0}° {m:02.0}′ {:04.1}″", arcsec % 60.0)
+    }
+}
+
+/// Pixel value for the readout: integers as-is, other values to four decimals.
+fn format_value(v: f32) -> String {
+    if v.fract() == 0.0 {
+        format!("{v:.0}")
+    } else {
+        format!("{v:.4}")
+    }
+}
+
+/// A loaded frame with more than this fraction of near-full-scale pixels gets a
+/// saturation warning in the menu bar.
+const SATURATION_WARN: f32 = 0.01;
+
+/// Black clipping above this fraction of pixels is flagged in the status bar.
+const CLIP_WARN_BLACK: f32 = 0.01;
+/// White clipping above this fraction of pixels is flagged in the status bar.
+const CLIP_WARN_WHITE: f32 = 0.001;
+
+/// Status-bar readout of black (▼) / white (▲) clipped pixels, per channel for RGB;
+/// channels clipping more than the warning thresholds are highlighted.
+fn clipping_label(ui: &mut egui::Ui, clipping: &[Clipping]) {
+    let names = if clipping.len() == 3 { ["R ", "G ", "B "] } else { [""; 3] };
+    let pct = |f: f32| if f > 0.0 && f < 0.0001 { "<0.01".to_string() } else { format!("{:.2}", f * 100.0) };
+    // Laid out right-to-left: last channel first.
+    for (clip, name) in clipping.iter().zip(names).rev() {
+        let text = format!("{name}▼{}% ▲{}%", pct(clip.black), pct(clip.white));
+        let warn = clip.black > CLIP_WARN_BLACK || clip.white > CLIP_WARN_WHITE;
+        let color = if warn { egui::Color32::from_rgb(255, 160, 60) } else { ui.visuals().weak_text_color() };
+        ui.label(egui::RichText::new(text).monospace().color(color));
+    }
+    ui.label(egui::RichText::new("Clipped:").weak())
+        .on_hover_text("Pixels rendered pure black (▼) / pure white (▲) by the current stretch");
+}
+
+/// Format one header value for the capture summary bar, e.g. `EXPTIME 300 s`.
+/// Numbers are rounded to two decimals; well-known keywords get their unit.
+fn capture_field(key: &str, value: &str) -> String {
+    let value = match value.parse::<f64>() {
+        Ok(v) => format!("{}", (v * 100.0).round() / 100.0),
+        Err(_) => value.to_string(),
+    };
+    let unit = match key {
+        "EXPTIME" | "EXPOSURE" => " s",
+        "CCD-TEMP" | "SET-TEMP" => " °C",
+        "FOCALLEN" | "APTDIA" => " mm",
+        "XPIXSZ" | "YPIXSZ" => " µm",
+        _ => "",
+    };
+    format!("{key} {value}{unit}")
+}
+
+/// Night theme: black and dim red only, so the screen does not spoil dark adaptation.
+fn night_visuals() -> egui::Visuals {
+    use egui::Color32;
+    let red = Color32::from_rgb(190, 30, 30);
+    let dim = Color32::from_rgb(100, 15, 15);
+    let mut v = egui::Visuals::dark();
+    v.override_text_color = Some(red);
+    v.hyperlink_color = red;
+    v.warn_fg_color = red;
+    v.error_fg_color = Color32::from_rgb(255, 40, 40);
+    v.panel_fill = Color32::from_rgb(8, 0, 0);
+    v.window_fill = Color32::from_rgb(14, 0, 0);
+    v.faint_bg_color = Color32::from_rgb(20, 0, 0);
+    v.extreme_bg_color = Color32::BLACK;
+    v.window_stroke.color = dim;
+    v.selection.bg_fill = Color32::from_rgb(70, 0, 0);
+    v.selection.stroke.color = red;
+    let widgets = &mut v.widgets;
+    for (w, bg) in [
+        (&mut widgets.noninteractive, 10),
+        (&mut widgets.inactive, 30),
+        (&mut widgets.hovered, 50),
+        (&mut widgets.active, 70),
+        (&mut widgets.open, 40),
+    ] {
+        w.bg_fill = Color32::from_rgb(bg, 0, 0);
+        w.weak_bg_fill = Color32::from_rgb(bg, 0, 0);
+        w.bg_stroke.color = dim;
+        w.fg_stroke.color = red;
+    }
+    v
+}
+
+/// `path` made absolute against the working directory (without resolving links).
+fn absolute_path(path: &std::path::Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Rows of the File info window for `img`, loaded from `path`: the stored data
+/// format and how it was converted to the displayed values.
+fn file_info(path: &std::path::Path, img: &FitsImage) -> Vec<(&'static str, String)> {
+    let format = img.data_format();
+    let host = if cfg!(target_endian = "little") { "little" } else { "big" };
+    let optional = |v: Option<String>| v.unwrap_or_else(|| "(absent)".to_string());
+    let mut rows = vec![
+        ("File", absolute_path(path).display().to_string()),
+        ("Size", path.metadata().map_or("?".to_string(), |m| format!("{} bytes", m.len()))),
+        ("HDU", match img.channel_hdus {
+            Some([r, g, b]) => format!("{r}, {g}, {b} stacked as R, G, B, of {}", img.hdu_count),
+            None => format!("{} of {}", img.hdu, img.hdu_count),
+        }),
+        ("Dimensions", format!("{} × {} × {}", img.width, img.height, img.channels)),
+        ("BITPIX", format.bitpix.map_or("(absent)".to_string(), |b| b.to_string())),
+        ("Stored as", format.storage()),
+        ("BSCALE", optional(format.bscale.map(|v| v.to_string()))),
+        ("BZERO", optional(format.bzero.map(|v| v.to_string()))),
+        ("BLANK", optional(format.blank.map(|v| v.to_string()))),
+        ("Byte order", format!("big-endian in the file, {host}-endian on this machine")),
+        ("Scaling", if format.scaled() { "applied by cfitsio on read".to_string() } else { "none".to_string() }),
+        ("Physical values", format.physical()),
+    ];
+    if img.is_bayer {
+        let pattern = img.header("BAYERPAT").unwrap_or("?").trim_matches('\'').trim();
+        let range = if img.bitdepth_max == 255.0 { "8-bit, debayered at 8 bits" } else { "brought into 0–65535 and debayered" };
+        rows.push(("Bayer", format!("{pattern} mosaic, {range}")));
+    }
+    let ceiling = if img.bitdepth_max > 0.0 { format_value(img.bitdepth_max) } else { "data range (float)".to_string() };
+    rows.push(("Full scale", ceiling));
+    if let (Some(lo), Some(hi)) = (img.datamin, img.datamax) {
+        rows.push(("DATAMIN / DATAMAX", format!("{} / {}", format_value(lo), format_value(hi))));
+    }
+    if let Some(n) = img.hot_pixels_fixed {
+        rows.push(("Hot pixels fixed", n.to_string()));
+    }
+    rows.push(("Pedestal", match (img.pedestal, img.header("PEDESTAL")) {
+        (Some(p), Some(raw)) => format!("{} subtracted (PEDESTAL = {raw})", format_value(p)),
+        _ => "none".to_string(),
+    }));
+    rows
+}
+
+/// Move `path` to the trash, or delete it outright where there is no trash.
+fn trash_or_remove(path: &std::path::Path) -> Result<(), String> {
+    trash::delete(path)
+        .map_err(|e| e.to_string())
+        .or_else(|_| std::fs::remove_file(path).map_err(|e| e.to_string()))
+}
+
+/// Right-click menu of a file name: reveal it in the file manager or copy its path.
+fn file_context_menu(response: &egui::Response, path: &std::path::Path, reveal: &mut Option<PathBuf>) {
+    response.context_menu(|ui| {
+        if ui.button("Reveal in file manager").clicked() {
+            *reveal = Some(path.to_path_buf());
+            ui.close_menu();
+        }
+        if ui.button("Copy full path").clicked() {
+            ui.output_mut(|o| o.copied_text = absolute_path(path).display().to_string());
+            ui.close_menu();
+        }
+    });
+}
+
+/// Render `img` for output. AutoStretch goes through the STF in `stf`, computing it
+/// first (shifted by `offset`) if it is None.
+fn render_image(
+    img: &FitsImage,
+    stretch: Stretch,
+    view: ChannelView,
+    color: ColorOptions,
+    stf: &mut Option<Vec<Stf>>,
+    white_clip: f32,
+    offset: StfOffset,
+) -> Vec<u8> {
+    match stretch {
+        Stretch::AutoStretch { .. } => {
+            let stf = stf.get_or_insert_with(|| adjusted_stf(img, view, white_clip, color, offset));
+            img.to_rgba_stf(view, stf, color)
+        }
+        stretch => img.to_rgba(stretch, view, color),
+    }
+}
+
+/// The automatic STF of `img` with the STF slider adjustments `offset` applied.
+fn adjusted_stf(img: &FitsImage, view: ChannelView, white_clip: f32, color: ColorOptions, offset: StfOffset) -> Vec<Stf> {
+    let mut stf = img.auto_stf(view, white_clip, color);
+    shift_stf(&mut stf, offset);
+    stf
+}
+
+/// Move every channel's STF by `offset`, keeping each parameter in its range.
+fn shift_stf(stf: &mut [Stf], offset: StfOffset) {
+    for s in stf {
+        s.shadows = (s.shadows + offset.shadows).clamp(0.0, 1.0);
+        s.midtones = (s.midtones + offset.midtones).clamp(1e-4, 1.0 - 1e-4);
+        s.highlights = (s.highlights + offset.highlights).clamp(s.shadows, 1.0);
+    }
+}
+
+/// `v` limited to `range`.
+fn clamp_to(v: f32, range: RangeInclusive<f32>) -> f32 {
+    v.clamp(*range.start(), *range.end())
+}
+
+/// A range of fractions in percent, for the controls that show percentages.
+fn percent(range: RangeInclusive<f32>) -> RangeInclusive<f32> {
+    range.start() * 100.0..=range.end() * 100.0
+}
+
+/// Name of a stretch mode in the config file and on the command line.
+pub(crate) fn stretch_id(stretch: Stretch) -> &'static str {
+    match stretch {
+        Stretch::AutoStretch { .. } => "auto",
+        Stretch::Percentile { .. } => "percentile",
+        Stretch::Linear => "linear",
+        Stretch::RawLinear => "raw",
+        Stretch::Log { .. } => "log",
+        Stretch::HistEq { .. } => "equalised",
+    }
+}
+
+/// Short name of a stretch for the menu bar and the split-screen labels.
+fn stretch_label(stretch: Stretch) -> String {
+    match stretch {
+        Stretch::AutoStretch { .. } => "Auto".to_string(),
+        Stretch::Percentile { low, high } => format!("{:.1}–{:.1}%", low * 100.0, high * 100.0),
+        Stretch::Linear => "Linear".to_string(),
+        Stretch::RawLinear => "Raw".to_string(),
+        Stretch::Log { k } => format!("Log k={k:.0}"),
+        Stretch::HistEq { .. } => "Equalised".to_string(),
+    }
+}
+
+/// Upload the mip pyramid (½, ¼, … down to about `MIP_MIN_SIZE`) of an RGBA image.
+/// egui has no GPU mipmaps: building it ourselves lets zoomed-out views sample a
+/// box-filtered level instead of aliasing dense star fields.
+fn load_mips(ctx: &egui::Context, name: &str, rgba: Vec<u8>, width: usize, height: usize) -> Vec<TextureHandle> {
+    let mut mips = Vec::new();
+    let (mut level, mut w, mut h) = (rgba, width, height);
+    while w.max(h) >= 2 * MIP_MIN_SIZE {
+        (level, w, h) = halve_rgba(&level, w, h);
+        let color_image = egui::ColorImage::from_rgba_unmultiplied([w, h], &level);
+        mips.push(ctx.load_texture(format!("{name}_mip{}", mips.len() + 1), color_image, egui::TextureOptions::LINEAR));
+    }
+    mips
+}
+
+/// Downsample an RGBA buffer by 2 in each direction with a 2×2 box filter.
+/// Odd trailing rows/columns are dropped.
+fn halve_rgba(rgba: &[u8], width: usize, height: usize) -> (Vec<u8>, usize, usize) {
+    let (w, h) = (width / 2, height / 2);
+    let mut out = vec![0u8; w * h * 4];
+    for y in 0..h {
+        let row0 = &rgba[(2 * y) * width * 4..];
+        let row1 = &rgba[(2 * y + 1) * width * 4..];
+        for x in 0..w {
+            for c in 0..4 {
+                let i = 2 * x * 4 + c;
+                let sum = row0[i] as u16 + row0[i + 4] as u16 + row1[i] as u16 + row1[i + 4] as u16;
+                out[(y * w + x) * 4 + c] = ((sum + 2) / 4) as u8;
+            }
+        }
+    }
+    (out, w, h)
+}
+
+/// File picked by the go-to overlay: a 1-based index (clamped to the list) or the
+/// first file whose name contains `query`, ignoring case.
+fn goto_target(files: &[PathBuf], query: &str) -> Option<usize> {
+    let query = query.trim();
+    if query.is_empty() || files.is_empty() {
+        return None;
+    }
+    if let Ok(n) = query.parse::<usize>() {
+        return Some(n.clamp(1, files.len()) - 1);
+    }
+    let query = query.to_lowercase();
+    files.iter().position(|f| {
+        f.file_name().is_some_and(|n| n.to_string_lossy().to_lowercase().contains(&query))
+    })
+}
+
+/// Index of the most recently modified file in `files`.
+fn newest_file(files: &[PathBuf]) -> Option<usize> {
+    files
+        .iter()
+        .enumerate()
+        .filter_map(|(i, p)| Some((i, p.metadata().ok()?.modified().ok()?)))
+        .max_by_key(|&(_, t)| t)
+        .map(|(i, _)| i)
+}
+
+/// Subdirectories of `dir`, sorted, leaving out hidden ones; read once and
+/// kept in `cache`.
+fn subdirectories<'a>(dir: &std::path::Path, cache: &'a mut HashMap<PathBuf, Vec<PathBuf>>) -> &'a [PathBuf] {
+    cache.entry(dir.to_path_buf()).or_insert_with(|| {
+        let Ok(entries) = std::fs::read_dir(dir) else { return Vec::new() };
+        let mut dirs: Vec<PathBuf> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.is_dir() && !p.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.')))
+            .collect();
+        dirs.sort();
+        dirs
+    })
+}
+
+/// The folders below `dir` as a tree: the arrow expands a folder, reading its
+/// subdirectories only then; clicking a name sets `open` to re-root the browser there.
+fn folder_tree(
+    ui: &mut egui::Ui,
+    dir: &std::path::Path,
+    cache: &mut HashMap<PathBuf, Vec<PathBuf>>,
+    open: &mut Option<PathBuf>,
+) {
+    for sub in subdirectories(dir, cache).to_vec() {
+        let name = sub.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let id = ui.make_persistent_id(&sub);
+        egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, false)
+            .show_header(ui, |ui| {
+                if ui.selectable_label(false, format!("📁 {name}")).on_hover_text("Browse this folder").clicked() {
+                    *open = Some(sub.clone());
+                }
+            })
+            .body(|ui| folder_tree(ui, &sub, cache, open));
     }
 }
 
-fn collect_fits_files(dir: &std::path::Path) -> Vec<PathBuf> {
+/// The files of `dir` that pass `filter`, sorted by name.
+pub(crate) fn collect_fits_files(dir: &std::path::Path, filter: &FileFilter) -> Vec<PathBuf> {
     let Ok(entries) = std::fs::read_dir(dir) else {
         return Vec::new();
     };
     let mut files: Vec<PathBuf> = entries
         .flatten()
         .map(|e| e.path())
-        .filter(|p| {
-            p.is_file()
-                && matches!(
-                    p.extension()
-                        .and_then(|e| e.to_str())
-                        .map(|e| e.to_ascii_lowercase())
-                        .as_deref(),
-                    Some("fits" | "fit" | "fz")
-                )
-        })
+        .filter(|p| p.is_file() && filter.matches(p))
         .collect();
     files.sort();
     files
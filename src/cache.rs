@@ -0,0 +1,87 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::fits::{FitsImage, LoadOptions};
+
+/// Default budget for decoded pixel data kept around for quick back-and-forth browsing.
+pub const DEFAULT_BUDGET_BYTES: usize = 1 << 30;
+
+struct Entry {
+    path: PathBuf,
+    opts: LoadOptions,
+    /// Modification time of the file when it was decoded
+    mtime: Option<SystemTime>,
+    image: FitsImage,
+}
+
+/// Least-recently-used cache of decoded images, keyed by path and load options
+/// and bounded by the total size of the cached pixel data.
+pub struct ImageCache {
+    /// Most recently used entry last
+    entries: VecDeque<Entry>,
+    budget_bytes: usize,
+    used_bytes: usize,
+}
+
+impl ImageCache {
+    pub fn new(budget_bytes: usize) -> Self {
+        ImageCache { entries: VecDeque::new(), budget_bytes, used_bytes: 0 }
+    }
+
+    /// Return a copy of the cached image for `path` decoded with `opts`, if the
+    /// file has not been modified since. Stale entries are dropped.
+    pub fn get(&mut self, path: &Path, opts: LoadOptions) -> Option<FitsImage> {
+        let idx = self.entries.iter().position(|e| e.path == path && e.opts == opts)?;
+        let entry = self.entries.remove(idx)?;
+        if entry.mtime.is_none() || entry.mtime != file_mtime(path) {
+            self.used_bytes -= image_bytes(&entry.image);
+            return None;
+        }
+        let image = entry.image.clone();
+        self.entries.push_back(entry);
+        Some(image)
+    }
+
+    /// Remember a freshly decoded image, evicting the oldest entries when over budget.
+    /// Images larger than the whole budget are not cached.
+    pub fn insert(&mut self, path: PathBuf, opts: LoadOptions, image: FitsImage) {
+        let bytes = image_bytes(&image);
+        if bytes > self.budget_bytes {
+            return;
+        }
+        if let Some(idx) = self.entries.iter().position(|e| e.path == path && e.opts == opts) {
+            if let Some(old) = self.entries.remove(idx) {
+                self.used_bytes -= image_bytes(&old.image);
+            }
+        }
+        let mtime = file_mtime(&path);
+        self.used_bytes += bytes;
+        self.entries.push_back(Entry { path, opts, mtime, image });
+        while self.used_bytes > self.budget_bytes {
+            let Some(old) = self.entries.pop_front() else { break };
+            self.used_bytes -= image_bytes(&old.image);
+        }
+    }
+
+    /// Drop every cached decode of `path` (all load options).
+    pub fn remove(&mut self, path: &Path) {
+        let used = &mut self.used_bytes;
+        self.entries.retain(|e| {
+            let keep = e.path != path;
+            if !keep {
+                *used -= image_bytes(&e.image);
+            }
+            keep
+        });
+    }
+}
+
+fn image_bytes(img: &FitsImage) -> usize {
+    let raw = img.raw_mosaic.as_ref().map_or(0, |m| m.data.len() * std::mem::size_of::<u16>());
+    img.data.len() * std::mem::size_of::<f32>() + raw
+}
+
+fn file_mtime(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
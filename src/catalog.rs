@@ -0,0 +1,96 @@
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+
+/// Bright stars and Messier objects shipped with the viewer (`assets/catalog.csv`).
+const BUNDLED: &str = include_str!("../assets/catalog.csv");
+
+/// A named position on the sky, drawn over plate-solved images.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CatalogEntry {
+    /// Label drawn next to the marker
+    pub name: String,
+    /// Right ascension in degrees (J2000)
+    pub ra: f64,
+    /// Declination in degrees (J2000)
+    pub dec: f64,
+}
+
+/// The bundled catalogue.
+pub fn bundled() -> Vec<CatalogEntry> {
+    parse(BUNDLED).expect("bundled catalogue parses")
+}
+
+/// Read a catalogue from a CSV file of `name,ra,dec` lines (see [`parse`]).
+pub fn load(path: &Path) -> Result<Vec<CatalogEntry>> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    parse(&text).with_context(|| format!("in {}", path.display()))
+}
+
+/// Parse `name,ra,dec` lines. RA is in decimal degrees or sexagesimal hours
+/// (`05:35:17.3` or `05 35 17.3`), Dec in decimal or sexagesimal degrees. Blank
+/// lines, `#` comments and a header line whose RA is not a number are skipped.
+pub fn parse(text: &str) -> Result<Vec<CatalogEntry>> {
+    let mut entries = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let [name, ra, dec, ..] = fields[..] else {
+            bail!("line {}: expected name,ra,dec", n + 1);
+        };
+        let (Some(ra), Some(dec)) = (angle(ra, true), angle(dec, false)) else {
+            if entries.is_empty() && n == first_data_line(text) {
+                continue; // column titles
+            }
+            bail!("line {}: bad coordinates {ra:?}, {dec:?}", n + 1);
+        };
+        if !(0.0..360.0).contains(&ra) || !(-90.0..=90.0).contains(&dec) {
+            bail!("line {}: coordinates out of range", n + 1);
+        }
+        entries.push(CatalogEntry { name: name.to_string(), ra, dec });
+    }
+    Ok(entries)
+}
+
+/// Index of the first line that is neither blank nor a comment.
+fn first_data_line(text: &str) -> usize {
+    text.lines().position(|l| !l.trim().is_empty() && !l.trim().starts_with('#')).unwrap_or(0)
+}
+
+/// An angle in degrees from decimal degrees or `d:m:s` / `d m s`; with `hours`,
+/// sexagesimal values are hours and are converted to degrees.
+fn angle(text: &str, hours: bool) -> Option<f64> {
+    let parts: Vec<&str> = text.split([':', ' ']).filter(|p| !p.is_empty()).collect();
+    if parts.len() == 1 {
+        return parts[0].parse().ok();
+    }
+    let negative = text.trim_start().starts_with('-');
+    let mut value = 0.0;
+    for (part, unit) in parts.iter().zip([1.0, 60.0, 3600.0]) {
+        value += part.trim_start_matches(['+', '-']).parse::<f64>().ok()? / unit;
+    }
+    let value = if negative { -value } else { value };
+    Some(if hours { value * 15.0 } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_decimal_and_sexagesimal() {
+        let text = "# comment\nname,ra,dec\nM42,05:35:17.3,-05:23:28\nVega, 279.2347 , 38.7837\nX,00 30 00,-00 30 00\n";
+        let entries = parse(text).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].name, "M42");
+        assert!((entries[0].ra - 83.822_083).abs() < 1e-5);
+        assert!((entries[0].dec + 5.391_111).abs() < 1e-5);
+        assert_eq!((entries[1].ra, entries[1].dec), (279.2347, 38.7837));
+        assert_eq!((entries[2].ra, entries[2].dec), (7.5, -0.5));
+        assert!(parse("A,400,0").is_err());
+        assert!(parse("A,1,2\nB,x,y").is_err());
+        assert!(bundled().iter().any(|e| e.name == "M31"));
+    }
+}
@@ -0,0 +1,270 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::fits::{DEFAULT_BACKGROUND, DEFAULT_WHITE_CLIP, FITS_EXTENSIONS};
+use crate::keymap::Keymap;
+
+/// Settings persisted between sessions in `<config dir>/fastfits/config.toml`.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub keymap: Keymap,
+    /// Vim-style navigation keys on top of the keymap (see [`crate::keymap::VIM_KEYS`])
+    pub vim_keys: bool,
+    pub theme: Theme,
+    /// View settings of each directory opened, keyed by its canonical path
+    pub dir_views: BTreeMap<PathBuf, DirView>,
+    pub file_filter: FileFilter,
+    /// Next / previous wrap around the ends of the list instead of stopping there
+    pub wrap_navigation: bool,
+    /// CSV of `name,ra,dec` drawn by the catalogue overlay instead of the bundled one
+    pub catalog: Option<PathBuf>,
+    /// Stretch presets saved in Preferences, by name
+    pub presets: BTreeMap<String, StretchPreset>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            keymap: Keymap::default(),
+            vim_keys: false,
+            theme: Theme::default(),
+            dir_views: BTreeMap::new(),
+            file_filter: FileFilter::default(),
+            wrap_navigation: true,
+            catalog: None,
+            presets: BTreeMap::new(),
+        }
+    }
+}
+
+/// Which files of a directory the browser lists.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileFilter {
+    /// Extensions, lower-case and without the dot, matched case-insensitively
+    pub extensions: Vec<String>,
+    /// List every file regardless of extension
+    pub all_files: bool,
+}
+
+impl Default for FileFilter {
+    fn default() -> Self {
+        FileFilter { extensions: FITS_EXTENSIONS.map(String::from).to_vec(), all_files: false }
+    }
+}
+
+impl FileFilter {
+    pub fn matches(&self, path: &Path) -> bool {
+        self.all_files
+            || path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| self.extensions.iter().any(|f| e.eq_ignore_ascii_case(f)))
+    }
+
+    /// Set the extensions from a list such as `fits, .FTS fz`; an empty list
+    /// restores the defaults.
+    pub fn set_extensions(&mut self, list: &str) {
+        let extensions: Vec<String> = list
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .map(|e| e.trim_start_matches('.').to_ascii_lowercase())
+            .filter(|e| !e.is_empty())
+            .collect();
+        self.extensions = if extensions.is_empty() { FileFilter::default().extensions } else { extensions };
+    }
+}
+
+/// Display settings remembered for a directory and restored when it is opened
+/// again; directories without an entry start with the defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DirView {
+    /// Stretch mode: "auto", "percentile", "linear", "raw", "log" or "equalised"
+    pub stretch: String,
+    /// Channel view of colour images: None for the composite, else the plane index
+    pub channel: Option<usize>,
+    /// Composite palette: "rgb", "sho" or "hoo"
+    pub palette: String,
+    pub saturation: f32,
+}
+
+impl Default for DirView {
+    fn default() -> Self {
+        DirView { stretch: "auto".to_string(), channel: None, palette: "rgb".to_string(), saturation: 1.0 }
+    }
+}
+
+/// Stretch and colour settings saved under a name in Preferences, to give other
+/// files the same treatment with one click.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StretchPreset {
+    /// Stretch mode, as in [`DirView::stretch`]
+    pub stretch: String,
+    /// Percentile (fraction) AutoStretch clips to white
+    pub white_clip: f32,
+    /// Low / high percentiles (fractions) of the percentile stretch
+    pub linear_clip: (f32, f32),
+    /// Knee of the logarithmic stretch
+    pub log_knee: f32,
+    /// Histogram equalisation of RGB through one luminance CDF
+    pub histeq_shared: bool,
+    /// Levels AutoStretch takes the R, G, B sky to: midtones and white balance
+    pub background: [f32; 3],
+    /// Colour saturation of the RGB composite (1 = unchanged)
+    pub saturation: f32,
+    /// One AutoStretch curve for all three channels
+    pub linked: bool,
+    /// sRGB output gamma
+    pub srgb: bool,
+    /// STF slider adjustments, re-applied to each image's automatic STF
+    pub stf_offset: StfOffset,
+}
+
+impl Default for StretchPreset {
+    fn default() -> Self {
+        StretchPreset {
+            stretch: "auto".to_string(),
+            white_clip: DEFAULT_WHITE_CLIP,
+            linear_clip: (0.005, 0.995),
+            log_knee: 100.0,
+            histeq_shared: true,
+            background: [DEFAULT_BACKGROUND; 3],
+            saturation: 1.0,
+            linked: false,
+            srgb: false,
+            stf_offset: StfOffset::default(),
+        }
+    }
+}
+
+/// How far the STF sliders moved AutoStretch from the automatic STF, averaged
+/// over the channels like the sliders show it.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StfOffset {
+    /// Added to the black point
+    pub shadows: f32,
+    /// Added to the midtone balance
+    pub midtones: f32,
+    /// Added to the white point
+    pub highlights: f32,
+}
+
+/// UI colour theme chosen in Preferences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Theme {
+    /// Follow the desktop setting where eframe reports it, else dark
+    #[default]
+    System,
+    Dark,
+    Light,
+    /// Dim, all-red UI and image to preserve dark adaptation at the telescope
+    Night,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 4] = [Theme::System, Theme::Dark, Theme::Light, Theme::Night];
+
+    /// Name used in the config file.
+    pub fn id(self) -> &'static str {
+        match self {
+            Theme::System => "system",
+            Theme::Dark => "dark",
+            Theme::Light => "light",
+            Theme::Night => "night",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Theme::System => "System",
+            Theme::Dark => "Dark",
+            Theme::Light => "Light",
+            Theme::Night => "Night (red)",
+        }
+    }
+
+    fn from_id(id: &str) -> Option<Theme> {
+        Theme::ALL.into_iter().find(|t| t.id() == id)
+    }
+}
+
+/// On-disk layout of [`Config`]; every field is optional so older or hand-edited
+/// files keep working.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct ConfigFile {
+    keymap: BTreeMap<String, Vec<String>>,
+    vim_keys: bool,
+    theme: String,
+    dir_views: BTreeMap<String, DirView>,
+    /// Browser extensions; empty for the defaults
+    extensions: Vec<String>,
+    all_files: bool,
+    /// Absent in files written before the setting existed, meaning on
+    wrap_navigation: Option<bool>,
+    catalog: Option<PathBuf>,
+    presets: BTreeMap<String, StretchPreset>,
+}
+
+impl Config {
+    fn path() -> Option<PathBuf> {
+        Some(dirs::config_dir()?.join("fastfits").join("config.toml"))
+    }
+
+    /// Load the config file; a missing file gives the defaults.
+    pub fn load() -> Result<Self> {
+        let Some(path) = Self::path() else { return Ok(Config::default()) };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+        };
+        let file: ConfigFile = toml::from_str(&text).with_context(|| format!("parsing {}", path.display()))?;
+        Ok(Config {
+            keymap: Keymap::from_names(&file.keymap),
+            vim_keys: file.vim_keys,
+            theme: Theme::from_id(&file.theme).unwrap_or_default(),
+            dir_views: file.dir_views.into_iter().map(|(dir, view)| (PathBuf::from(dir), view)).collect(),
+            file_filter: {
+                let mut filter = FileFilter { all_files: file.all_files, ..FileFilter::default() };
+                filter.set_extensions(&file.extensions.join(","));
+                filter
+            },
+            wrap_navigation: file.wrap_navigation.unwrap_or(true),
+            catalog: file.catalog,
+            presets: file.presets,
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path().context("no config directory on this system")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("creating {}", dir.display()))?;
+        }
+        let file = ConfigFile {
+            keymap: self.keymap.to_names(),
+            vim_keys: self.vim_keys,
+            theme: self.theme.id().to_string(),
+            dir_views: self
+                .dir_views
+                .iter()
+                .map(|(dir, view)| (dir.to_string_lossy().into_owned(), view.clone()))
+                .collect(),
+            extensions: if self.file_filter.extensions == FileFilter::default().extensions {
+                Vec::new()
+            } else {
+                self.file_filter.extensions.clone()
+            },
+            all_files: self.file_filter.all_files,
+            wrap_navigation: Some(self.wrap_navigation),
+            catalog: self.catalog.clone(),
+            presets: self.presets.clone(),
+        };
+        let text = toml::to_string_pretty(&file).context("serialising config")?;
+        std::fs::write(&path, text).with_context(|| format!("writing {}", path.display()))
+    }
+}
@@ -0,0 +1,361 @@
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use anyhow::{bail, Context, Result};
+use std::collections::HashSet;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+
+use crate::fits::{ChannelView, ColorOptions, FitsImage, LoadOptions, Star, Stretch};
+
+/// Contact-sheet layout for [`write_montage`].
+#[derive(Debug, Clone, Copy)]
+pub struct MontageOptions {
+    /// Thumbnails per row
+    pub columns: usize,
+    /// Thumbnail size in pixels (longest side)
+    pub cell: usize,
+}
+
+/// Write an RGBA8 buffer as a PNG file.
+pub fn write_png(path: &Path, width: usize, height: usize, rgba: &[u8]) -> Result<()> {
+    let file = std::fs::File::create(path)
+        .with_context(|| format!("creating {}", path.display()))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().context("writing PNG header")?;
+    writer.write_image_data(rgba).context("writing PNG data")?;
+    writer.finish().context("finishing PNG")?;
+    Ok(())
+}
+
+/// Burn `lines` of text into the bottom-left corner of an RGBA8 image, white on a
+/// translucent dark box. The text size scales with the image height.
+pub fn draw_caption(rgba: &mut [u8], width: usize, height: usize, lines: &[String]) {
+    if lines.is_empty() || width == 0 || height == 0 {
+        return;
+    }
+    let fonts = egui::FontDefinitions::default();
+    let Some(font) = caption_font(&fonts) else { return };
+
+    let px = (height as f32 / 40.0).clamp(12.0, 64.0);
+    let scaled = font.as_scaled(PxScale::from(px));
+    let line_height = scaled.height() + scaled.line_gap();
+    let margin = px * 0.5;
+
+    let box_w = lines.iter().map(|l| text_width(&font, px, l)).fold(0.0, f32::max) + 2.0 * margin;
+    let box_h = line_height * lines.len() as f32 + 2.0 * margin;
+    let x0 = margin;
+    let y0 = height as f32 - margin - box_h;
+
+    // Background box
+    let (bx0, by0) = (x0.max(0.0) as usize, y0.max(0.0) as usize);
+    let (bx1, by1) = (((x0 + box_w) as usize).min(width), ((y0 + box_h) as usize).min(height));
+    for y in by0..by1 {
+        for px in rgba[(y * width + bx0) * 4..(y * width + bx1) * 4].chunks_exact_mut(4) {
+            for c in &mut px[..3] {
+                *c = (*c as f32 * 0.4) as u8;
+            }
+        }
+    }
+
+    for (i, line) in lines.iter().enumerate() {
+        let top = y0 + margin + i as f32 * line_height;
+        draw_text(rgba, width, height, &font, px, (x0 + margin, top), line);
+    }
+}
+
+/// Load every file, autostretch it and lay the thumbnails out in a grid with their
+/// file names underneath, then write the sheet to `out` as a PNG. Files that fail to
+/// load get an empty cell labelled with the error.
+pub fn write_montage(files: &[PathBuf], opts: MontageOptions, out: &Path) -> Result<()> {
+    if files.is_empty() {
+        bail!("no FITS files to put in the montage");
+    }
+    let fonts = egui::FontDefinitions::default();
+    let font = caption_font(&fonts).context("loading caption font")?;
+    let (columns, cell) = (opts.columns.max(1), opts.cell.max(16));
+    let label_px = 14.0;
+    let pad = 8;
+    let label_h = (label_px * 1.5) as usize;
+    let (cell_w, cell_h) = (cell + pad, cell + label_h + pad);
+    let rows = files.len().div_ceil(columns);
+    let (width, height) = (columns * cell_w + pad, rows * cell_h + pad);
+    let mut sheet = vec![0u8; width * height * 4];
+    for px in sheet.chunks_exact_mut(4) {
+        px.copy_from_slice(&[24, 24, 24, 255]);
+    }
+
+    for (i, path) in files.iter().enumerate() {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        eprintln!("[{}/{}] {name}", i + 1, files.len());
+        let (x0, y0) = (pad + (i % columns) * cell_w, pad + (i / columns) * cell_h);
+        let label = match thumbnail(path, cell) {
+            Ok((thumb, tw, th)) => {
+                // Centre the thumbnail in its cell
+                let (ox, oy) = (x0 + (cell - tw) / 2, y0 + (cell - th) / 2);
+                for (row, src) in thumb.chunks_exact(tw * 4).enumerate() {
+                    let start = ((oy + row) * width + ox) * 4;
+                    sheet[start..start + tw * 4].copy_from_slice(src);
+                }
+                name.into_owned()
+            }
+            Err(e) => format!("{name}: {e:#}"),
+        };
+        let max_chars = (cell as f32 / (label_px * 0.6)) as usize;
+        let label: String = if label.chars().count() > max_chars {
+            label.chars().take(max_chars.saturating_sub(1)).chain(['…']).collect()
+        } else {
+            label
+        };
+        let top = (y0 + cell) as f32 + label_px * 0.25;
+        draw_text(&mut sheet, width, height, &font, label_px, (x0 as f32, top), &label);
+    }
+
+    write_png(out, width, height, &sheet)
+}
+
+/// Render `path` autostretched (composite view for colour images), downsampled to
+/// fit in `size`×`size`. Only a decimated copy of the image is read, like the
+/// quick preview. Returns the RGBA8 pixels, width and height.
+pub fn thumbnail(path: &Path, size: usize) -> Result<(Vec<u8>, usize, usize)> {
+    let img = FitsImage::load_decimated(path, LoadOptions::default(), size)?;
+    let view = if img.channels >= 3 { ChannelView::Rgb } else { ChannelView::Single(0) };
+    let rgba = img.to_rgba(Stretch::AUTO, view, ColorOptions::default());
+    Ok(fit_rgba(&rgba, img.width, img.height, size))
+}
+
+/// Downsample an RGBA8 image to fit in `size`×`size` by averaging the source pixels
+/// covered by each output pixel. Images already small enough are returned unchanged.
+fn fit_rgba(rgba: &[u8], width: usize, height: usize, size: usize) -> (Vec<u8>, usize, usize) {
+    let scale = (size as f32 / width.max(height) as f32).min(1.0);
+    let (w, h) = (((width as f32 * scale) as usize).max(1), ((height as f32 * scale) as usize).max(1));
+    if (w, h) == (width, height) {
+        return (rgba.to_vec(), w, h);
+    }
+    let mut out = vec![0u8; w * h * 4];
+    for y in 0..h {
+        let (sy0, sy1) = (y * height / h, ((y + 1) * height / h).max(y * height / h + 1));
+        for x in 0..w {
+            let (sx0, sx1) = (x * width / w, ((x + 1) * width / w).max(x * width / w + 1));
+            let mut sum = [0u32; 4];
+            for sy in sy0..sy1 {
+                for px in rgba[(sy * width + sx0) * 4..(sy * width + sx1) * 4].chunks_exact(4) {
+                    for c in 0..4 {
+                        sum[c] += px[c] as u32;
+                    }
+                }
+            }
+            let n = ((sy1 - sy0) * (sx1 - sx0)) as u32;
+            for c in 0..4 {
+                out[(y * w + x) * 4 + c] = (sum[c] / n) as u8;
+            }
+        }
+    }
+    (out, w, h)
+}
+
+/// egui's bundled monospace font, reused rather than shipping another one.
+fn caption_font(fonts: &egui::FontDefinitions) -> Option<FontRef<'_>> {
+    FontRef::try_from_slice(&fonts.font_data.get("Hack")?.font).ok()
+}
+
+fn text_width(font: &FontRef, px: f32, text: &str) -> f32 {
+    let scaled = font.as_scaled(PxScale::from(px));
+    text.chars().map(|c| scaled.h_advance(font.glyph_id(c))).sum()
+}
+
+/// Blend white `text` of height `px` into an RGBA8 image with its top-left corner at `pos`.
+fn draw_text(
+    rgba: &mut [u8],
+    width: usize,
+    height: usize,
+    font: &FontRef,
+    px: f32,
+    pos: (f32, f32),
+    text: &str,
+) {
+    let scale = PxScale::from(px);
+    let scaled = font.as_scaled(scale);
+    let mut caret = point(pos.0, pos.1 + scaled.ascent());
+    for c in text.chars() {
+        let glyph = font.glyph_id(c).with_scale_and_position(scale, caret);
+        caret.x += scaled.h_advance(glyph.id);
+        let Some(outline) = font.outline_glyph(glyph) else { continue };
+        let bounds = outline.px_bounds();
+        outline.draw(|gx, gy, coverage| {
+            let x = bounds.min.x as i64 + gx as i64;
+            let y = bounds.min.y as i64 + gy as i64;
+            if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+                return;
+            }
+            let idx = (y as usize * width + x as usize) * 4;
+            for c in &mut rgba[idx..idx + 3] {
+                *c = (*c as f32 + (255.0 - *c as f32) * coverage) as u8;
+            }
+        });
+    }
+}
+
+/// Columns of the statistics CSV written by [`write_stats_csv`].
+const STATS_HEADER: &str = "file,stars,fwhm_mean,hfr_mean,snr_median,background,noise,error";
+
+/// Write one CSV row of image-quality statistics per file to `out`: star count,
+/// mean FWHM and HFR (pixels), median star SNR, and the sky background level and
+/// noise σ (averaged over the planes). Files are measured on a pool of worker
+/// threads and each row is written as soon as it is ready, so an interrupted run
+/// can be resumed: files already listed in an existing `out` are skipped. Files
+/// that fail to load get a row with only the error.
+pub fn write_stats_csv(files: &[PathBuf], out: &Path) -> Result<()> {
+    if files.is_empty() {
+        bail!("no FITS files to measure");
+    }
+    let done: HashSet<String> = match std::fs::read_to_string(out) {
+        Ok(text) => {
+            let Some((done, complete)) = resume_point(&text) else {
+                bail!("{} exists and is not a fastfits statistics file", out.display());
+            };
+            if complete < text.len() {
+                // Drop the row an interrupted run left half-written
+                let file = std::fs::OpenOptions::new().write(true).open(out);
+                file.and_then(|f| f.set_len(complete as u64))
+                    .with_context(|| format!("truncating {}", out.display()))?;
+            }
+            done
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashSet::new(),
+        Err(e) => return Err(e).with_context(|| format!("reading {}", out.display())),
+    };
+    let todo: Vec<&PathBuf> = files.iter().filter(|p| !done.contains(&p.display().to_string())).collect();
+    if todo.len() < files.len() {
+        eprintln!("resuming: {} of {} files already in {}", files.len() - todo.len(), files.len(), out.display());
+    }
+
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(out)
+        .with_context(|| format!("opening {}", out.display()))?;
+    let mut writer = BufWriter::new(file);
+    if done.is_empty() && writer.get_ref().metadata()?.len() == 0 {
+        writeln!(writer, "{STATS_HEADER}")?;
+    }
+
+    let jobs = std::thread::available_parallelism().map_or(1, |n| n.get()).min(todo.len()).max(1);
+    let next = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+    std::thread::scope(|scope| -> Result<()> {
+        for _ in 0..jobs {
+            let (tx, next, todo) = (tx.clone(), &next, &todo);
+            scope.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = todo.get(i) else { break };
+                if tx.send((path, stats_row(path))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+        for (n, (path, row)) in rx.into_iter().enumerate() {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            eprintln!("[{}/{}] {name}", n + 1, todo.len());
+            writeln!(writer, "{},{row}", csv_field(&path.display().to_string()))?;
+            writer.flush()?;
+        }
+        Ok(())
+    })
+}
+
+/// The statistics columns of `path` after the file name.
+fn stats_row(path: &Path) -> String {
+    let img = match FitsImage::load(path, LoadOptions::default()) {
+        Ok(img) => img,
+        Err(e) => return format!(",,,,,,{}", csv_field(&e.to_string())),
+    };
+    let stars = img.detect_stars();
+    let mean = |f: fn(&Star) -> f32| {
+        let values: Vec<f32> = stars.iter().map(f).filter(|v| v.is_finite()).collect();
+        if values.is_empty() { String::new() } else { format!("{:.3}", values.iter().sum::<f32>() / values.len() as f32) }
+    };
+    let mut snr: Vec<f64> = stars.iter().map(|s| s.snr).collect();
+    snr.sort_by(f64::total_cmp);
+    let snr = snr.get(snr.len() / 2).map_or(String::new(), |s| format!("{s:.1}"));
+    let background = img.background_noise();
+    let planes = background.len().max(1) as f32;
+    let level = background.iter().map(|b| b.level).sum::<f32>() / planes;
+    let noise = background.iter().map(|b| b.sigma).sum::<f32>() / planes;
+    format!("{},{},{},{snr},{level},{noise},", stars.len(), mean(|s| s.fwhm), mean(|s| s.hfr))
+}
+
+/// Where to resume an existing statistics CSV `text`: the files it lists and the
+/// length of its complete lines. A last line without its line break was cut off
+/// by an interrupted run, so that file is measured again. None if `text` is not
+/// a statistics CSV.
+fn resume_point(text: &str) -> Option<(HashSet<String>, usize)> {
+    let complete = text.rfind('\n').map_or(0, |i| i + 1);
+    let mut lines = text[..complete].lines();
+    match lines.next() {
+        Some(header) if header == STATS_HEADER => {}
+        Some(_) => return None,
+        None if text.is_empty() => {}
+        None => return None,
+    }
+    Some((lines.filter_map(csv_first_field).collect(), complete))
+}
+
+/// `text` as a CSV field, quoted when it holds a comma, quote or line break.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// The first field of a CSV line written by [`csv_field`], unquoted.
+fn csv_first_field(line: &str) -> Option<String> {
+    if let Some(rest) = line.strip_prefix('"') {
+        let mut field = String::new();
+        let mut chars = rest.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => return Some(field),
+                c => field.push(c),
+            }
+        }
+        None
+    } else {
+        line.split(',').next().filter(|f| !f.is_empty()).map(str::to_string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_fields_are_quoted() {
+        assert_eq!(csv_field("m31.fits"), "m31.fits");
+        assert_eq!(csv_field("M31, night 2.fits"), "\"M31, night 2.fits\"");
+        assert_eq!(csv_field("the \"best\" one.fits"), "\"the \"\"best\"\" one.fits\"");
+        for name in ["m31.fits", "M31, night 2.fits", "the \"best\", one.fits"] {
+            let line = format!("{},12,2.500,", csv_field(name));
+            assert_eq!(csv_first_field(&line).as_deref(), Some(name));
+        }
+    }
+
+    #[test]
+    fn resume_skips_complete_rows_only() {
+        let text = format!("{STATS_HEADER}\na.fits,10,2.0,2.1,30.0,100,5,\n\"b,c.fits\",,,,,,error\nd.fi");
+        let (done, complete) = resume_point(&text).unwrap();
+        assert_eq!(done, HashSet::from(["a.fits".to_string(), "b,c.fits".to_string()]));
+        assert_eq!(&text[complete..], "d.fi");
+        assert_eq!(resume_point(""), Some((HashSet::new(), 0)));
+        assert_eq!(resume_point("name,value\nx,1\n"), None);
+    }
+}
@@ -1,169 +1,2029 @@
-use anyhow::{bail, Context, Result};
 use fitsio::hdu::HduInfo;
 #[allow(unused_imports)]
 use fitsio::images::ReadImage; // trait needed for hdu.read_image()
 use fitsio::FitsFile;
+use std::borrow::Cow;
 use std::io::Cursor;
 use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Why a FITS file (or one of its HDUs) could not be loaded.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum FitsError {
+    /// Reading the file failed.
+    #[error("{context}: {source}")]
+    Io {
+        /// What was being done, e.g. `opening light.fits`
+        context: String,
+        /// The underlying error
+        #[source]
+        source: std::io::Error,
+    },
+    /// cfitsio reported an error (e.g. not a FITS file, corrupt data).
+    #[error("{context}: {source}")]
+    Fitsio {
+        /// What was being done, e.g. `reading pixels`
+        context: String,
+        /// The underlying error
+        #[source]
+        source: fitsio::errors::Error,
+    },
+    /// The file has no HDU with image data.
+    #[error("no image HDU found in file")]
+    NoImageHdu,
+    /// The image has a number of axes other than 2 or 3.
+    #[error("unsupported FITS image NAXIS={0}")]
+    UnsupportedNaxis(usize),
+    /// The selected HDU holds a table rather than an image.
+    #[error("HDU {0} is not an image")]
+    NotAnImage(usize),
+    /// The requested mask HDU does not exist.
+    #[error("no {0} HDU")]
+    NoMaskHdu(String),
+    /// The mask HDU is not a usable 2-D image.
+    #[error("bad mask HDU: {0}")]
+    BadMask(String),
+    /// Demosaicing a Bayer image failed.
+    #[error("debayer error: {0}")]
+    Debayer(String),
+    /// The file ends before its headers or data do, e.g. a partial download.
+    #[error("{path}: file appears truncated ({detail})")]
+    Truncated {
+        /// The file
+        path: String,
+        /// What is missing, e.g. `HDU 1 needs 2880 data bytes, 1480 present`
+        detail: String,
+    },
+    /// The pixel data read does not fill the dimensions the header gives, e.g. a
+    /// corrupt `NAXISn` card.
+    #[error("HDU {hdu}: header gives {expected} values, the data holds {actual}")]
+    DataSize {
+        /// Index of the HDU
+        hdu: usize,
+        /// `NAXIS1 × NAXIS2 × …`
+        expected: usize,
+        /// Number of values read
+        actual: usize,
+    },
+    /// Encoding a rendered image as PNG failed.
+    #[error("encoding PNG: {0}")]
+    Png(#[source] png::EncodingError),
+}
+
+impl FitsError {
+    /// True for files that are valid FITS but hold nothing this crate can display
+    /// (no image, unsupported dimensions), as opposed to unreadable or corrupt files.
+    pub fn is_unsupported(&self) -> bool {
+        matches!(self, FitsError::NoImageHdu | FitsError::UnsupportedNaxis(_) | FitsError::NotAnImage(_))
+    }
+}
+
+/// Result type of the loading functions.
+pub type Result<T, E = FitsError> = std::result::Result<T, E>;
+
+/// Fail with [`FitsError::DataSize`] unless `actual` values were read where the
+/// header announced `expected`, so a corrupt header can't lead to out-of-bounds
+/// slicing later.
+fn check_data_size(hdu: usize, actual: usize, expected: usize) -> Result<()> {
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(FitsError::DataSize { hdu, expected, actual })
+    }
+}
+
+/// Wrap a cfitsio error with what was being done.
+fn fitsio_err(context: impl Into<String>) -> impl FnOnce(fitsio::errors::Error) -> FitsError {
+    move |source| FitsError::Fitsio { context: context.into(), source }
+}
+
+/// Wrap an I/O error with what was being done.
+fn io_err(context: impl Into<String>) -> impl FnOnce(std::io::Error) -> FitsError {
+    move |source| FitsError::Io { context: context.into(), source }
+}
+
+/// Which channel to display.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChannelView {
+    /// Composite RGB (only meaningful when channels == 3)
+    Rgb,
+    /// Single channel index (0 = R or the only channel, 1 = G, 2 = B)
+    Single(usize),
+}
+
+/// Stretch algorithm applied before display.
+#[allow(clippy::enum_variant_names)] // `AutoStretch` is the user-facing name
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stretch {
+    /// True linear: full data min → black, max → white.
+    Linear,
+    /// No per-image normalisation: 0 → black, `bitdepth_max` (full scale) → white,
+    /// so brightness compares directly across frames. Uses the data range when
+    /// the full scale is unknown (float data).
+    RawLinear,
+    /// Linear between two percentiles (fractions in [0, 1], e.g. 0.005 / 0.995),
+    /// so a handful of hot pixels cannot wash out the display.
+    Percentile {
+        /// Percentile mapped to black
+        low: f32,
+        /// Percentile mapped to white
+        high: f32,
+    },
+    /// Histogram-based screen transfer function (see [`FitsImage::auto_stf`]).
+    AutoStretch {
+        /// Percentile (fraction) clipped to white, e.g. 0.9998; raise it towards 1
+        /// to keep bright cores (planetary nebulae, galaxy nuclei) from blowing out
+        white_clip: f32,
+    },
+    /// Logarithmic over the data range: `ln(1 + k·x) / ln(1 + k)` with `x` in [0, 1].
+    /// Larger `k` lifts faint detail more; suits bright planetary and lunar frames.
+    Log {
+        /// Knee of the curve; values near 0 approach a linear stretch
+        k: f32,
+    },
+    /// Histogram equalisation: each value maps to its cumulative share of pixels,
+    /// spreading contrast over the whole output range. Meant for inspecting faint
+    /// structure and defects, not for a natural-looking image.
+    HistEq {
+        /// For RGB, equalise all three planes with the CDF of their mean (luminance)
+        /// instead of each plane with its own, which keeps the colour balance
+        shared: bool,
+    },
+}
+
+impl Stretch {
+    /// AutoStretch with the default white clip (top 0.02 % of pixels).
+    pub const AUTO: Stretch = Stretch::AutoStretch { white_clip: DEFAULT_WHITE_CLIP };
+}
+
+/// Default share of pixels below the AutoStretch white point.
+pub const DEFAULT_WHITE_CLIP: f32 = 0.9998;
+
+/// Default output level AutoStretch maps the sky background to (keeping it
+/// slightly off-black so faint structure just above sky is visible).
+pub const DEFAULT_BACKGROUND: f32 = 0.20;
+
+/// Demosaic algorithm used when debayering a Bayer-pattern image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DemosaicMode {
+    /// Sharper, slower
+    Cubic,
+    /// Faster, softer
+    Bilinear,
+}
+
+/// Which axis of a 3-D image holds the colour channels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorAxis {
+    /// Decide from `CTYPE1` / `CTYPE3`, else treat a first axis of length 3
+    /// in front of a longer third one as the channels
+    Auto,
+    /// `NAXIS3`: one plane per channel (the usual layout)
+    Last,
+    /// `NAXIS1`: pixel-interleaved channels, width and height on `NAXIS2` / `NAXIS3`
+    First,
+}
+
+/// Settings that affect how pixel data is decoded; changing any of them requires a reload.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoadOptions {
+    /// Demosaic algorithm for Bayer images
+    pub demosaic: DemosaicMode,
+    /// Display-only hot-pixel correction: pixels more than this many noise sigmas
+    /// above the median of their neighbours are replaced by that median.  None = off.
+    pub hot_pixel_sigma: Option<f32>,
+    /// Colour axis of 3-D images
+    pub color_axis: ColorAxis,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions {
+            demosaic: DemosaicMode::Bilinear,
+            hot_pixel_sigma: None,
+            color_axis: ColorAxis::Auto,
+        }
+    }
+}
+
+/// File extensions of FITS files, lower-case and without the dot.
+pub const FITS_EXTENSIONS: [&str; 4] = ["fits", "fit", "fts", "fz"];
+
+/// True if `path` has one of the [`FITS_EXTENSIONS`] (case-insensitive).
+pub fn is_fits_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| FITS_EXTENSIONS.iter().any(|f| e.eq_ignore_ascii_case(f)))
+}
+
+/// Kind of exposure, from the `IMAGETYP` (or `FRAME`) keyword written by capture
+/// programs, for telling the frames of a session apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FrameType {
+    /// Science frame ("Light Frame", "LIGHT", "Object")
+    Light,
+    /// Dark frame, including dark flats
+    Dark,
+    /// Flat field
+    Flat,
+    /// Bias (zero, offset) frame
+    Bias,
+    /// No frame-type keyword, or one not recognised
+    Unknown,
+}
+
+impl FrameType {
+    /// Every frame type, in display order.
+    pub const ALL: [FrameType; 5] =
+        [FrameType::Light, FrameType::Dark, FrameType::Flat, FrameType::Bias, FrameType::Unknown];
+
+    /// Classify a file by its headers: `IMAGETYP`, else `FRAME`.
+    pub fn of(headers: &[(String, String)]) -> FrameType {
+        ["IMAGETYP", "FRAME"]
+            .iter()
+            .find_map(|key| header_value(headers, key))
+            .map_or(FrameType::Unknown, FrameType::classify)
+    }
+
+    /// Classify a frame-type keyword value. The conventions vary between capture
+    /// programs ("Light Frame", "LIGHT", "Dark Flat", "Bias Frame", "ZERO", …), so
+    /// the value is matched on the words it contains, case-insensitively.
+    pub fn classify(value: &str) -> FrameType {
+        let value = value.to_ascii_lowercase();
+        if ["bias", "zero", "offset"].iter().any(|w| value.contains(w)) {
+            FrameType::Bias
+        } else if value.contains("dark") {
+            FrameType::Dark
+        } else if value.contains("flat") {
+            FrameType::Flat
+        } else if ["light", "object", "science"].iter().any(|w| value.contains(w)) {
+            FrameType::Light
+        } else {
+            FrameType::Unknown
+        }
+    }
+
+    /// Name shown in the file browser.
+    pub fn label(self) -> &'static str {
+        match self {
+            FrameType::Light => "Light",
+            FrameType::Dark => "Dark",
+            FrameType::Flat => "Flat",
+            FrameType::Bias => "Bias",
+            FrameType::Unknown => "Unknown",
+        }
+    }
+}
+
+/// Read the header of the image in `path` without touching its pixel data: the
+/// primary HDU, or the first extension when the primary holds no image (as in
+/// tile-compressed `.fz` files), with the primary's keywords merged in.
+///
+/// This only reads header blocks, so it is cheap enough to run over every file
+/// of a directory.
+pub fn read_image_headers(path: &Path) -> Result<Vec<(String, String)>> {
+    let hdus = scan_hdus(path, Some(1))?;
+    let empty_primary = find_header_int(&hdus[0].header, "NAXIS").unwrap_or(0) == 0;
+    merged_headers(&hdus, if empty_primary && hdus.len() > 1 { 1 } else { 0 })
+}
+
+/// Mapping of the three source planes onto the displayed R, G, B.
+///
+/// For narrowband palettes the planes are taken to be (Hα, OIII, SII) in that order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Palette {
+    /// Planes shown as-is: 0 → R, 1 → G, 2 → B.
+    Rgb,
+    /// Hubble palette: SII → R, Hα → G, OIII → B.
+    Sho,
+    /// Bicolour: Hα → R, OIII → G and B.
+    Hoo,
+}
+
+impl Palette {
+    /// Row-major 3×3 mixing matrix: `out[row] = Σ m[row][col] · plane[col]`,
+    /// applied per pixel to the stretched planes.
+    pub fn matrix(self) -> [[f32; 3]; 3] {
+        match self {
+            Palette::Rgb => [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            Palette::Sho => [[0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+            Palette::Hoo => [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 1.0, 0.0]],
+        }
+    }
+}
+
+/// Colour adjustments applied to composite RGB output after stretching.
+/// Greyscale and single-channel views ignore them, except `night`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorOptions {
+    /// Saturation multiplier: 0 = greyscale, 1 = unchanged, 2 = double.
+    pub saturation: f32,
+    /// How the source planes map onto the displayed colours.
+    pub palette: Palette,
+    /// Night vision: output only the red channel, carrying the luminance, so the
+    /// screen does not spoil dark adaptation. Applies to every view.
+    pub night: bool,
+    /// Linked RGB stretch: one curve, derived from the mean of the three planes over
+    /// their combined range, for all of them. Keeps colour ratios (and casts) where
+    /// the default independent stretch neutralises each channel's background.
+    pub linked: bool,
+    /// Treat the stretched values as linear light and encode them with the sRGB
+    /// transfer function, as Siril and PixInsight do for display. Off sends the
+    /// stretched values to the screen as they are.
+    pub srgb: bool,
+    /// Output level AutoStretch maps the sky background of R, G and B to. Equal
+    /// targets neutralise the sky; a higher red than blue one warms it up.
+    pub background: [f32; 3],
+}
+
+impl Default for ColorOptions {
+    fn default() -> Self {
+        ColorOptions {
+            saturation: 1.0,
+            palette: Palette::Rgb,
+            night: false,
+            linked: false,
+            srgb: false,
+            background: [DEFAULT_BACKGROUND; 3],
+        }
+    }
+}
+
+impl ColorOptions {
+    /// Background target of greyscale views and the linked stretch: the mean of
+    /// the per-channel ones.
+    fn grey_background(&self) -> f32 {
+        self.background.iter().sum::<f32>() / 3.0
+    }
+}
+
+/// How an image's pixels are stored in the file, from its BITPIX, BSCALE, BZERO and
+/// BLANK keywords (see [`FitsImage::data_format`]). FITS data is always big-endian;
+/// cfitsio converts it to host order and applies the scaling while reading.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DataFormat {
+    /// Bits per stored value, negative for IEEE floats; None when BITPIX is missing
+    pub bitpix: Option<i64>,
+    /// BSCALE as written; absent means 1
+    pub bscale: Option<f64>,
+    /// BZERO as written; absent means 0
+    pub bzero: Option<f64>,
+    /// Stored value marking undefined integer pixels
+    pub blank: Option<i64>,
+}
+
+impl DataFormat {
+    /// The stored type, e.g. `16-bit signed integer` or `32-bit IEEE float`.
+    pub fn storage(&self) -> String {
+        match self.bitpix {
+            Some(8) => "8-bit unsigned integer".to_string(),
+            Some(b) if b > 0 => format!("{b}-bit signed integer"),
+            Some(b) if b < 0 => format!("{}-bit IEEE float", -b),
+            _ => "unknown (no valid BITPIX)".to_string(),
+        }
+    }
+
+    /// Whether physical values differ from the stored ones: BSCALE ≠ 1 or BZERO ≠ 0.
+    pub fn scaled(&self) -> bool {
+        self.bscale.is_some_and(|s| s != 1.0) || self.bzero.is_some_and(|z| z != 0.0)
+    }
+
+    /// The physical values the scaling produces, e.g. `unsigned 16-bit (BZERO offset)`.
+    pub fn physical(&self) -> String {
+        let (bscale, bzero) = (self.bscale.unwrap_or(1.0), self.bzero.unwrap_or(0.0));
+        match self.bitpix {
+            Some(b) if b > 0 && bscale == 1.0 => match (b, bzero) {
+                (8, -128.0) => "signed 8-bit (BZERO offset)".to_string(),
+                (16, 32768.0) => "unsigned 16-bit (BZERO offset)".to_string(),
+                (32, 2147483648.0) => "unsigned 32-bit (BZERO offset)".to_string(),
+                (64, 9223372036854775808.0) => "unsigned 64-bit (BZERO offset)".to_string(),
+                (_, 0.0) => "as stored".to_string(),
+                (_, z) => format!("stored + {z}"),
+            },
+            Some(_) if self.scaled() => format!("stored × {bscale} + {bzero}"),
+            Some(_) => "as stored".to_string(),
+            None => "unknown".to_string(),
+        }
+    }
+}
+
+/// Wall-clock time spent in each stage of [`FitsImage::load_progressive`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoadTimings {
+    /// Opening the file, locating the image HDU and parsing headers
+    pub header: Duration,
+    /// Reading (and decompressing) the pixel data
+    pub read: Duration,
+    /// Preview, hot-pixel correction, debayering and ceiling detection
+    pub process: Duration,
+}
+
+impl LoadTimings {
+    /// Sum of all stages.
+    pub fn total(&self) -> Duration {
+        self.header + self.read + self.process
+    }
+
+    /// Status-bar text such as `loaded in 420 ms (headers 4 ms, read 310 ms, debayer 106 ms)`.
+    pub fn summary(&self, debayered: bool) -> String {
+        let ms = |d: Duration| d.as_millis();
+        let process = if debayered { "debayer" } else { "process" };
+        format!(
+            "loaded in {} ms (headers {} ms, read {} ms, {process} {} ms)",
+            ms(self.total()),
+            ms(self.header),
+            ms(self.read),
+            ms(self.process),
+        )
+    }
+}
+
+/// Screen transfer function for one displayed plane (PixInsight-style STF).
+///
+/// `shadows` and `highlights` are fractions of the stretch domain
+/// `[data min, bit-depth ceiling]`; `midtones` is the MTF balance in (0, 1)
+/// (0.5 = linear between the black and white points).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stf {
+    /// Black point: values at or below map to black.
+    pub shadows: f32,
+    /// MTF midtone balance.
+    pub midtones: f32,
+    /// White point: values at or above clip to white.
+    pub highlights: f32,
+}
+
+impl Stf {
+    /// No stretch: black at the domain start, linear midtones, white at full scale.
+    pub const IDENTITY: Stf = Stf { shadows: 0.0, midtones: 0.5, highlights: 1.0 };
+
+    /// Resolve against a plane's `[data_min, data_max]` range and bit-depth ceiling.
+    /// None for a flat or degenerate range, which is shown mid-grey.
+    pub fn curve(self, data_min: f32, data_max: f32, bitdepth_max: f32) -> Option<StfCurve> {
+        let range = data_max - data_min;
+        let bd = if bitdepth_max > 0.0 { bitdepth_max } else { data_max };
+        if range == 0.0 || bd == 0.0 || bd <= data_min {
+            return None;
+        }
+        let full = bd - data_min;
+        let black = data_min + self.shadows * full;
+        Some(StfCurve {
+            black,
+            white: data_min + self.highlights * full,
+            scale: (bd - black).max(1.0),
+            midtones: self.midtones,
+        })
+    }
+}
+
+/// An [`Stf`] in absolute pixel values, as applied by the CPU LUT and the GPU shader.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StfCurve {
+    /// Values at or below map to black.
+    pub black: f32,
+    /// Values at or above map to white.
+    pub white: f32,
+    /// Width of the MTF input domain above `black` (up to the bit-depth ceiling).
+    pub scale: f32,
+    /// MTF midtone balance.
+    pub midtones: f32,
+}
+
+impl StfCurve {
+    /// Display value in [0, 1] of pixel value `v`.
+    pub fn apply(&self, v: f32) -> f32 {
+        if v <= self.black {
+            return 0.0;
+        }
+        if v >= self.white {
+            return 1.0;
+        }
+        mtf(((v - self.black) / self.scale).clamp(0.0, 1.0), self.midtones)
+    }
+
+    /// Share of `plane` at or below the black point / at or above the white point.
+    pub fn clipping(&self, plane: &[f32]) -> Clipping {
+        let (mut black, mut white) = (0usize, 0usize);
+        for &v in plane {
+            if v <= self.black {
+                black += 1;
+            } else if v >= self.white {
+                white += 1;
+            }
+        }
+        let npix = plane.len().max(1) as f32;
+        Clipping { black: black as f32 / npix, white: white as f32 / npix }
+    }
+}
+
+/// Raw float pixel data loaded from one FITS image HDU.
+///
+/// Data layout: planar, `channels` planes each of `width * height` f32 values.
+/// Index: `data[channel * width * height + row * width + col]`
+#[derive(Clone)]
+pub struct FitsImage {
+    /// Pixels per row
+    pub width: usize,
+    /// Number of rows
+    pub height: usize,
+    /// 1 = grayscale, 3 = RGB (either debayered or pre-separated)
+    pub channels: usize,
+    /// Raw float pixels in planar order.
+    pub data: Vec<f32>,
+    /// FITS header key/value pairs from the image HDU.
+    pub headers: Vec<(String, String)>,
+    /// Full-scale maximum for the image's bit depth (e.g. 65535 for 16-bit, 255 for 8-bit).
+    /// Used to anchor statistics in autostretch so sky normalisation matches Siril.
+    /// 0.0 means unknown / float data: autostretch falls back to data range.
+    pub bitdepth_max: f32,
+    /// True when the image was loaded via Bayer debayering.
+    /// Used to conditionally show demosaic options in the Preferences dialog.
+    pub is_bayer: bool,
+    /// DATAMIN header keyword, if present: lower end of the meaningful value range.
+    pub datamin: Option<f32>,
+    /// DATAMAX header keyword, if present: upper end of the meaningful value range.
+    pub datamax: Option<f32>,
+    /// Number of pixels replaced by hot-pixel correction (None when correction is off).
+    pub hot_pixels_fixed: Option<usize>,
+    /// 1 for full resolution; N for a quick preview that sampled every Nth pixel.
+    /// Multiply `width`/`height` by this to get the full-resolution size.
+    pub decimation: usize,
+    /// Binning applied by [`FitsImage::bin`] (1 = unbinned); like `decimation`, it
+    /// divides the full-resolution size.
+    pub binning: usize,
+    /// Undebayered sensor values of a Bayer image (None for other images and previews)
+    pub raw_mosaic: Option<RawMosaic>,
+    /// Index of the HDU the image was read from (0 = primary)
+    pub hdu: usize,
+    /// Number of HDUs in the file
+    pub hdu_count: usize,
+    /// The R, G and B HDUs when the colour planes were stored as separate
+    /// single-plane images and stacked on load (`hdu` is the R one)
+    pub channel_hdus: Option<[usize; 3]>,
+    /// Offset subtracted from `data` (and DATAMIN / DATAMAX) on load, from the
+    /// PEDESTAL keyword (see [`FitsImage::load`])
+    pub pedestal: Option<f32>,
+}
+
+/// The original CFA mosaic of a debayered image, before any cosmetic correction.
+#[derive(Clone)]
+pub struct RawMosaic {
+    /// Sensor values (ADU), `width * height`, row-major
+    pub data: Vec<u16>,
+    /// Colours of the 2×2 cell in reading order, e.g. `*b"RGGB"`
+    pub pattern: [u8; 4],
+}
+
+impl RawMosaic {
+    /// Label of Bayer site `site` (0..4, reading order in the 2×2 cell): R, B, G1 or G2.
+    pub fn site_label(&self, site: usize) -> String {
+        let colour = self.pattern[site];
+        if colour != b'G' {
+            return (colour as char).to_string();
+        }
+        let n = self.pattern[..=site].iter().filter(|&&c| c == b'G').count();
+        format!("G{n}")
+    }
+}
+
+/// Mean / spread of a set of pixel values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlaneStats {
+    /// Mean value
+    pub mean: f64,
+    /// Population standard deviation
+    pub stddev: f64,
+    /// Smallest value
+    pub min: f32,
+    /// Largest value
+    pub max: f32,
+    /// Number of finite values
+    pub count: usize,
+}
+
+impl PlaneStats {
+    /// Statistics of the finite values in `values` (all zero when there are none).
+    pub fn of(values: impl Iterator<Item = f32>) -> Self {
+        let (mut sum, mut sum_sq, mut count) = (0f64, 0f64, 0usize);
+        let (mut min, mut max) = (f32::INFINITY, f32::NEG_INFINITY);
+        for v in values.filter(|v| v.is_finite()) {
+            sum += v as f64;
+            sum_sq += v as f64 * v as f64;
+            min = min.min(v);
+            max = max.max(v);
+            count += 1;
+        }
+        if count == 0 {
+            return PlaneStats { mean: 0.0, stddev: 0.0, min: 0.0, max: 0.0, count };
+        }
+        let mean = sum / count as f64;
+        let stddev = (sum_sq / count as f64 - mean * mean).max(0.0).sqrt();
+        PlaneStats { mean, stddev, min, max, count }
+    }
+}
+
+/// How [`FitsImage::combine`] merges two frames pixel by pixel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Combine {
+    /// `a − b`, e.g. a light minus its dark
+    Difference,
+    /// `a / b`, e.g. a flat over another flat (NaN where `b` is 0)
+    Ratio,
+}
+
+/// A one-dimensional spectrum (see [`FitsImage::spectrum`]): flux against the
+/// world coordinate of its axis.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spectrum {
+    /// Coordinate of each sample, from `CRVALn` / `CRPIXn` / `CDELTn`
+    /// (1-based pixel numbers when the axis has no WCS)
+    pub wavelength: Vec<f64>,
+    /// Value of each sample
+    pub flux: Vec<f32>,
+    /// Axis title from `CTYPEn` and `CUNITn`, e.g. `WAVE (Angstrom)`
+    pub axis: String,
+}
+
+/// Sky background level and noise of one plane, from robust statistics so stars
+/// and hot pixels barely affect them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BackgroundNoise {
+    /// Median value
+    pub level: f32,
+    /// Gaussian-equivalent noise: 1.4826 × the median absolute deviation
+    pub sigma: f32,
+}
+
+impl BackgroundNoise {
+    /// Signal-to-noise ratio of a pixel value above the background.
+    pub fn snr(&self, value: f32) -> f32 {
+        if self.sigma > 0.0 {
+            (value - self.level) / self.sigma
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Circular aperture photometry of one star (see [`FitsImage::aperture_photometry`]).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Photometry {
+    /// Flux-weighted centroid in pixels, (0, 0) the corner of the first pixel
+    pub x: f32,
+    /// Centroid row coordinate, as `x`
+    pub y: f32,
+    /// Sum of the sky-subtracted values inside the aperture
+    pub flux: f64,
+    /// Sky level per pixel and its noise, from the annulus
+    pub sky: BackgroundNoise,
+    /// Pixels inside the aperture
+    pub pixels: usize,
+}
+
+impl Photometry {
+    /// Instrumental magnitude, −2.5 log₁₀(flux); None without positive flux.
+    pub fn magnitude(&self) -> Option<f64> {
+        (self.flux > 0.0).then(|| -2.5 * self.flux.log10())
+    }
+
+    /// Background-limited signal-to-noise ratio: the flux over the sky noise summed
+    /// over the aperture. The star's own shot noise is left out, as the gain is unknown.
+    pub fn snr(&self) -> f64 {
+        let noise = self.sky.sigma as f64 * (self.pixels as f64).sqrt();
+        if noise > 0.0 { self.flux / noise } else { 0.0 }
+    }
+}
+
+/// Centroiding passes of [`FitsImage::aperture_photometry`].
+const CENTROID_PASSES: usize = 3;
+
+/// Ring width of [`RadialProfile::bins`], in pixels.
+pub const PROFILE_BIN: f32 = 0.5;
+
+/// FWHM of a Gaussian in units of its σ, 2√(2 ln 2).
+const FWHM_PER_SIGMA: f32 = 2.354_82;
+
+/// Detection threshold of [`FitsImage::detect_stars`], in noise σ above the sky.
+pub const DETECT_SIGMA: f32 = 5.0;
+
+/// Most peaks [`FitsImage::detect_stars`] measures, the brightest first.
+pub const MAX_STARS: usize = 2000;
+
+/// Aperture and annulus radii (pixels) used to measure detected stars.
+const STAR_RADIUS: f32 = 6.0;
+const STAR_ANNULUS: (f32, f32) = (10.0, 15.0);
+
+/// FWHM range (pixels) accepted as a star rather than a hot pixel or a blob.
+const STAR_FWHM: std::ops::RangeInclusive<f32> = 0.8..=20.0;
+
+/// A star found by [`FitsImage::detect_stars`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Star {
+    /// Centroid in pixels, (0, 0) the corner of the first pixel
+    pub x: f32,
+    /// Centroid row coordinate, as `x`
+    pub y: f32,
+    /// Sky-subtracted flux within the aperture
+    pub flux: f64,
+    /// Full width at half maximum, in pixels
+    pub fwhm: f32,
+    /// Half-flux radius in pixels; NaN when it could not be found
+    pub hfr: f32,
+    /// Background-limited signal-to-noise ratio (see [`Photometry::snr`])
+    pub snr: f64,
+}
+
+/// Median FWHM of the `stars` in each cell of a 3 × 3 grid over a `width` ×
+/// `height` image, rows top to bottom; None for cells without stars.
+pub fn fwhm_grid(stars: &[Star], width: usize, height: usize) -> [[Option<f32>; 3]; 3] {
+    let mut cells: [[Vec<f32>; 3]; 3] = Default::default();
+    for s in stars {
+        let col = ((s.x / width as f32 * 3.0) as usize).min(2);
+        let row = ((s.y / height as f32 * 3.0) as usize).min(2);
+        cells[row][col].push(s.fwhm);
+    }
+    cells.map(|row| {
+        row.map(|mut fwhm| {
+            fwhm.sort_by(f32::total_cmp);
+            (!fwhm.is_empty()).then(|| fwhm[fwhm.len() / 2])
+        })
+    })
+}
+
+/// Radial intensity profile of one star (see [`FitsImage::radial_profile`]).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RadialProfile {
+    /// Centroid, sky and flux within the aperture
+    pub photometry: Photometry,
+    /// (ring centre radius, mean sky-subtracted value) for each ring holding a pixel
+    pub bins: Vec<(f32, f32)>,
+    /// Fitted Gaussian (peak above the sky, σ in pixels); None when the fit fails
+    pub gaussian: Option<(f32, f32)>,
+    /// Full width at half maximum of the fitted Gaussian, in pixels
+    pub fwhm: Option<f32>,
+    /// Half-flux radius: the radius enclosing half the aperture's flux, in pixels
+    pub hfr: Option<f32>,
+}
+
+/// Celestial coordinates of an image: the FITS WCS gnomonic (`TAN`) projection
+/// from `CRPIX`/`CRVAL` and a `CD` matrix, or `CDELT` with a `PC` matrix or
+/// `CROTA2`. SIP distortion terms are ignored, which is fine for measuring
+/// separations away from the corners of wide fields.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wcs {
+    crpix: [f64; 2],
+    /// RA, Dec of the reference pixel in degrees
+    crval: [f64; 2],
+    /// Pixel offset → projection-plane degrees
+    cd: [[f64; 2]; 2],
+}
+
+impl Wcs {
+    fn from_headers(headers: &[(String, String)]) -> Option<Wcs> {
+        let ctype = header_value(headers, "CTYPE1")?;
+        if !(ctype.starts_with("RA--") && ctype.contains("-TAN")) {
+            return None;
+        }
+        let num = |key: &str| header_f64(headers, key);
+        let crpix = [num("CRPIX1")?, num("CRPIX2")?];
+        let crval = [num("CRVAL1")?, num("CRVAL2")?];
+        let cd = if let Some(cd11) = num("CD1_1") {
+            [[cd11, num("CD1_2").unwrap_or(0.0)], [num("CD2_1").unwrap_or(0.0), num("CD2_2").unwrap_or(0.0)]]
+        } else {
+            let (d1, d2) = (num("CDELT1")?, num("CDELT2")?);
+            if let Some(pc11) = num("PC1_1") {
+                let pc = |key| num(key).unwrap_or(0.0);
+                [[d1 * pc11, d1 * pc("PC1_2")], [d2 * pc("PC2_1"), d2 * num("PC2_2").unwrap_or(1.0)]]
+            } else {
+                let (sin, cos) = num("CROTA2").unwrap_or(0.0).to_radians().sin_cos();
+                [[d1 * cos, -d2 * sin], [d1 * sin, d2 * cos]]
+            }
+        };
+        let det = cd[0][0] * cd[1][1] - cd[0][1] * cd[1][0];
+        (det != 0.0 && det.is_finite()).then_some(Wcs { crpix, crval, cd })
+    }
+
+    /// RA and Dec in degrees of the position (`x`, `y`) in `data` pixel
+    /// coordinates: 0-based, (0, 0) the centre of the first pixel.
+    pub fn pixel_to_world(&self, x: f64, y: f64) -> (f64, f64) {
+        // FITS pixel coordinates are 1-based
+        let (u, v) = (x + 1.0 - self.crpix[0], y + 1.0 - self.crpix[1]);
+        let xi = (self.cd[0][0] * u + self.cd[0][1] * v).to_radians();
+        let eta = (self.cd[1][0] * u + self.cd[1][1] * v).to_radians();
+        let (ra0, dec0) = (self.crval[0].to_radians(), self.crval[1].to_radians());
+        let rho = xi.hypot(eta);
+        if rho == 0.0 {
+            return (self.crval[0], self.crval[1]);
+        }
+        let c = rho.atan();
+        let (sin_c, cos_c) = c.sin_cos();
+        let dec = (cos_c * dec0.sin() + eta * sin_c * dec0.cos() / rho).asin();
+        let ra = ra0 + (xi * sin_c).atan2(rho * dec0.cos() * cos_c - eta * dec0.sin() * sin_c);
+        (ra.to_degrees().rem_euclid(360.0), dec.to_degrees())
+    }
+
+    /// Pixel position (as for [`Wcs::pixel_to_world`]) of RA, Dec in degrees; None
+    /// for points 90° or more from the reference point, which do not project.
+    pub fn world_to_pixel(&self, ra: f64, dec: f64) -> Option<(f64, f64)> {
+        let dec0 = self.crval[1].to_radians();
+        let (dra, dec) = ((ra - self.crval[0]).to_radians(), dec.to_radians());
+        let cos_c = dec0.sin() * dec.sin() + dec0.cos() * dec.cos() * dra.cos();
+        if cos_c <= 1e-6 {
+            return None;
+        }
+        let xi = (dec.cos() * dra.sin() / cos_c).to_degrees();
+        let eta = ((dec0.cos() * dec.sin() - dec0.sin() * dec.cos() * dra.cos()) / cos_c).to_degrees();
+        let [[a, b], [c, d]] = self.cd;
+        let det = a * d - b * c;
+        let (u, v) = ((d * xi - b * eta) / det, (a * eta - c * xi) / det);
+        Some((u + self.crpix[0] - 1.0, v + self.crpix[1] - 1.0))
+    }
+}
+
+/// Angular separation of two (RA, Dec) positions and the position angle of `b`
+/// seen from `a` (east of north), all in degrees.
+pub fn separation(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    let (ra1, dec1) = (a.0.to_radians(), a.1.to_radians());
+    let (ra2, dec2) = (b.0.to_radians(), b.1.to_radians());
+    let dra = ra2 - ra1;
+    // Haversine: accurate for small separations
+    let h = ((dec2 - dec1) / 2.0).sin().powi(2) + dec1.cos() * dec2.cos() * (dra / 2.0).sin().powi(2);
+    let sep = 2.0 * h.sqrt().min(1.0).asin();
+    let pa = (dra.sin() * dec2.cos()).atan2(dec1.cos() * dec2.sin() - dec1.sin() * dec2.cos() * dra.cos());
+    (sep.to_degrees(), pa.to_degrees().rem_euclid(360.0))
+}
+
+/// Extension names tried, in order, when no mask HDU is specified.
+const MASK_EXTNAMES: [&str; 2] = ["DQ", "MASK"];
+
+/// Pixels flagged by a data-quality / mask HDU (any nonzero value).
+#[derive(Clone)]
+pub struct Mask {
+    /// Pixels per row
+    pub width: usize,
+    /// Number of rows
+    pub height: usize,
+    /// `width * height`, row-major
+    pub flagged: Vec<bool>,
+}
+
+impl Mask {
+    /// Load the mask HDU `hdu` of `path`: a 0-based HDU index or an EXTNAME
+    /// (case-insensitive). Empty tries the `DQ` then `MASK` extensions.
+    /// Only the first plane of a 3-D mask is used.
+    pub fn load(path: &Path, hdu: &str) -> Result<Self> {
+        let mut fits =
+            FitsFile::open(path).map_err(fitsio_err(format!("opening {}", path.display())))?;
+        let hdu = hdu.trim();
+        let found = if let Ok(idx) = hdu.parse::<usize>() {
+            fits.hdu(idx).map_err(|_| FitsError::NoMaskHdu(idx.to_string()))?
+        } else if !hdu.is_empty() {
+            let name = hdu.to_uppercase();
+            fits.hdu(name.as_str()).map_err(|_| FitsError::NoMaskHdu(name))?
+        } else {
+            MASK_EXTNAMES
+                .iter()
+                .find_map(|&name| fits.hdu(name).ok())
+                .ok_or_else(|| FitsError::NoMaskHdu("DQ or MASK".to_string()))?
+        };
+        // Same axis order as the science image: [NAXIS1, NAXIS2, ...]
+        let (width, height) = match &found.info {
+            HduInfo::ImageInfo { shape, .. } if shape.len() >= 2 => (shape[0], shape[1]),
+            _ => return Err(FitsError::BadMask("not a 2-D image".to_string())),
+        };
+        let values: Vec<f32> = found.read_image(&mut fits).map_err(fitsio_err("reading mask pixels"))?;
+        if values.len() < width * height {
+            let msg = format!("holds {} pixels, expected {}", values.len(), width * height);
+            return Err(FitsError::BadMask(msg));
+        }
+        let flagged = values[..width * height].iter().map(|&v| v != 0.0).collect();
+        Ok(Mask { width, height, flagged })
+    }
+
+    /// Number of flagged pixels.
+    pub fn count(&self) -> usize {
+        self.flagged.iter().filter(|&&f| f).count()
+    }
+}
+
+/// Frames smaller than this load directly without a preview stage.
+const PREVIEW_MIN_PIXELS: usize = 4_000_000;
+/// Preview decimation aims for roughly this many pixels along the longest side.
+const PREVIEW_TARGET_SIZE: usize = 1024;
+
+/// Decimation factor for a quick preview of a `width`×`height` frame, or None if the
+/// frame is small enough to show directly.  Always even, so a Bayer mosaic decimates
+/// into whole 2×2 CFA cells.
+fn preview_factor(width: usize, height: usize) -> Option<usize> {
+    if width * height < PREVIEW_MIN_PIXELS {
+        return None;
+    }
+    let f = (width.max(height) / PREVIEW_TARGET_SIZE).max(2);
+    Some(f + f % 2)
+}
+
+/// Where and how the image of a file is stored, from its headers, before any
+/// pixels are read.
+struct Layout {
+    fits: FitsFile,
+    /// Image HDU (the red plane's, for colour planes stored as separate HDUs)
+    idx: usize,
+    hdu_count: usize,
+    channel_hdus: Option<[usize; 3]>,
+    headers: Vec<(String, String)>,
+    width: usize,
+    height: usize,
+    /// Number of planes
+    naxis3: usize,
+    /// Pixel-interleaved planes (the channels are the first axis)
+    interleaved: bool,
+    bayer_cfa: Option<bayer::CFA>,
+    pedestal: Option<f32>,
+}
+
+impl Layout {
+    /// Open `path` and locate the first image HDU that contains data.
+    fn open(path: &Path, opts: LoadOptions) -> Result<Self> {
+        // cfitsio's errors for a partial file are cryptic: check the layout first
+        let raw_hdus = scan_hdus(path, None)?;
+        let channel_hdus = channel_hdus(&raw_hdus.iter().map(own_headers).collect::<Vec<_>>());
+        let mut fits =
+            FitsFile::open(path).map_err(fitsio_err(format!("opening {}", path.display())))?;
+
+        // Find first HDU with non-empty image data. cfitsio presents tile-compressed
+        // images (ZIMAGE = T binary tables, e.g. from fpack) through its image
+        // interface, so they appear as ImageInfo and are decompressed by read_image.
+        let hdu_count = fits.iter().count();
+        let mut image_hdu_idx = None;
+        for i in 0..hdu_count {
+            let hdu = fits.hdu(i).map_err(fitsio_err(format!("reading HDU {i}")))?;
+            if let HduInfo::ImageInfo { ref shape, .. } = hdu.info {
+                if !shape.is_empty() && shape.iter().product::<usize>() > 0 {
+                    image_hdu_idx = Some(i);
+                    break;
+                }
+            }
+        }
+        let idx = match channel_hdus {
+            Some([red, _, _]) => red,
+            None => image_hdu_idx.ok_or(FitsError::NoImageHdu)?,
+        };
+        let hdu = fits.hdu(idx).map_err(fitsio_err(format!("reading HDU {idx}")))?;
+
+        // Collect headers first (needed for the colour axis and Bayer detection)
+        let headers = read_headers(path, idx)?;
+
+        // cfitsio reports shape in FITS axis order: [NAXIS1, NAXIS2, NAXIS3, ...]
+        // NAXIS1 = fastest-varying (columns = width)
+        // NAXIS2 = rows = height
+        // NAXIS3 = planes / channels (if present)
+        // unless the channels are the first axis: pixel-interleaved RGB triplets.
+        // 1-D data (a spectrum) becomes a single row.
+        let (width, height, mut naxis3, interleaved) = match &hdu.info {
+            HduInfo::ImageInfo { shape, .. } => match shape.len() {
+                1 => (shape[0], 1, 1usize, false),
+                2 => (shape[0], shape[1], 1usize, false),
+                3 if channels_first(shape, &headers, opts.color_axis) => (shape[1], shape[2], shape[0], true),
+                3 => (shape[0], shape[1], shape[2], false),
+                n => return Err(FitsError::UnsupportedNaxis(n)),
+            },
+            _ => return Err(FitsError::NotAnImage(idx)),
+        };
+        if channel_hdus.is_some() {
+            naxis3 = 3;
+        }
+
+        // Detect Bayer pattern for single-plane images
+        let bayer_cfa = if naxis3 == 1 {
+            detect_bayer_pattern(&headers)
+        } else {
+            None
+        };
+        let pedestal = pedestal(&headers);
+        Ok(Layout {
+            fits,
+            idx,
+            hdu_count,
+            channel_hdus,
+            headers,
+            width,
+            height,
+            naxis3,
+            interleaved,
+            bayer_cfa,
+            pedestal,
+        })
+    }
+
+    /// A [`FitsImage`] of `channels` planes of `data`, sampled every `f`-th pixel,
+    /// with the pedestal subtracted (from the full-scale ceiling too, when known).
+    fn image(&self, channels: usize, data: Vec<f32>, bitdepth_max: f32, f: usize) -> FitsImage {
+        let pedestal = self.pedestal;
+        let header = |key| header_f64(&self.headers, key).map(|v| v as f32 - pedestal.unwrap_or(0.0));
+        let bitdepth_max = if bitdepth_max > 0.0 { bitdepth_max - pedestal.unwrap_or(0.0) } else { bitdepth_max };
+        FitsImage {
+            width: self.width / f,
+            height: self.height / f,
+            channels,
+            data: subtract_pedestal(data, pedestal),
+            headers: self.headers.clone(),
+            bitdepth_max,
+            is_bayer: self.bayer_cfa.is_some(),
+            datamin: header("DATAMIN"),
+            datamax: header("DATAMAX"),
+            hot_pixels_fixed: None,
+            decimation: f,
+            binning: 1,
+            raw_mosaic: None,
+            hdu: self.idx,
+            hdu_count: self.hdu_count,
+            channel_hdus: self.channel_hdus,
+            pedestal,
+        }
+    }
+
+    /// Every `f`-th pixel of every `f`-th row (Bayer data: every `f`-th 2×2 cell,
+    /// `f` even), reading only the rows it samples.
+    fn read_decimated(&mut self, f: usize) -> Result<FitsImage> {
+        let (width, height, naxis3) = (self.width, self.height, self.naxis3);
+        if let Some(cfa) = self.bayer_cfa {
+            let rows = read_row_subset(&mut self.fits, self.idx, width, height, 1, f, 2)?;
+            let ceiling = if is_8bit_mosaic(&self.headers) { 255.0 } else { 65535.0 };
+            let small = bayer_preview(&mosaic_to_u16(&rows), width, cfa, f);
+            return Ok(self.image(3, small, ceiling, f));
+        }
+        let row_len = if self.interleaved { width * naxis3 } else { width };
+        let mut rows = Vec::new();
+        match self.channel_hdus {
+            Some(planes) => {
+                for i in planes {
+                    rows.extend(read_row_subset(&mut self.fits, i, row_len, height, 1, f, 1)?);
+                }
+            }
+            None => {
+                let planes = if self.interleaved { 1 } else { naxis3 };
+                rows = read_row_subset(&mut self.fits, self.idx, row_len, height, planes, f, 1)?;
+            }
+        }
+        if self.interleaved {
+            rows = deinterleave(&rows, naxis3);
+        }
+        let small: Vec<f32> =
+            rows.chunks_exact(width).flat_map(|row| row.iter().step_by(f).take(width / f).copied()).collect();
+        let bd_max = bitdepth_ceiling(&self.headers, &small);
+        Ok(self.image(naxis3, small, bd_max, f))
+    }
+}
+
+impl FitsImage {
+    /// Load the first image HDU that contains data from `path`. A PEDESTAL the
+    /// pipeline added to the values is subtracted, so background levels and
+    /// photometry are relative to true zero.
+    pub fn load(path: &Path, opts: LoadOptions) -> Result<Self> {
+        Self::load_progressive(path, opts, |_| {}).map(|(img, _)| img)
+    }
+
+    /// Like [`FitsImage::load`], but for large frames first hands a decimated preview
+    /// (see [`FitsImage::load_decimated`]) to `on_preview` before the full frame is
+    /// read, then finishes the full-resolution image.
+    /// Also returns how long each loading stage took.
+    pub fn load_progressive(
+        path: &Path,
+        opts: LoadOptions,
+        on_preview: impl FnOnce(FitsImage),
+    ) -> Result<(Self, LoadTimings)> {
+        let mut timings = LoadTimings::default();
+        let mut stage = Instant::now();
+        let mut layout = Layout::open(path, opts)?;
+        let (idx, width, height, naxis3) = (layout.idx, layout.width, layout.height, layout.naxis3);
+        timings.header = stage.elapsed();
+        stage = Instant::now();
+        if let Some(f) = preview_factor(width, height) {
+            on_preview(layout.read_decimated(f)?);
+        }
+        let preview_took = stage.elapsed();
+
+        let headers = &layout.headers;
+        let fits = &mut layout.fits;
+        let mut hot_pixels_fixed = None;
+        let mut raw_mosaic = None;
+        let (channels, data, bitdepth_max) = if let Some(cfa) = layout.bayer_cfa {
+            // Debayer path: read physical values (cfitsio applies BSCALE/BZERO), bring
+            // them into [0, 65535], run demosaic, store as 3-channel f32. 8-bit
+            // mosaics are demosaiced at 8 bits and keep their [0, 255] range.
+            let hdu = fits.hdu(idx).map_err(fitsio_err(format!("reading HDU {idx}")))?;
+            stage = Instant::now();
+            let raw: Vec<f32> = hdu.read_image(fits).map_err(fitsio_err("reading pixels"))?;
+            check_data_size(idx, raw.len(), width * height)?;
+            timings.read = stage.elapsed();
+            stage = Instant::now();
+            let raw_u16 = mosaic_to_u16(&raw);
+            drop(raw);
+            let eight_bit = is_8bit_mosaic(headers);
+            let ceiling = if eight_bit { 255.0 } else { 65535.0 };
+            // Correct on a copy of the mosaic, comparing each pixel only with same-colour
+            // neighbours; the original sensor values are kept for raw statistics.
+            let corrected: Option<Vec<u16>> = opts.hot_pixel_sigma.map(|k| {
+                let mut plane: Vec<f32> = raw_u16.iter().map(|&v| v as f32).collect();
+                hot_pixels_fixed = Some(correct_hot_pixels(&mut plane, width, height, 2, k));
+                plane.iter().map(|&v| v.round() as u16).collect()
+            });
+            let mosaic = corrected.as_deref().unwrap_or(&raw_u16);
+            let debayered = debayer_u16(mosaic, width, height, cfa, opts.demosaic, eight_bit)?;
+            raw_mosaic = Some(RawMosaic { data: raw_u16, pattern: cfa_pattern(cfa) });
+            (3usize, debayered, ceiling)
+        } else {
+            // Standard path: read as f32 directly (cfitsio applies BSCALE/BZERO).
+            // Colour planes stored as separate HDUs are stacked into one cube.
+            stage = Instant::now();
+            let mut raw: Vec<f32> = Vec::with_capacity(width * height * naxis3);
+            for i in layout.channel_hdus.map_or(vec![idx], |planes| planes.to_vec()) {
+                let hdu = fits.hdu(i).map_err(fitsio_err(format!("reading HDU {i}")))?;
+                let plane: Vec<f32> = hdu.read_image(fits).map_err(fitsio_err("reading pixels"))?;
+                raw.extend(plane);
+            }
+            check_data_size(idx, raw.len(), width * height * naxis3)?;
+            if layout.interleaved {
+                raw = deinterleave(&raw, naxis3);
+            }
+            timings.read = stage.elapsed();
+            stage = Instant::now();
+            if let Some(k) = opts.hot_pixel_sigma {
+                let npix = width * height;
+                let fixed = raw
+                    .chunks_exact_mut(npix)
+                    .map(|plane| correct_hot_pixels(plane, width, height, 1, k))
+                    .sum();
+                hot_pixels_fixed = Some(fixed);
+            }
+            let bd_max = bitdepth_ceiling(headers, &raw);
+            (naxis3, raw, bd_max)
+        };
+        timings.process = stage.elapsed() + preview_took;
+
+        let mut img = layout.image(channels, data, bitdepth_max, 1);
+        img.hot_pixels_fixed = hot_pixels_fixed;
+        img.raw_mosaic = raw_mosaic;
+        Ok((img, timings))
+    }
+
+    /// Load a copy of the image of `path` decimated to about `size` pixels on its
+    /// longest side: every Nth pixel, Bayer data as 2×2 super-pixels, without
+    /// debayering or cosmetic correction. Only the rows it samples are read.
+    pub fn load_decimated(path: &Path, opts: LoadOptions, size: usize) -> Result<Self> {
+        let mut layout = Layout::open(path, opts)?;
+        let (width, height) = (layout.width, layout.height);
+        let f = (width.max(height) / size.max(1)).min(width.min(height)).max(1);
+        // Bayer data decimates into whole 2×2 CFA cells
+        let f = if layout.bayer_cfa.is_some() { (f + f % 2).min(height & !1).max(2) } else { f };
+        layout.read_decimated(f)
+    }
+
+    /// One-line summary such as `4656×3520, 3ch, 16-bit, debayered`; files with
+    /// several HDUs add the one shown, e.g. `HDU 1 (SCI) of 3`.
+    ///
+    /// The bit depth is inferred from `bitdepth_max`; float data (ceiling 0) is shown as "float".
+    pub fn summary(&self) -> String {
+        let depth = match self.bitdepth_max {
+            255.0 => "8-bit".to_string(),
+            65535.0 => "16-bit".to_string(),
+            d if d > 0.0 => format!("{}-bit", (d + 1.0).log2().round() as u32),
+            _ => "float".to_string(),
+        };
+        let mut s = format!("{}×{}, {}ch, {depth}", self.width, self.height, self.channels);
+        if self.is_bayer {
+            s.push_str(", debayered");
+        }
+        if let Some(n) = self.hot_pixels_fixed {
+            s.push_str(&format!(", {n} hot px fixed"));
+        }
+        if self.binning > 1 {
+            s.push_str(&format!(", binned {0}×{0}", self.binning));
+        }
+        if self.decimation > 1 {
+            s.push_str(&format!(", preview 1/{}", self.decimation));
+        }
+        if let Some([r, g, b]) = self.channel_hdus {
+            s.push_str(&format!(", HDUs {r}, {g}, {b} as RGB of {}", self.hdu_count));
+        } else if self.hdu_count > 1 {
+            s.push_str(&format!(", HDU {}", self.hdu));
+            if let Some(name) = self.extname() {
+                s.push_str(&format!(" ({name})"));
+            }
+            s.push_str(&format!(" of {}", self.hdu_count));
+        }
+        s
+    }
+
+    /// EXTNAME of the HDU the image was read from, if it has one.
+    pub fn extname(&self) -> Option<&str> {
+        self.header("EXTNAME").filter(|v| !v.is_empty())
+    }
+
+    /// Average `factor`×`factor` blocks of each plane into a smaller image, e.g. to
+    /// judge noise at a coarser scale. Partial blocks at the right/bottom edge are dropped.
+    pub fn bin(&self, factor: usize) -> FitsImage {
+        let factor = factor.max(1);
+        let (w, h) = (self.width / factor, self.height / factor);
+        let norm = 1.0 / (factor * factor) as f32;
+        let mut data = vec![0f32; w * h * self.channels];
+        for (src, dst) in self
+            .data
+            .chunks_exact(self.width * self.height)
+            .zip(data.chunks_exact_mut((w * h).max(1)))
+        {
+            for (y, dst_row) in dst.chunks_exact_mut(w.max(1)).take(h).enumerate() {
+                for row in src.chunks_exact(self.width).skip(y * factor).take(factor) {
+                    for (x, d) in dst_row.iter_mut().enumerate() {
+                        *d += row[x * factor..(x + 1) * factor].iter().sum::<f32>();
+                    }
+                }
+                dst_row.iter_mut().for_each(|d| *d *= norm);
+            }
+        }
+        FitsImage {
+            width: w,
+            height: h,
+            data,
+            headers: self.headers.clone(),
+            binning: self.binning * factor,
+            raw_mosaic: None,
+            ..*self
+        }
+    }
+
+    /// Copy of a three-channel image with each channel's sky background (median)
+    /// shifted down to the lowest of the three, so a light-pollution tint turns
+    /// neutral grey before stretching. Only offsets are applied, never a scale, and
+    /// `DATAMIN` moves down with the data so no shadow detail gets clipped. Other
+    /// images are returned unchanged.
+    pub fn neutralize_background(&self) -> FitsImage {
+        let mut out = FitsImage { data: self.data.clone(), headers: self.headers.clone(), raw_mosaic: None, ..*self };
+        if self.channels != 3 {
+            return out;
+        }
+        let range = self.header_range();
+        let backgrounds: Vec<f32> = (0..3)
+            .map(|c| {
+                let (hist, min, max) = Histogram::with_range(self.plane(c), range);
+                min + hist.percentile(0.5) * (max - min)
+            })
+            .collect();
+        let floor = backgrounds.iter().copied().fold(f32::INFINITY, f32::min);
+        let npix = self.width * self.height;
+        for (plane, bg) in out.data.chunks_exact_mut(npix).zip(&backgrounds) {
+            let offset = bg - floor;
+            if offset > 0.0 {
+                plane.iter_mut().for_each(|v| *v -= offset);
+            }
+        }
+        let max_offset = backgrounds.iter().map(|bg| bg - floor).fold(0.0, f32::max);
+        out.datamin = self.datamin.map(|m| m - max_offset);
+        out
+    }
+
+    /// Statistics of the raw mosaic per Bayer site (R, G1, G2, B in cell reading
+    /// order, labelled from the pattern), less the pedestal like `data`. None
+    /// unless the image was debayered.
+    pub fn cfa_stats(&self) -> Option<Vec<(String, PlaneStats)>> {
+        let raw = self.raw_mosaic.as_ref()?;
+        let pedestal = self.pedestal.unwrap_or(0.0);
+        let stats = (0..4)
+            .map(|site| {
+                let (dx, dy) = (site % 2, site / 2);
+                let values = raw
+                    .data
+                    .chunks_exact(self.width)
+                    .skip(dy)
+                    .step_by(2)
+                    .flat_map(|row| row.iter().skip(dx).step_by(2).map(|&v| v as f32 - pedestal));
+                (raw.site_label(site), PlaneStats::of(values))
+            })
+            .collect();
+        Some(stats)
+    }
+
+    /// Share of pixels at or above 98 % of `bitdepth_max`, i.e. saturated or close
+    /// to it. Bayer frames are measured on the raw sensor values (less the
+    /// pedestal), since debayering smooths peaks. None when the full scale is
+    /// unknown (float data).
+    pub fn saturated_fraction(&self) -> Option<f32> {
+        const LEVEL: f32 = 0.98;
+        if self.bitdepth_max <= 0.0 {
+            return None;
+        }
+        let threshold = LEVEL * self.bitdepth_max;
+        let (saturated, total) = match &self.raw_mosaic {
+            Some(raw) => {
+                let pedestal = self.pedestal.unwrap_or(0.0);
+                (raw.data.iter().filter(|&&v| v as f32 - pedestal >= threshold).count(), raw.data.len())
+            }
+            None => (self.data.iter().filter(|&&v| v >= threshold).count(), self.data.len()),
+        };
+        Some(saturated as f32 / total.max(1) as f32)
+    }
+
+    /// Aperture photometry of the star near (`x`, `y`) (pixels, (0, 0) the corner
+    /// of the first pixel): the centre moves to the flux-weighted centroid, the sky
+    /// is the median of the annulus between `annulus.0` and `annulus.1` pixels from
+    /// it, and the flux is the sum of the sky-subtracted values of the pixels whose
+    /// centres lie within `radius`. Colour images are measured on the mean of their
+    /// planes. None when the aperture or the annulus holds no valid pixel.
+    pub fn aperture_photometry(&self, x: f32, y: f32, radius: f32, annulus: (f32, f32)) -> Option<Photometry> {
+        let ring = |cx, cy, r_in, r_out| self.ring_pixels(cx, cy, r_in, r_out);
+        let sky_at = |cx: f32, cy: f32| {
+            let sky: Vec<f32> = ring(cx, cy, annulus.0, annulus.1).into_iter().map(|p| p.2).collect();
+            (!sky.is_empty()).then(|| {
+                let (level, mad) = median_mad(&sky);
+                BackgroundNoise { level, sigma: 1.4826 * mad }
+            })
+        };
+
+        let (mut cx, mut cy) = (x, y);
+        let mut sky = sky_at(cx, cy)?;
+        for _ in 0..CENTROID_PASSES {
+            let (mut sx, mut sy, mut sw) = (0.0f64, 0.0f64, 0.0f64);
+            for (px, py, v) in ring(cx, cy, 0.0, radius) {
+                let w = (v - sky.level).max(0.0) as f64;
+                sx += w * px as f64;
+                sy += w * py as f64;
+                sw += w;
+            }
+            if sw <= 0.0 {
+                break;
+            }
+            (cx, cy) = ((sx / sw) as f32, (sy / sw) as f32);
+            sky = sky_at(cx, cy)?;
+        }
+        let inside = ring(cx, cy, 0.0, radius);
+        if inside.is_empty() {
+            return None;
+        }
+        let flux = inside.iter().map(|p| (p.2 - sky.level) as f64).sum();
+        Some(Photometry { x: cx, y: cy, flux, sky, pixels: inside.len() })
+    }
+
+    /// Radial profile of the star near (`x`, `y`), centred and sky-subtracted as in
+    /// [`aperture_photometry`](Self::aperture_photometry): the mean of each
+    /// [`PROFILE_BIN`]-wide ring out to the inner edge of the annulus, a Gaussian
+    /// fitted to the pixels within `radius` for the FWHM, and the half-flux radius.
+    pub fn radial_profile(&self, x: f32, y: f32, radius: f32, annulus: (f32, f32)) -> Option<RadialProfile> {
+        let photometry = self.aperture_photometry(x, y, radius, annulus)?;
+        let mut samples: Vec<(f32, f32)> = self
+            .ring_pixels(photometry.x, photometry.y, 0.0, annulus.0.max(radius))
+            .into_iter()
+            .map(|(px, py, v)| ((px - photometry.x).hypot(py - photometry.y), v - photometry.sky.level))
+            .collect();
+        samples.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut bins = Vec::new();
+        for bin in samples.chunk_by(|a, b| (a.0 / PROFILE_BIN) as usize == (b.0 / PROFILE_BIN) as usize) {
+            let n = bin.len() as f32;
+            let r = ((bin[0].0 / PROFILE_BIN).floor() + 0.5) * PROFILE_BIN;
+            bins.push((r, bin.iter().map(|s| s.1).sum::<f32>() / n));
+        }
+
+        let inside = &samples[..samples.partition_point(|s| s.0 < radius)];
+        // Gaussian A·exp(−r²/2σ²): a straight line in (r², ln v), weighted by v² as
+        // the log magnifies the noise of the faint wings
+        let (mut sw, mut sx, mut sy, mut sxx, mut sxy) = (0.0f64, 0.0f64, 0.0f64, 0.0f64, 0.0f64);
+        for &(r, v) in inside.iter().filter(|s| s.1 > 0.0) {
+            let (w, x, y) = ((v as f64).powi(2), (r as f64).powi(2), (v as f64).ln());
+            sw += w;
+            sx += w * x;
+            sy += w * y;
+            sxx += w * x * x;
+            sxy += w * x * y;
+        }
+        let den = sw * sxx - sx * sx;
+        let slope = if den > 0.0 { (sw * sxy - sx * sy) / den } else { 0.0 };
+        let gaussian = (slope < 0.0).then(|| {
+            let sigma = (-0.5 / slope).sqrt();
+            let peak = ((sy - slope * sx) / sw).exp();
+            (peak as f32, sigma as f32)
+        });
+
+        // Radius enclosing half the flux, interpolated between pixel distances
+        let half = photometry.flux / 2.0;
+        let mut enclosed = 0.0f64;
+        let mut hfr = None;
+        let mut previous = 0.0f32;
+        for &(r, v) in inside {
+            let next = enclosed + v as f64;
+            if half > 0.0 && next >= half {
+                let t = if v > 0.0 { ((half - enclosed) / v as f64) as f32 } else { 1.0 };
+                hfr = Some(previous + t * (r - previous));
+                break;
+            }
+            enclosed = next;
+            previous = r;
+        }
+
+        Some(RadialProfile {
+            photometry,
+            bins,
+            gaussian,
+            fwhm: gaussian.map(|(_, sigma)| sigma * FWHM_PER_SIGMA),
+            hfr,
+        })
+    }
+
+    /// (x, y, value) of the finite pixels with centres from `r_in` up to `r_out`
+    /// pixels of (`cx`, `cy`); colour images give the mean of their planes.
+    fn ring_pixels(&self, cx: f32, cy: f32, r_in: f32, r_out: f32) -> Vec<(f32, f32, f32)> {
+        let npix = self.width * self.height;
+        let x_span = (cx - r_out).floor().max(0.0) as usize..((cx + r_out).ceil().max(0.0) as usize).min(self.width);
+        let y_span = (cy - r_out).floor().max(0.0) as usize..((cy + r_out).ceil().max(0.0) as usize).min(self.height);
+        let mut out = Vec::new();
+        for py in y_span {
+            for px in x_span.clone() {
+                let (fx, fy) = (px as f32 + 0.5, py as f32 + 0.5);
+                let d = (fx - cx).hypot(fy - cy);
+                let i = py * self.width + px;
+                let v = (0..self.channels).map(|c| self.data[c * npix + i]).sum::<f32>() / self.channels as f32;
+                if d >= r_in && d < r_out && v.is_finite() {
+                    out.push((fx, fy, v));
+                }
+            }
+        }
+        out
+    }
+
+    /// Find the stars of the image: local maxima [`DETECT_SIGMA`] noise σ above the
+    /// sky (on the mean of the planes), each measured with
+    /// [`radial_profile`](Self::radial_profile). Saturated peaks, blends whose
+    /// centroid wanders off the peak and shapes without a plausible FWHM are left
+    /// out; at most [`MAX_STARS`] of the brightest peaks are measured.
+    pub fn detect_stars(&self) -> Vec<Star> {
+        let (w, h) = (self.width, self.height);
+        let luma: Vec<f32> = if self.channels == 1 {
+            self.data.clone()
+        } else {
+            (0..w * h).map(|i| (0..self.channels).map(|c| self.plane(c)[i]).sum::<f32>() / self.channels as f32).collect()
+        };
+        let (level, mad) = median_mad(&luma);
+        let threshold = level + DETECT_SIGMA * 1.4826 * mad;
+        let saturation = if self.bitdepth_max > 0.0 { 0.98 * self.bitdepth_max } else { f32::INFINITY };
+        let border = STAR_RADIUS.ceil() as usize + 1;
+
+        let mut peaks = Vec::new();
+        for y in border..h.saturating_sub(border) {
+            for x in border..w.saturating_sub(border) {
+                let v = luma[y * w + x];
+                if !(v > threshold && v < saturation) {
+                    continue;
+                }
+                // Strictly above the pixels before it and not below those after, so a
+                // flat-topped peak counts once
+                let is_peak = (-1isize..=1).all(|dy| {
+                    (-1isize..=1).all(|dx| {
+                        let n = luma[(y as isize + dy) as usize * w + (x as isize + dx) as usize];
+                        match (dy, dx) {
+                            (0, 0) => true,
+                            _ if (dy, dx) < (0, 0) => v > n,
+                            _ => v >= n,
+                        }
+                    })
+                });
+                if is_peak {
+                    peaks.push((v, x, y));
+                }
+            }
+        }
+        peaks.sort_by(|a, b| b.0.total_cmp(&a.0));
+        peaks.truncate(MAX_STARS);
+
+        peaks
+            .into_iter()
+            .filter_map(|(_, x, y)| {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let profile = self.radial_profile(px, py, STAR_RADIUS, STAR_ANNULUS)?;
+                let p = profile.photometry;
+                let fwhm = profile.fwhm.filter(|f| STAR_FWHM.contains(f))?;
+                ((p.x - px).hypot(p.y - py) <= 1.5 && p.flux > 0.0).then(|| Star {
+                    x: p.x,
+                    y: p.y,
+                    flux: p.flux,
+                    fwhm,
+                    hfr: profile.hfr.unwrap_or(f32::NAN),
+                    snr: p.snr(),
+                })
+            })
+            .collect()
+    }
+
+    /// The celestial coordinate system from the headers, when they hold a TAN
+    /// projection (plate-solved frames).
+    pub fn wcs(&self) -> Option<Wcs> {
+        Wcs::from_headers(&self.headers)
+    }
+
+    /// The data as a spectrum when it is one-dimensional: NAXIS = 1 (loaded as a
+    /// single row), or a single-plane image one pixel high or wide.
+    pub fn spectrum(&self) -> Option<Spectrum> {
+        let axis = match (self.channels, self.width, self.height) {
+            (1, w, 1) if w > 1 => 1,
+            (1, 1, h) if h > 1 => 2,
+            _ => return None,
+        };
+        let key = |name: &str| format!("{name}{axis}");
+        let crval = header_f64(&self.headers, &key("CRVAL"));
+        let crpix = header_f64(&self.headers, &key("CRPIX")).unwrap_or(1.0);
+        let cdelt = header_f64(&self.headers, &key("CDELT"))
+            .or_else(|| header_f64(&self.headers, &format!("CD{axis}_{axis}")))
+            .unwrap_or(1.0);
+        let flux = self.data.clone();
+        let wavelength = (0..flux.len())
+            .map(|i| {
+                let pixel = (i + 1) as f64;
+                crval.map_or(pixel, |crval| crval + (pixel - crpix) * cdelt)
+            })
+            .collect();
+        let card = |name: &str| header_value(&self.headers, &key(name)).filter(|s| !s.is_empty());
+        let axis = match (card("CTYPE"), card("CUNIT")) {
+            _ if crval.is_none() => "pixel".to_string(),
+            (Some(ctype), Some(unit)) => format!("{ctype} ({unit})"),
+            (Some(label), None) | (None, Some(label)) => label.to_string(),
+            (None, None) => "coordinate".to_string(),
+        };
+        Some(Spectrum { wavelength, flux, axis })
+    }
+
+    /// Plate scale in arcseconds per full-resolution pixel: from the WCS when
+    /// the frame is plate-solved, else a `PIXSCALE`, `SECPIX` or `SCALE` card,
+    /// else `XPIXSZ` (µm) over `FOCALLEN` (mm).
+    pub fn pixel_scale(&self) -> Option<f64> {
+        let scale = if let Some(wcs) = self.wcs() {
+            let cd = wcs.cd;
+            (cd[0][0] * cd[1][1] - cd[0][1] * cd[1][0]).abs().sqrt() * 3600.0
+        } else if let Some(scale) =
+            ["PIXSCALE", "SECPIX", "SCALE"].into_iter().find_map(|key| header_f64(&self.headers, key))
+        {
+            scale
+        } else {
+            let (pixel, focal) = (header_f64(&self.headers, "XPIXSZ")?, header_f64(&self.headers, "FOCALLEN")?);
+            // 206265″ per radian; µm over mm gives 1e-3 radian
+            206.265 * pixel / focal
+        };
+        (scale > 0.0 && scale.is_finite()).then_some(scale)
+    }
+
+    /// Background level and noise of every plane (see [`BackgroundNoise`]).
+    pub fn background_noise(&self) -> Vec<BackgroundNoise> {
+        (0..self.channels)
+            .map(|c| {
+                let (level, mad) = median_mad(self.plane(c));
+                BackgroundNoise { level, sigma: 1.4826 * mad }
+            })
+            .collect()
+    }
+
+    /// Statistics of `self − other` for every plane, pixel by pixel, e.g. to check
+    /// successive darks or flats for drift. None unless both have the same size and
+    /// number of planes.
+    pub fn difference_stats(&self, other: &FitsImage) -> Option<Vec<PlaneStats>> {
+        if !self.same_shape(other) {
+            return None;
+        }
+        Some(
+            (0..self.channels)
+                .map(|c| PlaneStats::of(self.plane(c).iter().zip(other.plane(c)).map(|(a, b)| a - b)))
+                .collect(),
+        )
+    }
+
+    /// The image `self − other` or `self / other` as float data, for viewing how
+    /// two frames differ. None unless both have the same size and number of planes.
+    pub fn combine(&self, other: &FitsImage, how: Combine) -> Option<FitsImage> {
+        if !self.same_shape(other) {
+            return None;
+        }
+        let pairs = self.data.iter().zip(&other.data);
+        Some(FitsImage {
+            data: match how {
+                Combine::Difference => pairs.map(|(a, b)| a - b).collect(),
+                Combine::Ratio => pairs.map(|(a, b)| if *b == 0.0 { f32::NAN } else { a / b }).collect(),
+            },
+            headers: self.headers.clone(),
+            bitdepth_max: 0.0,
+            datamin: None,
+            datamax: None,
+            raw_mosaic: None,
+            ..*self
+        })
+    }
+
+    /// Whether `other` has the same size and number of planes, so the two can be
+    /// compared pixel by pixel.
+    pub fn same_shape(&self, other: &FitsImage) -> bool {
+        (self.width, self.height, self.channels) == (other.width, other.height, other.channels)
+            && self.data.len() == other.data.len()
+    }
+
+    /// Values of every plane at full-resolution pixel (`x`, `y`), taking preview
+    /// decimation and binning into account. None outside the image.
+    pub fn pixel(&self, x: usize, y: usize) -> Option<Vec<f32>> {
+        let reduction = self.decimation * self.binning;
+        let (x, y) = (x / reduction, y / reduction);
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let npix = self.width * self.height;
+        Some((0..self.channels).map(|c| self.data[c * npix + y * self.width + x]).collect())
+    }
+
+    /// Undebayered sensor value at pixel (`x`, `y`), less the pedestal like `data`,
+    /// and the label of its Bayer site. None unless the raw mosaic was kept
+    /// (full-resolution Bayer images only).
+    pub fn raw_pixel(&self, x: usize, y: usize) -> Option<(f32, String)> {
+        let raw = self.raw_mosaic.as_ref()?;
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let value = raw.data[y * self.width + x] as f32 - self.pedestal.unwrap_or(0.0);
+        Some((value, raw.site_label((y % 2) * 2 + x % 2)))
+    }
+
+    /// Value of header keyword `key`, if present.
+    pub fn header(&self, key: &str) -> Option<&str> {
+        header_value(&self.headers, key)
+    }
+
+    /// How the pixels are stored in the file (for compressed images, the
+    /// uncompressed image they stand for).
+    pub fn data_format(&self) -> DataFormat {
+        let int = |key| header_value(&self.headers, key).and_then(|v| v.parse::<i64>().ok());
+        DataFormat {
+            bitpix: int("BITPIX"),
+            bscale: header_f64(&self.headers, "BSCALE"),
+            bzero: header_f64(&self.headers, "BZERO"),
+            blank: int("BLANK"),
+        }
+    }
+
+    /// Stretch range from the DATAMIN/DATAMAX keywords, when both are present and sane.
+    /// Anchoring on these keeps hot pixels outside the range from blowing out the scaling.
+    fn header_range(&self) -> Option<(f32, f32)> {
+        match (self.datamin, self.datamax) {
+            (Some(lo), Some(hi)) if lo.is_finite() && hi.is_finite() && hi > lo => Some((lo, hi)),
+            _ => None,
+        }
+    }
+
+    /// Indices of the planes shown by `view`: one for greyscale views, three
+    /// (R, G, B) for composite.
+    pub fn display_plane_indices(&self, view: ChannelView) -> Vec<usize> {
+        match (self.channels, view) {
+            (1, _) => vec![0],
+            (_, ChannelView::Single(c)) => vec![c.min(self.channels - 1)],
+            (3, ChannelView::Rgb) => vec![0, 1, 2],
+            // Fallback: show first plane as grayscale
+            _ => vec![0],
+        }
+    }
+
+    /// The data planes shown by `view`: one for greyscale views, three (R, G, B) for
+    /// composite, each with exactly `width × height` values (see [`FitsImage::full_plane`]).
+    fn display_planes(&self, view: ChannelView) -> Vec<Cow<'_, [f32]>> {
+        self.display_plane_indices(view).into_iter().map(|c| self.full_plane(c)).collect()
+    }
+
+    /// Pixels of plane `c`.
+    pub fn plane(&self, c: usize) -> &[f32] {
+        let npix = self.width * self.height;
+        &self.data[(c * npix).min(self.data.len())..((c + 1) * npix).min(self.data.len())]
+    }
+
+    /// Stretch range of one plane: DATAMIN/DATAMAX when set, else its own min/max.
+    pub fn plane_range(&self, plane: &[f32]) -> (f32, f32) {
+        self.header_range().unwrap_or_else(|| data_min_max(plane))
+    }
+
+    /// Automatic screen transfer function for each plane shown by `view`
+    /// (one entry for greyscale, three for RGB).  This is the expensive,
+    /// histogram-based part of AutoStretch; the result can be tweaked and
+    /// re-applied cheaply with [`FitsImage::to_rgba_stf`]. `white_clip` is the
+    /// percentile mapped to white (see [`Stretch::AutoStretch`]). With `color.linked`,
+    /// the three RGB entries are one STF of the planes' mean (see [`ColorOptions::linked`]);
+    /// `color.background` sets where each plane's sky ends up.
+    pub fn auto_stf(&self, view: ChannelView, white_clip: f32, color: ColorOptions) -> Vec<Stf> {
+        let bd = self.bitdepth_max;
+        let planes = self.display_planes(view);
+        if let (true, [r, g, b]) = (color.linked, planes.as_slice()) {
+            let [r, g, b] = [r, g, b].map(|p| &**p);
+            let (luminance, (min, max)) = mean_plane([r, g, b], self.header_range());
+            let stf =
+                auto_stf(&Histogram::of(&luminance, min, max), min, max, bd, white_clip, color.grey_background());
+            return vec![stf; 3];
+        }
+        let targets = match planes.len() {
+            3 => color.background,
+            _ => [color.grey_background(); 3],
+        };
+        std::thread::scope(|s| {
+            let handles: Vec<_> = planes
+                .iter()
+                .zip(targets)
+                .map(|(p, target)| {
+                    s.spawn(move || {
+                        let (hist, min, max) = Histogram::with_range(p, self.header_range());
+                        auto_stf(&hist, min, max, bd, white_clip, target)
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        })
+    }
+
+    /// Build an RGBA byte buffer for `view` using explicit STF parameters
+    /// (one per displayed plane, as returned by [`FitsImage::auto_stf`]).
+    pub fn to_rgba_stf(&self, view: ChannelView, stf: &[Stf], color: ColorOptions) -> Vec<u8> {
+        let bd = self.bitdepth_max;
+        let planes = self.display_planes(view);
+        let mut ranges: Vec<(f32, f32)> = planes.iter().map(|p| self.plane_range(p)).collect();
+        if color.linked && ranges.len() == 3 {
+            let combined = ranges
+                .iter()
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &(a, b)| (lo.min(a), hi.max(b)));
+            ranges = vec![combined; 3];
+        }
+        let luts: Vec<Vec<u8>> = ranges
+            .iter()
+            .enumerate()
+            .map(|(i, &(min, max))| {
+                let params = stf.get(i).or(stf.first()).copied().unwrap_or(Stf::IDENTITY);
+                stf_lut(params, min, max, bd, color.srgb)
+            })
+            .collect();
+        if planes.len() == 3 {
+            map_rgb(
+                [&planes[0], &planes[1], &planes[2]],
+                [&luts[0], &luts[1], &luts[2]],
+                [ranges[0], ranges[1], ranges[2]],
+                color,
+            )
+        } else {
+            let mut out = map_gray(&planes[0], &luts[0], ranges[0]);
+            if color.night {
+                red_only(&mut out);
+            }
+            out
+        }
+    }
 
-/// Which channel to display.
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum ChannelView {
-    /// Composite RGB (only meaningful when channels == 3)
-    Rgb,
-    /// Single channel index (0 = R or the only channel, 1 = G, 2 = B)
-    Single(usize),
+    /// Build an RGBA byte buffer for display, applying `stretch` and showing `view`;
+    /// `color` adjusts composite RGB output.
+    /// Returns `width * height * 4` bytes in RGBA order (top-left origin).
+    pub fn to_rgba(&self, stretch: Stretch, view: ChannelView, color: ColorOptions) -> Vec<u8> {
+        let bd = self.bitdepth_max;
+        let range = self.header_range();
+        let planes = self.display_planes(view);
+        match planes.as_slice() {
+            [r, g, b] => to_rgba_rgb(r, g, b, stretch, bd, range, color),
+            planes => to_rgba_gray(&planes[0], stretch, bd, range, color),
+        }
+    }
+
+    /// Plane `c` with exactly `width × height` values: padded with NaN (shown as
+    /// missing data) when `data` is short of them, so rendering never slices past
+    /// its end. Loading checks the size, so only hand-built images are ever padded.
+    pub fn full_plane(&self, c: usize) -> Cow<'_, [f32]> {
+        let npix = self.width * self.height;
+        let plane = self.plane(c);
+        if plane.len() == npix {
+            Cow::Borrowed(plane)
+        } else {
+            let mut padded = plane.to_vec();
+            padded.resize(npix, f32::NAN);
+            Cow::Owned(padded)
+        }
+    }
+
+    /// Render `view` with `stretch` and encode it as an 8-bit RGB PNG in memory, at
+    /// the image's own `width` × `height`. Uses the default [`ColorOptions`]; no GUI
+    /// is involved, so this works in headless (server) code.
+    pub fn render_png(&self, stretch: Stretch, view: ChannelView) -> Result<Vec<u8>> {
+        let rgba = self.to_rgba(stretch, view, ColorOptions::default());
+        // The display buffer is always opaque, so the alpha channel only costs space
+        let rgb: Vec<u8> = rgba.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect();
+        let mut out = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(FitsError::Png)?;
+        writer.write_image_data(&rgb).map_err(FitsError::Png)?;
+        writer.finish().map_err(FitsError::Png)?;
+        Ok(out)
+    }
 }
 
-/// Stretch algorithm applied before display.
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum Stretch {
-    Linear,
-    AutoStretch,
+/// Look up a header value by keyword in `headers` as returned by
+/// [`read_image_headers`]; the value is trimmed, quotes already removed.
+pub fn header_value<'a>(headers: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.trim())
 }
 
-/// Demosaic algorithm used when debayering a Bayer-pattern image.
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum DemosaicMode {
-    Cubic,
-    Bilinear,
+/// Parse a numeric header value by keyword (see [`header_value`]).
+pub fn header_f64(headers: &[(String, String)], key: &str) -> Option<f64> {
+    header_value(headers, key).and_then(|v| v.parse::<f64>().ok())
 }
 
-/// Raw float pixel data loaded from one FITS image HDU.
-///
-/// Data layout: planar, `channels` planes each of `width * height` f32 values.
-/// Index: `data[channel * width * height + row * width + col]`
-pub struct FitsImage {
-    pub width: usize,
-    pub height: usize,
-    /// 1 = grayscale, 3 = RGB (either debayered or pre-separated)
-    pub channels: usize,
-    /// Raw float pixels in planar order.
-    pub data: Vec<f32>,
-    /// FITS header key/value pairs from the image HDU.
-    pub headers: Vec<(String, String)>,
-    /// Full-scale maximum for the image's bit depth (e.g. 65535 for 16-bit, 255 for 8-bit).
-    /// Used to anchor statistics in autostretch so sky normalisation matches Siril.
-    /// 0.0 means unknown / float data: autostretch falls back to data range.
-    pub bitdepth_max: f32,
-    /// True when the image was loaded via Bayer debayering.
-    /// Used to conditionally show demosaic options in the Preferences dialog.
-    pub is_bayer: bool,
+/// Offset to remove from the pixel values, from the PEDESTAL keyword. Pipelines
+/// add it to keep calibrated values positive and record it with either sign:
+/// PixInsight writes the amount added (`100`), MaxIm DL the correction to apply
+/// (`-100`). Either way the data sits that far above zero, so the magnitude is
+/// what gets subtracted.
+fn pedestal(headers: &[(String, String)]) -> Option<f32> {
+    header_f64(headers, "PEDESTAL").map(|p| p.abs() as f32).filter(|&p| p > 0.0)
 }
 
-impl FitsImage {
-    /// Load the first image HDU that contains data from `path`.
-    pub fn load(path: &Path, demosaic: DemosaicMode) -> Result<Self> {
-        let mut fits =
-            FitsFile::open(path).with_context(|| format!("opening {}", path.display()))?;
+/// `data` with `pedestal` subtracted from every value.
+fn subtract_pedestal(mut data: Vec<f32>, pedestal: Option<f32>) -> Vec<f32> {
+    if let Some(p) = pedestal {
+        data.iter_mut().for_each(|v| *v -= p);
+    }
+    data
+}
 
-        // Find first HDU with non-empty image data
-        let hdu_count = fits.iter().count();
-        let mut image_hdu_idx = None;
-        for i in 0..hdu_count {
-            let hdu = fits.hdu(i)?;
-            if let HduInfo::ImageInfo { ref shape, .. } = hdu.info {
-                if !shape.is_empty() && shape.iter().product::<usize>() > 0 {
-                    image_hdu_idx = Some(i);
-                    break;
-                }
-            }
+/// Derive the full-scale ceiling (in physical units, after BSCALE/BZERO) for an
+/// integer image from its BITPIX; 0.0 for float data (autostretch then uses the data range).
+///
+/// - BITPIX 8 / 16: the storage range mapped through BSCALE/BZERO when those keywords
+///   are present (e.g. BITPIX=16 with BZERO=32768 → 65535); 255 / 65535 otherwise.
+/// - BITPIX 32: integer containers hold anything from 16-bit camera data to genuine
+///   32-bit science data, so the ceiling is keyed off the actual data maximum,
+///   rounded up to the next `2^n − 1` (at least 255).
+fn bitdepth_ceiling(headers: &[(String, String)], data: &[f32]) -> f32 {
+    let bitpix = header_value(headers, "BITPIX").and_then(|v| v.parse::<i32>().ok());
+    let bscale = header_f64(headers, "BSCALE");
+    let bzero = header_f64(headers, "BZERO");
+    let scaled = bscale.is_some() || bzero.is_some();
+    let (bscale, bzero) = (bscale.unwrap_or(1.0), bzero.unwrap_or(0.0));
+
+    match bitpix {
+        Some(8) if scaled => (bzero + bscale * 255.0) as f32,
+        Some(8) => 255.0,
+        Some(16) if scaled => (bzero + bscale * i16::MAX as f64) as f32,
+        Some(16) => 65535.0,
+        Some(32) => {
+            let (_, max) = data_min_max(data);
+            let bits = (max.max(1.0) as f64 + 1.0).log2().ceil().clamp(8.0, 32.0);
+            (2f64.powf(bits) - 1.0) as f32
         }
-        let idx = image_hdu_idx.ok_or_else(|| anyhow::anyhow!("no image HDU found in file"))?;
-        let hdu = fits.hdu(idx)?;
+        _ => 0.0, // float (BITPIX=-32/-64): 0 → fall back to data range
+    }
+}
 
-        // cfitsio reports shape in FITS axis order: [NAXIS1, NAXIS2, NAXIS3, ...]
-        // NAXIS1 = fastest-varying (columns = width)
-        // NAXIS2 = rows = height
-        // NAXIS3 = planes / channels (if present)
-        let (width, height, naxis3) = match &hdu.info {
-            HduInfo::ImageInfo { shape, .. } => match shape.len() {
-                2 => (shape[0], shape[1], 1usize),
-                3 => (shape[0], shape[1], shape[2]),
-                n => bail!("unsupported FITS image NAXIS={n}"),
-            },
-            _ => bail!("HDU {idx} is not an image"),
-        };
+/// Colours of a CFA's 2×2 cell in reading order.
+fn cfa_pattern(cfa: bayer::CFA) -> [u8; 4] {
+    match cfa {
+        bayer::CFA::RGGB => *b"RGGB",
+        bayer::CFA::BGGR => *b"BGGR",
+        bayer::CFA::GRBG => *b"GRBG",
+        bayer::CFA::GBRG => *b"GBRG",
+    }
+}
 
-        // Collect headers first (needed for Bayer detection)
-        let headers = read_headers(path, idx)?;
+/// Convert a Bayer mosaic's physical values to the u16 range the demosaic works in.
+///
+/// Unsigned 16-bit frames (BITPIX=16, BZERO=32768) already land in [0, 65535],
+/// as does signed data from cameras of up to 15 bits; values are rounded, and
+/// the odd negative one (noise in a bias-subtracted frame) is clamped to 0.
+fn mosaic_to_u16(data: &[f32]) -> Vec<u16> {
+    data.iter().map(|&v| v.round().clamp(0.0, 65535.0) as u16).collect()
+}
 
-        // Detect Bayer pattern for single-plane images
-        let bayer_cfa = if naxis3 == 1 {
-            detect_bayer_pattern(&headers)
-        } else {
-            None
-        };
+// ---------------------------------------------------------------------------
+// Quick preview
+// ---------------------------------------------------------------------------
 
-        let is_bayer = bayer_cfa.is_some();
-        let (channels, data, bitdepth_max) = if let Some(cfa) = bayer_cfa {
-            // Debayer path: read as u16, run demosaic, store as 3-channel f32.
-            // u16 data is always [0, 65535].
-            let hdu = fits.hdu(idx)?;
-            let raw_u16: Vec<u16> = hdu.read_image(&mut fits)?;
-            let debayered = debayer_u16(&raw_u16, width, height, cfa, demosaic)?;
-            (3usize, debayered, 65535.0f32)
-        } else {
-            // Standard path: read as f32 directly (cfitsio applies BSCALE/BZERO).
-            let hdu = fits.hdu(idx)?;
-            let raw: Vec<f32> = hdu.read_image(&mut fits)?;
-            // Derive the bitdepth ceiling from the BITPIX header keyword.
-            let bd_max = headers
-                .iter()
-                .find(|(k, _)| k == "BITPIX")
-                .and_then(|(_, v)| v.trim().parse::<i32>().ok())
-                .map(|bitpix| match bitpix {
-                    8 => 255.0f32,
-                    16 => 65535.0f32,
-                    32 => 65535.0f32, // 32-bit int FITS are usually 16-bit data in disguise
-                    _ => 0.0f32,      // float (BITPIX=-32/-64): 0 → fall back to data range
-                })
-                .unwrap_or(0.0f32);
-            (naxis3, raw, bd_max)
-        };
+/// Read, for every `f`-th row `y` of each of the `planes` planes of HDU `hdu`,
+/// rows `y..y + run` (`row_len` values each), leaving the rest of the data unread.
+fn read_row_subset(
+    fits: &mut FitsFile,
+    hdu: usize,
+    row_len: usize,
+    height: usize,
+    planes: usize,
+    f: usize,
+    run: usize,
+) -> Result<Vec<f32>> {
+    let found = fits.hdu(hdu).map_err(fitsio_err(format!("reading HDU {hdu}")))?;
+    let mut out = Vec::with_capacity(planes * (height / f) * run * row_len);
+    for plane in 0..planes {
+        for y in (0..height / f).map(|y| plane * height + y * f) {
+            let start = y * row_len;
+            let rows: Vec<f32> = found
+                .read_section(fits, start, start + run * row_len)
+                .map_err(fitsio_err("reading preview rows"))?;
+            check_data_size(hdu, rows.len(), run * row_len)?;
+            out.extend(rows);
+        }
+    }
+    Ok(out)
+}
 
-        Ok(FitsImage {
-            width,
-            height,
-            channels,
-            data,
-            headers,
-            bitdepth_max,
-            is_bayer,
-        })
+/// Cheap colour preview of a Bayer mosaic from [`read_row_subset`]: `rows` holds
+/// both rows of every `f`-th 2×2 CFA cell row (`f` even), and every `f`-th cell
+/// along them becomes one RGB pixel (G = mean of the two greens).  Planar f32 output.
+fn bayer_preview(rows: &[u16], width: usize, cfa: bayer::CFA, f: usize) -> Vec<f32> {
+    // (dx, dy) offsets of R, G1, G2, B within the 2×2 cell
+    let [r, g1, g2, b] = match cfa {
+        bayer::CFA::RGGB => [(0, 0), (1, 0), (0, 1), (1, 1)],
+        bayer::CFA::BGGR => [(1, 1), (1, 0), (0, 1), (0, 0)],
+        bayer::CFA::GRBG => [(1, 0), (0, 0), (1, 1), (0, 1)],
+        bayer::CFA::GBRG => [(0, 1), (0, 0), (1, 1), (1, 0)],
+    };
+    let (pw, ph) = (width / f, rows.len() / (2 * width));
+    let npix = pw * ph;
+    let mut out = vec![0f32; npix * 3];
+    let at = |x: usize, y: usize, (dx, dy): (usize, usize)| rows[(y + dy) * width + (x + dx).min(width - 1)] as f32;
+    for y in 0..ph {
+        for x in 0..pw {
+            let (sx, sy) = (x * f, y * 2);
+            let i = y * pw + x;
+            out[i] = at(sx, sy, r);
+            out[npix + i] = 0.5 * (at(sx, sy, g1) + at(sx, sy, g2));
+            out[2 * npix + i] = at(sx, sy, b);
+        }
     }
+    out
+}
 
-    /// Build an RGBA byte buffer for display, applying `stretch` and showing `view`.
-    /// Returns `width * height * 4` bytes in RGBA order (top-left origin).
-    pub fn to_rgba(&self, stretch: Stretch, view: ChannelView) -> Vec<u8> {
-        let npix = self.width * self.height;
-        let bd = self.bitdepth_max;
+// ---------------------------------------------------------------------------
+// Cosmetic correction
+// ---------------------------------------------------------------------------
 
-        let result = match (self.channels, view) {
-            (1, _) => {
-                let plane = &self.data[..npix];
-                to_rgba_gray(plane, stretch, bd)
+/// Replace hot pixels in one plane by the median of their 8 neighbours.
+///
+/// Neighbours are taken `step` pixels away: 1 for ordinary planes, 2 for a raw
+/// Bayer mosaic so that only pixels of the same CFA colour are compared.  A pixel
+/// is hot when it exceeds that median by more than `k_sigma` times the plane's
+/// noise (robust MAD estimate).  Returns the number of pixels replaced.
+fn correct_hot_pixels(plane: &mut [f32], width: usize, height: usize, step: usize, k_sigma: f32) -> usize {
+    if width <= 2 * step || height <= 2 * step {
+        return 0;
+    }
+    let threshold = k_sigma * robust_sigma(plane).max(f32::EPSILON);
+
+    let mut fixes = Vec::new();
+    for y in step..height - step {
+        for x in step..width - step {
+            let v = plane[y * width + x];
+            if !v.is_finite() {
+                continue;
             }
-            (_, ChannelView::Single(c)) => {
-                let c = c.min(self.channels - 1);
-                let offset = c * npix;
-                let plane = &self.data[offset..offset + npix];
-                to_rgba_gray(plane, stretch, bd)
+            let mut n = [0f32; 8];
+            let mut k = 0;
+            for dy in [-1isize, 0, 1] {
+                for dx in [-1isize, 0, 1] {
+                    if dx == 0 && dy == 0 {
+                        continue;
+                    }
+                    let yy = (y as isize + dy * step as isize) as usize;
+                    let xx = (x as isize + dx * step as isize) as usize;
+                    n[k] = plane[yy * width + xx];
+                    k += 1;
+                }
             }
-            (3, ChannelView::Rgb) => {
-                let r = &self.data[0..npix];
-                let g = &self.data[npix..2 * npix];
-                let b = &self.data[2 * npix..3 * npix];
-                to_rgba_rgb(r, g, b, stretch, bd)
+            // Cheap rejection: the median is never below the minimum neighbour.
+            let nmin = n.iter().copied().fold(f32::MAX, f32::min);
+            if v - nmin <= threshold {
+                continue;
             }
-            _ => {
-                // Fallback: show first plane as grayscale
-                let plane = &self.data[..npix.min(self.data.len())];
-                to_rgba_gray(plane, stretch, bd)
+            n.sort_unstable_by(|a, b| a.total_cmp(b));
+            let median = 0.5 * (n[3] + n[4]);
+            if v - median > threshold {
+                fixes.push((y * width + x, median));
             }
-        };
-        result
+        }
+    }
+    // Apply afterwards so corrected pixels never influence their neighbours' medians.
+    for &(i, m) in &fixes {
+        plane[i] = m;
+    }
+    fixes.len()
+}
+
+/// Robust noise estimate: 1.4826 × median absolute deviation, from a subsample
+/// of at most ~1 M pixels.
+fn robust_sigma(plane: &[f32]) -> f32 {
+    let stride = (plane.len() / 1_000_000).max(1);
+    let mut sample: Vec<f32> = plane.iter().step_by(stride).copied().filter(|v| v.is_finite()).collect();
+    if sample.is_empty() {
+        return 0.0;
     }
+    let mid = sample.len() / 2;
+    let median = *sample.select_nth_unstable_by(mid, |a, b| a.total_cmp(b)).1;
+    for v in &mut sample {
+        *v = (*v - median).abs();
+    }
+    let mad = *sample.select_nth_unstable_by(mid, |a, b| a.total_cmp(b)).1;
+    1.4826 * mad
 }
 
 // ---------------------------------------------------------------------------
@@ -217,30 +2077,70 @@ fn detect_bayer_pattern(headers: &[(String, String)]) -> Option<bayer::CFA> {
     }
 }
 
+/// Whether the channels of a 3-D image of `shape` (FITS axis order) are its
+/// first axis rather than its third.
+fn channels_first(shape: &[usize], headers: &[(String, String)], axis: ColorAxis) -> bool {
+    let is_colour = |key| {
+        header_value(headers, key).is_some_and(|v| {
+            let v = v.to_ascii_uppercase();
+            ["RGB", "COLOR", "COLOUR", "CHANNEL", "BAND"].iter().any(|c| v.starts_with(c))
+        })
+    };
+    match axis {
+        ColorAxis::Last => false,
+        ColorAxis::First => true,
+        ColorAxis::Auto if is_colour("CTYPE3") => false,
+        ColorAxis::Auto if is_colour("CTYPE1") => true,
+        ColorAxis::Auto => shape[0] == 3 && shape[2] > 3,
+    }
+}
+
+/// Pixel-interleaved samples (`channels` values per pixel) → planar data.
+fn deinterleave(data: &[f32], channels: usize) -> Vec<f32> {
+    (0..channels).flat_map(|c| data.iter().skip(c).step_by(channels).copied()).collect()
+}
+
+/// True for a mosaic of plain 8-bit samples (BITPIX = 8 without scaling), which
+/// is demosaiced at 8 bits rather than 16.
+fn is_8bit_mosaic(headers: &[(String, String)]) -> bool {
+    header_value(headers, "BITPIX") == Some("8")
+        && header_f64(headers, "BZERO").unwrap_or(0.0) == 0.0
+        && header_f64(headers, "BSCALE").unwrap_or(1.0) == 1.0
+}
+
 /// Debayer a u16 single-plane image into three f32 planes (R, G, B).
-/// Output is stored as planar f32: [R plane, G plane, B plane], values in [0, 65535].
+/// Output is stored as planar f32: [R plane, G plane, B plane], values in [0, 65535],
+/// or with `eight_bit` (samples already in [0, 255]) demosaiced at 8 bits, in [0, 255].
 fn debayer_u16(
     raw: &[u16],
     width: usize,
     height: usize,
     cfa: bayer::CFA,
     demosaic: DemosaicMode,
+    eight_bit: bool,
 ) -> Result<Vec<f32>> {
-    // Convert u16 slice to little-endian bytes for the bayer crate
-    let mut bytes = Vec::with_capacity(raw.len() * 2);
-    for &v in raw {
-        bytes.extend_from_slice(&v.to_le_bytes());
-    }
+    // Convert to bytes for the bayer crate: one per sample at 8 bits, else little-endian u16
+    let (bytes, raster_depth, bayer_depth) = if eight_bit {
+        let bytes: Vec<u8> = raw.iter().map(|&v| v.min(255) as u8).collect();
+        (bytes, bayer::RasterDepth::Depth8, bayer::BayerDepth::Depth8)
+    } else {
+        let mut bytes = Vec::with_capacity(raw.len() * 2);
+        for &v in raw {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        (bytes, bayer::RasterDepth::Depth16, bayer::BayerDepth::Depth16LE)
+    };
 
-    // Output buffer: 3 bytes per pixel at 16-bit = 6 bytes/pixel
+    // Output buffer: 3 samples per pixel, 1 or 2 bytes each
     let npix = width * height;
-    let mut rgb_buf = vec![0u8; npix * 6];
+    let sample_bytes = if eight_bit { 1 } else { 2 };
+    let mut rgb_buf = vec![0u8; npix * 3 * sample_bytes];
 
     {
         let mut dst = bayer::RasterMut::new(
             width,
             height,
-            bayer::RasterDepth::Depth16,
+            raster_depth,
             &mut rgb_buf,
         );
         let algo = match demosaic {
@@ -249,22 +2149,25 @@ fn debayer_u16(
         };
         bayer::run_demosaic(
             &mut Cursor::new(&bytes),
-            bayer::BayerDepth::Depth16LE,
+            bayer_depth,
             cfa,
             algo,
             &mut dst,
         )
-        .map_err(|e| anyhow::anyhow!("debayer error: {e:?}"))?;
+        .map_err(|e| FitsError::Debayer(format!("{e:?}")))?;
     }
 
-    // Convert interleaved RGB u16 → planar f32
-    // rgb_buf layout: [R0_lo, R0_hi, G0_lo, G0_hi, B0_lo, B0_hi, R1_lo, ...]
+    // Convert interleaved RGB → planar f32
+    // rgb_buf layout: [R0, G0, B0, R1, ...] with 16-bit samples as [lo, hi]
+    let sample = |i: usize| match eight_bit {
+        true => rgb_buf[i] as f32,
+        false => u16::from_le_bytes([rgb_buf[2 * i], rgb_buf[2 * i + 1]]) as f32,
+    };
     let mut data = vec![0f32; npix * 3];
     for i in 0..npix {
-        let base = i * 6;
-        data[i]          = u16::from_le_bytes([rgb_buf[base],     rgb_buf[base + 1]]) as f32;
-        data[npix + i]   = u16::from_le_bytes([rgb_buf[base + 2], rgb_buf[base + 3]]) as f32;
-        data[2 * npix + i] = u16::from_le_bytes([rgb_buf[base + 4], rgb_buf[base + 5]]) as f32;
+        data[i]            = sample(i * 3);
+        data[npix + i]     = sample(i * 3 + 1);
+        data[2 * npix + i] = sample(i * 3 + 2);
     }
 
     Ok(data)
@@ -274,16 +2177,125 @@ fn debayer_u16(
 // Stretch helpers
 // ---------------------------------------------------------------------------
 
-fn to_rgba_gray(plane: &[f32], stretch: Stretch, bitdepth_max: f32) -> Vec<u8> {
-    let (min, max) = data_min_max(plane);
-    let lut = match stretch {
-        Stretch::Linear => linear_lut(min, max),
-        Stretch::AutoStretch => autostretch_lut(plane, min, max, bitdepth_max),
-    };
+/// Display LUT of one plane for `stretch`, with the `[min, max]` range it is indexed over.
+/// `range` overrides the computed data min/max (e.g. from DATAMIN/DATAMAX);
+/// values outside it clip to black/white. Linear needs no histogram, so it only
+/// scans the plane for its min/max. AutoStretch maps the sky to `background`.
+/// With `srgb` the levels are sRGB-encoded before they are rounded to bytes.
+fn plane_lut(
+    plane: &[f32],
+    stretch: Stretch,
+    bitdepth_max: f32,
+    range: Option<(f32, f32)>,
+    background: f32,
+    srgb: bool,
+) -> (Vec<u8>, (f32, f32)) {
+    match stretch {
+        Stretch::Linear => {
+            let (min, max) = range.unwrap_or_else(|| data_min_max(plane));
+            (linear_ramp(srgb), (min, max))
+        }
+        Stretch::Percentile { low, high } => {
+            let (hist, min, max) = Histogram::with_range(plane, range);
+            (percentile_lut(&hist, low, high, srgb), (min, max))
+        }
+        Stretch::AutoStretch { white_clip } => {
+            let (hist, min, max) = Histogram::with_range(plane, range);
+            let stf = auto_stf(&hist, min, max, bitdepth_max, white_clip, background);
+            (stf_lut(stf, min, max, bitdepth_max, srgb), (min, max))
+        }
+        Stretch::RawLinear => {
+            let (min, max) =
+                if bitdepth_max > 0.0 { (0.0, bitdepth_max) } else { range.unwrap_or_else(|| data_min_max(plane)) };
+            (linear_ramp(srgb), (min, max))
+        }
+        Stretch::Log { k } => {
+            let (min, max) = range.unwrap_or_else(|| data_min_max(plane));
+            (log_lut(k, srgb), (min, max))
+        }
+        Stretch::HistEq { .. } => {
+            let (hist, min, max) = Histogram::with_range(plane, range);
+            (histeq_lut(&hist, srgb), (min, max))
+        }
+    }
+}
+
+fn to_rgba_gray(
+    plane: &[f32],
+    stretch: Stretch,
+    bitdepth_max: f32,
+    range: Option<(f32, f32)>,
+    color: ColorOptions,
+) -> Vec<u8> {
+    let (lut, range) = plane_lut(plane, stretch, bitdepth_max, range, color.grey_background(), color.srgb);
+    let mut out = map_gray(plane, &lut, range);
+    if color.night {
+        red_only(&mut out);
+    }
+    out
+}
+
+fn to_rgba_rgb(
+    r: &[f32],
+    g: &[f32],
+    b: &[f32],
+    stretch: Stretch,
+    bitdepth_max: f32,
+    range: Option<(f32, f32)>,
+    color: ColorOptions,
+) -> Vec<u8> {
+    if color.linked || stretch == (Stretch::HistEq { shared: true }) {
+        let (luminance, range) = mean_plane([r, g, b], range);
+        let (lut, range) = plane_lut(&luminance, stretch, bitdepth_max, Some(range), color.grey_background(), color.srgb);
+        return map_rgb([r, g, b], [&lut, &lut, &lut], [range; 3], color);
+    }
+    // Each channel's stretch is independent: run R, G, B in parallel.
+    // std::thread::scope keeps it dependency-free; each thread owns its
+    // histogram allocation so there is no cache contention.
+    let [r_bg, g_bg, b_bg] = color.background;
+    let ((r_lut, r_range), (g_lut, g_range), (b_lut, b_range)) = std::thread::scope(|s| {
+        let rh = s.spawn(|| plane_lut(r, stretch, bitdepth_max, range, r_bg, color.srgb));
+        let gh = s.spawn(|| plane_lut(g, stretch, bitdepth_max, range, g_bg, color.srgb));
+        let bh = s.spawn(|| plane_lut(b, stretch, bitdepth_max, range, b_bg, color.srgb));
+        (rh.join().unwrap(), gh.join().unwrap(), bh.join().unwrap())
+    });
+
+    map_rgb([r, g, b], [&r_lut, &g_lut, &b_lut], [r_range, g_range, b_range], color)
+}
+
+/// A display level in [0, 1] as the byte sent to the screen, sRGB-encoded first
+/// with `srgb` so dark levels keep their precision.
+fn level_byte(x: f32, srgb: bool) -> u8 {
+    let x = x.clamp(0.0, 1.0);
+    let x = if srgb { srgb_encode(x) } else { x };
+    (x * 255.0).round() as u8
+}
+
+/// The sRGB transfer function (IEC 61966-2-1): linear light in [0, 1] to the
+/// encoded value in [0, 1].
+pub fn srgb_encode(x: f32) -> f32 {
+    if x <= 0.003_130_8 {
+        12.92 * x
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Colour painted for NaN / infinite pixels so bad data stands out instead of
+/// passing for black or white. No component is 0 or 255, so it does not count as clipping.
+pub const NON_FINITE_RGB: [u8; 3] = [220, 20, 60];
+
+/// Map one plane through `lut` (indexed over `[min, max]`) into grey RGBA;
+/// non-finite values get [`NON_FINITE_RGB`].
+fn map_gray(plane: &[f32], lut: &[u8], (min, max): (f32, f32)) -> Vec<u8> {
     // Pre-compute scale once: avoids a division per pixel inside the loop.
     let scale = if max == min { 0.0 } else { (LUT_SIZE - 1) as f32 / (max - min) };
     let mut out = vec![255u8; plane.len() * 4];
     for (i, &v) in plane.iter().enumerate() {
+        if !v.is_finite() {
+            out[i * 4..i * 4 + 3].copy_from_slice(&NON_FINITE_RGB);
+            continue;
+        }
         let idx = (((v - min) * scale + 0.5) as usize).min(LUT_SIZE - 1);
         let g = lut[idx];
         out[i * 4]     = g;
@@ -294,29 +2306,18 @@ fn to_rgba_gray(plane: &[f32], stretch: Stretch, bitdepth_max: f32) -> Vec<u8> {
     out
 }
 
-fn to_rgba_rgb(r: &[f32], g: &[f32], b: &[f32], stretch: Stretch, bitdepth_max: f32) -> Vec<u8> {
-    let (rmin, rmax) = data_min_max(r);
-    let (gmin, gmax) = data_min_max(g);
-    let (bmin, bmax) = data_min_max(b);
-
-    let (r_lut, g_lut, b_lut) = match stretch {
-        Stretch::Linear => (
-            linear_lut(rmin, rmax),
-            linear_lut(gmin, gmax),
-            linear_lut(bmin, bmax),
-        ),
-        Stretch::AutoStretch => {
-            // Each channel's autostretch is independent: run R, G, B in parallel.
-            // std::thread::scope keeps it dependency-free; each thread owns its
-            // histogram allocation so there is no cache contention.
-            std::thread::scope(|s| {
-                let rh = s.spawn(|| autostretch_lut(r, rmin, rmax, bitdepth_max));
-                let gh = s.spawn(|| autostretch_lut(g, gmin, gmax, bitdepth_max));
-                let bh = s.spawn(|| autostretch_lut(b, bmin, bmax, bitdepth_max));
-                (rh.join().unwrap(), gh.join().unwrap(), bh.join().unwrap())
-            })
-        }
-    };
+/// Map three planes through their per-channel LUTs into colour RGBA,
+/// then apply the colour adjustments. Pixels with a non-finite value in any
+/// plane get [`NON_FINITE_RGB`].
+fn map_rgb(
+    planes: [&[f32]; 3],
+    luts: [&[u8]; 3],
+    ranges: [(f32, f32); 3],
+    color: ColorOptions,
+) -> Vec<u8> {
+    let [r, g, b] = planes;
+    let [r_lut, g_lut, b_lut] = luts;
+    let [(rmin, rmax), (gmin, gmax), (bmin, bmax)] = ranges;
 
     // Pre-compute per-channel scale: avoids a division per pixel inside the loop.
     let rscale = if rmax == rmin { 0.0 } else { (LUT_SIZE - 1) as f32 / (rmax - rmin) };
@@ -334,65 +2335,252 @@ fn to_rgba_rgb(r: &[f32], g: &[f32], b: &[f32], stretch: Stretch, bitdepth_max:
         out[i * 4 + 2] = b_lut[bi];
         // [i*4+3] = 255 already
     }
+    if color.palette != Palette::Rgb {
+        apply_palette(&mut out, color.palette.matrix());
+    }
+    if color.saturation != 1.0 {
+        apply_saturation(&mut out, color.saturation);
+    }
+    for i in 0..npix {
+        if !(r[i].is_finite() && g[i].is_finite() && b[i].is_finite()) {
+            out[i * 4..i * 4 + 3].copy_from_slice(&NON_FINITE_RGB);
+        }
+    }
+    if color.night {
+        red_only(&mut out);
+    }
     out
 }
 
+/// Night vision: move each pixel's luminance (Rec. 709 weights) into red and zero
+/// green and blue.
+fn red_only(rgba: &mut [u8]) {
+    for px in rgba.chunks_exact_mut(4) {
+        let luma = 0.2126 * px[0] as f32 + 0.7152 * px[1] as f32 + 0.0722 * px[2] as f32;
+        px[0] = (luma + 0.5) as u8;
+        px[1] = 0;
+        px[2] = 0;
+    }
+}
+
+/// Share of a rendered channel's pixels that came out pure black or pure white.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Clipping {
+    /// Fraction of pixels at 0
+    pub black: f32,
+    /// Fraction of pixels at 255
+    pub white: f32,
+}
+
+/// Clipping of a rendered RGBA buffer: one entry per channel for composite colour
+/// output (`colour`), a single entry for greyscale.
+pub fn clipping(rgba: &[u8], colour: bool) -> Vec<Clipping> {
+    let npix = (rgba.len() / 4).max(1) as f32;
+    let channels = if colour { 3 } else { 1 };
+    (0..channels)
+        .map(|c| {
+            let (mut black, mut white) = (0usize, 0usize);
+            for px in rgba.chunks_exact(4) {
+                match px[c] {
+                    0 => black += 1,
+                    255 => white += 1,
+                    _ => {}
+                }
+            }
+            Clipping { black: black as f32 / npix, white: white as f32 / npix }
+        })
+        .collect()
+}
+
+/// Remix every RGBA pixel through a 3×3 channel matrix (see [`Palette::matrix`]).
+fn apply_palette(rgba: &mut [u8], m: [[f32; 3]; 3]) {
+    for px in rgba.chunks_exact_mut(4) {
+        let src = [px[0] as f32, px[1] as f32, px[2] as f32];
+        for (c, row) in px[..3].iter_mut().zip(m.iter()) {
+            let v = row[0] * src[0] + row[1] * src[1] + row[2] * src[2];
+            *c = v.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+/// Scale HSV saturation of every RGBA pixel by `factor`, keeping hue and value.
+///
+/// Each channel moves away from (or towards) the pixel's maximum by the same factor,
+/// so the channel ratios that define hue are preserved and the brightest channel
+/// never clips.  The boost is limited per pixel so no channel goes below zero,
+/// and fades out near white where a channel is likely clipped by the stretch and
+/// its true colour is unknown.
+fn apply_saturation(rgba: &mut [u8], factor: f32) {
+    /// Value above which the boost tapers to nothing at 255.
+    const HIGHLIGHT_KNEE: f32 = 240.0;
+    let factor = factor.max(0.0);
+    for px in rgba.chunks_exact_mut(4) {
+        let [r, g, b] = [px[0] as f32, px[1] as f32, px[2] as f32];
+        let v = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        if v <= 0.0 || v == min {
+            continue; // black or neutral grey: no hue to saturate
+        }
+        let mut k = factor;
+        if k > 1.0 {
+            let taper = ((255.0 - v) / (255.0 - HIGHLIGHT_KNEE)).clamp(0.0, 1.0);
+            k = 1.0 + (k - 1.0) * taper;
+            k = k.min(v / (v - min)); // keep the weakest channel ≥ 0
+        }
+        for c in &mut px[..3] {
+            *c = (v - (v - *c as f32) * k).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Stretch implementation
 // ---------------------------------------------------------------------------
 
-const LUT_SIZE: usize = 4096;
+/// Number of entries of a display LUT: entry `i` is the output byte for the value
+/// `min + i / (LUT_SIZE − 1) · (max − min)` of the range the LUT spans.
+pub const LUT_SIZE: usize = 4096;
+
+/// The linear display ramp from black at the minimum of a LUT's range to white
+/// at the maximum, sRGB-encoded with `srgb`.
+fn linear_ramp(srgb: bool) -> Vec<u8> {
+    (0..LUT_SIZE).map(|i| level_byte(i as f32 / (LUT_SIZE - 1) as f32, srgb)).collect()
+}
+
+/// Logarithmic LUT `ln(1 + k·x) / ln(1 + k)` over the normalised data range. Values
+/// at or below the minimum (and every pixel of a flat image) map to black, like the
+/// linear stretch; a knee too small to matter falls back to linear.
+fn log_lut(k: f32, srgb: bool) -> Vec<u8> {
+    if k.is_nan() || k <= 1e-6 {
+        return linear_ramp(srgb);
+    }
+    let norm = k.ln_1p();
+    (0..LUT_SIZE)
+        .map(|i| {
+            let x = (i as f32 / (LUT_SIZE - 1) as f32).max(0.0);
+            level_byte((k * x).ln_1p() / norm, srgb)
+        })
+        .collect()
+}
+
+/// Histogram-equalisation LUT: each level maps to the share of pixels at or below it,
+/// rescaled so the lowest populated level is black. A flat plane falls back to linear.
+fn histeq_lut(hist: &Histogram, srgb: bool) -> Vec<u8> {
+    let first = hist.bins.iter().copied().find(|&c| c > 0).unwrap_or(0);
+    if !hist.has_range || hist.count <= first {
+        return linear_ramp(srgb);
+    }
+    let mut cdf = Vec::with_capacity(HIST_BINS);
+    let mut cumsum = 0u64;
+    for &c in &hist.bins {
+        cumsum += c;
+        cdf.push(cumsum.saturating_sub(first) as f64 / (hist.count - first) as f64);
+    }
+    (0..LUT_SIZE)
+        .map(|i| {
+            let bin = i * (HIST_BINS - 1) / (LUT_SIZE - 1);
+            level_byte(cdf[bin] as f32, srgb)
+        })
+        .collect()
+}
 
+/// Per-pixel mean of three planes and the range they span together (`range` when
+/// given), the basis of linked stretches and shared equalisation.
+fn mean_plane(planes: [&[f32]; 3], range: Option<(f32, f32)>) -> (Vec<f32>, (f32, f32)) {
+    let (min, max) = range.unwrap_or_else(|| {
+        planes.iter().map(|p| data_min_max(p)).fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), (a, b)| {
+            (lo.min(a), hi.max(b))
+        })
+    });
+    let [r, g, b] = planes;
+    let luminance: Vec<f32> = r.iter().zip(g).zip(b).map(|((&r, &g), &b)| (r + g + b) / 3.0).collect();
+    (luminance, (min, max))
+}
 
-fn linear_lut(_min: f32, _max: f32) -> Vec<u8> {
+/// Linear LUT between the `low` and `high` percentiles of a plane's histogram: values at or
+/// below the low point map to black, at or above the high point to white.
+fn percentile_lut(hist: &Histogram, low: f32, high: f32, srgb: bool) -> Vec<u8> {
+    let lo = hist.percentile(low.clamp(0.0, 1.0) as f64);
+    let hi = hist.percentile(high.clamp(0.0, 1.0) as f64);
+    if hi <= lo {
+        return linear_ramp(srgb);
+    }
     (0..LUT_SIZE)
-        .map(|i| ((i as f32 / (LUT_SIZE - 1) as f32) * 255.0).round() as u8)
+        .map(|i| {
+            let x = i as f32 / (LUT_SIZE - 1) as f32;
+            level_byte((x - lo) / (hi - lo), srgb)
+        })
+        .collect()
+}
+
+/// Autostretch LUT of `plane`, modelled after ASIFitsView / PixInsight STF
+/// behaviour: the automatic [`Stf`] for its histogram, as [`Stretch::AutoStretch`]
+/// renders it. Returns the [`LUT_SIZE`] entries with the `[min, max]` range they
+/// span; see [`stretch_plane`] for applying it. `bitdepth_max` is the full-scale
+/// value of the data (65535 for 16-bit), or 0 for float data.
+pub fn autostretch_lut(plane: &[f32], bitdepth_max: f32, white_clip: f32) -> (Vec<u8>, (f32, f32)) {
+    plane_lut(plane, Stretch::AutoStretch { white_clip }, bitdepth_max, None, DEFAULT_BACKGROUND, false)
+}
+
+/// Linear LUT of `plane`, as [`Stretch::Linear`] renders it: an even ramp from
+/// black at its minimum to white at its maximum. Returns the [`LUT_SIZE`] entries
+/// with the `[min, max]` range they span, like [`autostretch_lut`].
+pub fn linear_lut(plane: &[f32]) -> (Vec<u8>, (f32, f32)) {
+    plane_lut(plane, Stretch::Linear, 0.0, None, DEFAULT_BACKGROUND, false)
+}
+
+/// Stretch `plane` for display without building a [`FitsImage`]: one byte per
+/// value, as the greyscale view shows it. Non-finite values come out black.
+/// `bitdepth_max` is the full-scale value of the data, or 0 when unknown.
+pub fn stretch_plane(plane: &[f32], stretch: Stretch, bitdepth_max: f32) -> Vec<u8> {
+    let (lut, (min, max)) = plane_lut(plane, stretch, bitdepth_max, None, DEFAULT_BACKGROUND, false);
+    let scale = if max == min { 0.0 } else { (LUT_SIZE - 1) as f32 / (max - min) };
+    plane
+        .iter()
+        .map(|&v| if v.is_finite() { lut[(((v - min) * scale + 0.5) as usize).min(LUT_SIZE - 1)] } else { 0 })
         .collect()
 }
 
-/// Autostretch LUT modelled after ASIFitsView / PixInsight STF behaviour.
+/// Compute the automatic screen transfer function for one plane.
 ///
 /// Algorithm:
 /// 1. Find the sky-background level as the histogram **mode** (peak bin in the
 ///    lower third of the value range).  This is the black point c0.
-/// 2. Find the **median of all pixels above c0** — the representative faint-signal
-///    level — and use it as the midtone input value.
-/// 3. Clip the top 0.02 % to white (saturated stars / hot pixels).
-/// 4. Compute MTF midtone parameter m so that MTF(x_mid, m) = TARGET_BG.
-/// 5. Build the LUT: v ≤ c0 → 0, v ≥ white → 255, else MTF((v−c0)/(bd−c0), m).
-fn autostretch_lut(data: &[f32], data_min: f32, data_max: f32, bitdepth_max: f32) -> Vec<u8> {
-    /// Sky background maps to this output fraction (keeping it slightly off-black
-    /// so faint structure just above sky is visible).
-    const TARGET_BG: f32 = 0.20;
-    const HIGH_PCTILE: f64 = 0.9998;
-
+/// 2. Place the midtone input value at mode + K σ above the sky (see
+///    [`background_mode_and_midtone`]).
+/// 3. Clip everything above the `white_clip` percentile to white (by default the
+///    top 0.02 %: saturated stars / hot pixels).
+/// 4. Compute MTF midtone parameter m so that MTF(x_mid, m) = `background`
+///    (see [`DEFAULT_BACKGROUND`]).
+fn auto_stf(
+    hist: &Histogram,
+    data_min: f32,
+    data_max: f32,
+    bitdepth_max: f32,
+    white_clip: f32,
+    background: f32,
+) -> Stf {
     let range = data_max - data_min;
-    if range == 0.0 {
-        return vec![128u8; LUT_SIZE];
-    }
-
     let bd = if bitdepth_max > 0.0 { bitdepth_max } else { data_max };
-    if bd == 0.0 {
-        return vec![128u8; LUT_SIZE];
+    if range == 0.0 || bd == 0.0 || bd <= data_min {
+        return Stf::IDENTITY;
     }
+    let full = bd - data_min;
 
     // 1. Sky background (histogram mode) and median of above-background signal.
-    let (c0_abs, mid_abs) = background_mode_and_midtone(data, data_min, data_max);
+    let (c0_abs, mid_abs) = background_mode_and_midtone(hist, data_min, data_max);
 
-    // 2. White point: clip top 0.02 % (hot pixels / saturated stars).
-    let hi_frac = percentile_norm(data, data_min, data_max, HIGH_PCTILE);
+    // 2. White point: clip above the white percentile (hot pixels / saturated stars).
+    let hi_frac = hist.percentile(white_clip.clamp(0.5, 1.0) as f64);
     let white_abs = data_min + hi_frac * range;
 
-    // 3. Stretch domain [c0_abs, bd].  Using the full bitdepth ceiling as the
-    //    white end keeps very bright stars below clipping unless truly saturated.
+    // 3. Midtone in normalised [0, 1] stretch space (see `stf_lut` for the domain).
     let scale = (bd - c0_abs).max(1.0);
-
-    // 4. Midtone in normalised [0, 1] stretch space.
     let x_mid = ((mid_abs - c0_abs) / scale).clamp(1e-9, 1.0 - 1e-9);
 
-    // 5. MTF midtone parameter m : MTF(x_mid, m) = TARGET_BG.
+    // 4. MTF midtone parameter m : MTF(x_mid, m) = background.
     //    Closed-form inverse: m = x*(T−1) / (2*x*T − T − x)
-    let t = TARGET_BG;
+    let t = background.clamp(0.01, 0.99);
     let denom = 2.0 * x_mid * t - t - x_mid;
     let m = if denom.abs() > 1e-9 {
         (x_mid * (t - 1.0) / denom).clamp(1e-9, 1.0 - 1e-9)
@@ -400,15 +2588,28 @@ fn autostretch_lut(data: &[f32], data_min: f32, data_max: f32, bitdepth_max: f32
         t
     };
 
-    // 6. Build LUT.
+    Stf {
+        shadows: (c0_abs - data_min) / full,
+        midtones: m,
+        highlights: (white_abs - data_min) / full,
+    }
+}
+
+/// Build the display LUT (indexed over `[data_min, data_max]`) for explicit STF parameters.
+///
+/// v ≤ shadows → 0, v ≥ highlights → 255, else MTF((v − c0) / (bd − c0), m),
+/// where c0 is the absolute black point.  Using the full bitdepth ceiling as the
+/// white end of the MTF domain keeps very bright stars below clipping unless truly saturated.
+/// With `srgb` the levels are sRGB-encoded before rounding.
+fn stf_lut(stf: Stf, data_min: f32, data_max: f32, bitdepth_max: f32, srgb: bool) -> Vec<u8> {
+    let Some(curve) = stf.curve(data_min, data_max, bitdepth_max) else {
+        return vec![128u8; LUT_SIZE];
+    };
+    let range = data_max - data_min;
     (0..LUT_SIZE)
         .map(|i| {
             let v = data_min + (i as f32 / (LUT_SIZE - 1) as f32) * range;
-            if v <= c0_abs  { return 0u8;   }
-            if v >= white_abs { return 255u8; }
-            let x = ((v - c0_abs) / scale).clamp(0.0, 1.0);
-            let y = mtf(x, m);
-            (y * 255.0).round().clamp(0.0, 255.0) as u8
+            level_byte(curve.apply(v), srgb)
         })
         .collect()
 }
@@ -423,8 +2624,7 @@ fn autostretch_lut(data: &[f32], data_min: f32, data_max: f32, bitdepth_max: f32
 /// by galaxy/nebula signal).  K ≈ 13 is calibrated so that the midtone sits
 /// well above the noise floor — it represents real astronomical signal, not
 /// noise fluctuations — matching ASIFitsView's auto-stretch behaviour.
-fn background_mode_and_midtone(data: &[f32], min: f32, max: f32) -> (f32, f32) {
-    const BINS: usize = 4096;
+fn background_mode_and_midtone(hist: &Histogram, min: f32, max: f32) -> (f32, f32) {
     /// Midtone at mode + K × σ.  K ≈ 3 places the midtone ~3σ above the
     /// sky background, keeping faint signal visible while dampening noise.
     const K_MIDTONE: f32 = 3.0;
@@ -433,26 +2633,18 @@ fn background_mode_and_midtone(data: &[f32], min: f32, max: f32) -> (f32, f32) {
     if range <= 0.0 {
         return (min, (min + max) / 2.0);
     }
-    let bin_width = range / (BINS - 1) as f32;
-
-    // Build histogram over the full data range.
-    let mut hist = vec![0u64; BINS];
-    for &v in data {
-        if v.is_finite() {
-            let bin = (((v - min) / range).clamp(0.0, 1.0) * (BINS - 1) as f32) as usize;
-            hist[bin.min(BINS - 1)] += 1;
-        }
-    }
+    let bin_width = range / (HIST_BINS - 1) as f32;
+    let hist = &hist.bins;
 
     // Mode: peak bin in the lower 33 % of the range (sky-background region).
-    let search_end = BINS / 3;
+    let search_end = HIST_BINS / 3;
     let mode_bin = hist[..search_end]
         .iter()
         .enumerate()
         .max_by_key(|&(_, &c)| c)
         .map(|(i, _)| i)
         .unwrap_or(0);
-    let mode_val = min + (mode_bin as f32 / (BINS - 1) as f32) * range;
+    let mode_val = min + (mode_bin as f32 / (HIST_BINS - 1) as f32) * range;
 
     // Noise sigma from the LEFT-side half-max of the mode peak.
     // The left side is pure sky noise (no galaxy/nebula contamination).
@@ -472,40 +2664,125 @@ fn background_mode_and_midtone(data: &[f32], min: f32, max: f32) -> (f32, f32) {
     (mode_val, midtone)
 }
 
-/// Find the value at `pctile` (e.g. 0.9999) of `data`, returned as a fraction
-/// of the [min, max] range (so 0.0 = min, 1.0 = max).
-fn percentile_norm(data: &[f32], min: f32, max: f32, pctile: f64) -> f32 {
-    const BINS: usize = 4096;
-    let range = max - min;
-    if range == 0.0 {
-        return 1.0;
+const HIST_BINS: usize = 4096;
+
+/// Median and median absolute deviation of the finite values of `data`.
+///
+/// Histogram-based: a coarse histogram over the whole range locates the 10th–90th
+/// percentile band, a second one over just that band (values outside pile up in
+/// the end bins, which leaves the median in place) pins down the median, and a
+/// third of the deviations from it gives the MAD. Resolution is a 4096th of the
+/// band rather than of the full range, fine enough for background noise even when
+/// a few saturated stars stretch the range to 65535.
+pub fn median_mad(data: &[f32]) -> (f32, f32) {
+    let (coarse, min, max) = Histogram::with_range(data, None);
+    if !coarse.has_range || coarse.count == 0 {
+        return (min, 0.0);
+    }
+    let bin_width = (max - min) / (HIST_BINS - 1) as f32;
+    let lo = (min + coarse.percentile(0.1) * (max - min) - bin_width).max(min);
+    let hi = (min + coarse.percentile(0.9) * (max - min) + bin_width).min(max);
+    let band = Histogram::of(data, lo, hi);
+    let median = lo + band.percentile(0.5) * (hi - lo);
+    let reach = (median - lo).max(hi - median);
+    let deviations: Vec<f32> = data.iter().map(|&v| (v - median).abs()).collect();
+    let spread = Histogram::of(&deviations, 0.0, reach);
+    (median, spread.percentile(0.5) * reach)
+}
+
+/// The value below which the share `pctile` (e.g. 0.9998) of the finite values of
+/// `data` lie, as a fraction of their `[min, max]` range (0 = min, 1 = max). Read
+/// from a 4096-bin histogram, so it is exact to one bin; 1 when there is no range.
+pub fn percentile_norm(data: &[f32], pctile: f64) -> f32 {
+    let (hist, _, _) = Histogram::with_range(data, None);
+    hist.percentile(pctile)
+}
+
+/// Histogram of the finite values of a plane over `[min, max]`, built once and
+/// shared by the percentile and background estimates.
+struct Histogram {
+    bins: Vec<u64>,
+    /// Number of finite values counted
+    count: u64,
+    /// False when min == max (every estimate then falls back to its default)
+    has_range: bool,
+}
+
+impl Histogram {
+    fn of(data: &[f32], min: f32, max: f32) -> Self {
+        let range = max - min;
+        let mut bins = vec![0u64; HIST_BINS];
+        let mut count = 0u64;
+        if range != 0.0 {
+            for &v in data {
+                if v.is_finite() {
+                    bins[Self::bin(v, min, range)] += 1;
+                    count += 1;
+                }
+            }
+        }
+        Histogram { bins, count, has_range: range != 0.0 }
     }
-    let mut hist = vec![0u64; BINS];
-    let mut count = 0u64;
-    for &v in data {
-        if v.is_finite() {
-            let bin = (((v - min) / range).clamp(0.0, 1.0) * (BINS - 1) as f32) as usize;
-            hist[bin.min(BINS - 1)] += 1;
-            count += 1;
+
+    /// Histogram of `data` together with the range it spans: `range` when given,
+    /// else the min/max of the finite values.
+    ///
+    /// Integer data in 0..=65535 (raw camera frames) takes a single pass: exact
+    /// per-value counts give both the min/max and, re-binned, the histogram. Other
+    /// data needs a separate min/max pass first. On a 24 MP 16-bit sky frame this
+    /// takes ~50 ms against ~190 ms for the min/max plus histogram passes.
+    fn with_range(data: &[f32], range: Option<(f32, f32)>) -> (Self, f32, f32) {
+        if let Some((min, max)) = range {
+            return (Self::of(data, min, max), min, max);
         }
+        let Some(counts) = u16_counts(data) else {
+            let (min, max) = data_min_max(data);
+            return (Self::of(data, min, max), min, max);
+        };
+        let (Some(lo), Some(hi)) =
+            (counts.iter().position(|&c| c > 0), counts.iter().rposition(|&c| c > 0))
+        else {
+            // No data: same fallback range as `data_min_max`
+            return (Self::of(data, 0.0, 1.0), 0.0, 1.0);
+        };
+        let (min, max) = (lo as f32, hi as f32);
+        let range = max - min;
+        let mut bins = vec![0u64; HIST_BINS];
+        if range != 0.0 {
+            for (v, &c) in counts.iter().enumerate().take(hi + 1).skip(lo) {
+                bins[Self::bin(v as f32, min, range)] += c;
+            }
+        }
+        let count = if range != 0.0 { counts.iter().sum() } else { 0 };
+        (Histogram { bins, count, has_range: range != 0.0 }, min, max)
     }
-    if count == 0 {
-        return 1.0;
+
+    fn bin(v: f32, min: f32, range: f32) -> usize {
+        let bin = (((v - min) / range).clamp(0.0, 1.0) * (HIST_BINS - 1) as f32) as usize;
+        bin.min(HIST_BINS - 1)
     }
-    let target = ((count as f64 * pctile).ceil() as u64).min(count);
-    let mut cumsum = 0u64;
-    for (i, &h) in hist.iter().enumerate() {
-        cumsum += h;
-        if cumsum >= target {
-            return i as f32 / (BINS - 1) as f32;
+
+    /// The value at `pctile` (e.g. 0.9999) as a fraction of the [min, max] range
+    /// (so 0.0 = min, 1.0 = max).
+    fn percentile(&self, pctile: f64) -> f32 {
+        if !self.has_range || self.count == 0 {
+            return 1.0;
         }
+        let target = ((self.count as f64 * pctile).ceil() as u64).min(self.count);
+        let mut cumsum = 0u64;
+        for (i, &h) in self.bins.iter().enumerate() {
+            cumsum += h;
+            if cumsum >= target {
+                return i as f32 / (HIST_BINS - 1) as f32;
+            }
+        }
+        1.0
     }
-    1.0
 }
 
 /// Midtone Transfer Function used by Siril/PixInsight.
-/// Maps 0→0, m→0.5, 1→1 with a smooth S-ish curve.
-fn mtf(x: f32, m: f32) -> f32 {
+/// Maps 0→0, m→0.5, 1→1 with a smooth S-ish curve; `x` is clamped to [0, 1].
+pub fn mtf(x: f32, m: f32) -> f32 {
     if x <= 0.0 {
         return 0.0;
     }
@@ -528,15 +2805,43 @@ fn mtf(x: f32, m: f32) -> f32 {
 }
 
 
-fn data_min_max(data: &[f32]) -> (f32, f32) {
-    let mut min = f32::MAX;
-    let mut max = f32::MIN;
+/// Exact count of every value when all of `data` are integers in 0..=65535,
+/// else None (NaNs included).
+fn u16_counts(data: &[f32]) -> Option<Vec<u64>> {
+    let mut counts = vec![0u64; 1 << 16];
     for &v in data {
-        if v.is_finite() {
-            if v < min { min = v; }
-            if v > max { max = v; }
+        let i = v as u16;
+        if i as f32 != v {
+            return None;
+        }
+        counts[i as usize] += 1;
+    }
+    Some(counts)
+}
+
+/// Min/max of the finite values of `data`, or (0, 1) when there are none.
+/// Keeps eight independent running min/max lanes with branch-free selects so
+/// the compiler can vectorise the loop.
+fn data_min_max(data: &[f32]) -> (f32, f32) {
+    const LANES: usize = 8;
+    let mut min = [f32::MAX; LANES];
+    let mut max = [f32::MIN; LANES];
+    let chunks = data.chunks_exact(LANES);
+    let tail = chunks.remainder();
+    for chunk in chunks {
+        for i in 0..LANES {
+            let v = chunk[i];
+            let finite = v.is_finite();
+            min[i] = if finite && v < min[i] { v } else { min[i] };
+            max[i] = if finite && v > max[i] { v } else { max[i] };
         }
     }
+    let mut min = min.into_iter().fold(f32::MAX, f32::min);
+    let mut max = max.into_iter().fold(f32::MIN, f32::max);
+    for &v in tail.iter().filter(|v| v.is_finite()) {
+        min = min.min(v);
+        max = max.max(v);
+    }
     if min > max { (0.0, 1.0) } else { (min, max) }
 }
 
@@ -544,32 +2849,64 @@ fn data_min_max(data: &[f32]) -> (f32, f32) {
 // Header reading
 // ---------------------------------------------------------------------------
 
-/// Read all header records from `hdu_idx` by parsing the raw FITS file.
+/// Keywords of an empty primary HDU that describe its own (absent) data array and
+/// so are not inherited by the image extension of a multi-extension file.
+const PRIMARY_STRUCTURAL_KEYS: &[&str] = &[
+    "SIMPLE", "EXTEND", "BITPIX", "NAXIS", "PCOUNT", "GCOUNT", "BSCALE", "BZERO", "BLANK", "DATAMIN",
+    "DATAMAX", "CHECKSUM", "DATASUM", "EXTNAME", "EXTVER",
+];
+
+/// One HDU as laid out in a FITS file.
+struct RawHdu {
+    /// Header blocks, up to and including the one holding END
+    header: Vec<u8>,
+}
+
+/// Walk the HDUs of `path` by parsing the raw file, stopping after HDU `last`
+/// (None = all of them).
 ///
-/// FITS headers consist of 80-byte ASCII records packed into 2880-byte blocks.
-/// Each record is `KEY     = value / comment` or a commentary card (COMMENT,
-/// HISTORY, blank).  We skip structural/commentary cards and return the rest
-/// sorted alphabetically by key name.
-fn read_headers(fits_path: &Path, hdu_idx: usize) -> Result<Vec<(String, String)>> {
+/// FITS headers consist of 80-byte ASCII records packed into 2880-byte blocks,
+/// each followed by its data padded to whole blocks. A file that ends inside a
+/// header or before an HDU's data is complete gives [`FitsError::Truncated`];
+/// missing final padding and trailing bytes that start no extension are tolerated.
+fn scan_hdus(fits_path: &Path, last: Option<usize>) -> Result<Vec<RawHdu>> {
     use std::io::{BufReader, Read, Seek, SeekFrom};
 
     let file = std::fs::File::open(fits_path)
-        .with_context(|| format!("opening {} for header read", fits_path.display()))?;
+        .map_err(io_err(format!("opening {} for header read", fits_path.display())))?;
+    let file_len = file
+        .metadata()
+        .map_err(io_err(format!("reading size of {}", fits_path.display())))?
+        .len();
+    let truncated = |detail: String| FitsError::Truncated { path: fits_path.display().to_string(), detail };
+    if file_len == 0 {
+        return Err(truncated("empty file".to_string()));
+    }
     let mut reader = BufReader::new(file);
-
-    // Skip over preceding HDUs (each HDU = header blocks + data blocks).
-    // For HDU 0 we start at byte 0.
     let mut block = [0u8; 2880];
-    let mut hdus_seen = 0usize;
-
-    loop {
+    let mut hdus = Vec::new();
+    let mut pos = 0u64;
+
+    while pos < file_len {
+        // Bytes after the last HDU that don't start an extension are ignored
+        if !hdus.is_empty() {
+            let mut magic = [0u8; 8];
+            if reader.read_exact(&mut magic).is_err() || &magic != b"XTENSION" {
+                break;
+            }
+            reader.seek(SeekFrom::Current(-8))
+                .map_err(io_err("seeking in FITS header"))?;
+        }
         // --- Read header blocks for the current HDU ---
-        let mut header_bytes: Vec<u8> = Vec::new();
+        let mut header: Vec<u8> = Vec::new();
         let mut found_end = false;
         while !found_end {
+            if pos + header.len() as u64 + 2880 > file_len {
+                return Err(truncated(format!("header of HDU {} has no END card", hdus.len())));
+            }
             reader.read_exact(&mut block)
-                .context("reading FITS header block")?;
-            header_bytes.extend_from_slice(&block);
+                .map_err(io_err("reading FITS header block"))?;
+            header.extend_from_slice(&block);
             // Scan this block for an END record
             for rec in block.chunks_exact(80) {
                 if rec.starts_with(b"END     ") || rec.starts_with(b"END\x20\x20") || rec == b"END                                                                             " {
@@ -578,74 +2915,203 @@ fn read_headers(fits_path: &Path, hdu_idx: usize) -> Result<Vec<(String, String)
                 }
             }
         }
+        pos += header.len() as u64;
 
-        if hdus_seen == hdu_idx {
-            // Parse and return headers for this HDU
-            let mut headers: Vec<(String, String)> = Vec::new();
-            for rec in header_bytes.chunks_exact(80) {
-                let card = std::str::from_utf8(rec).unwrap_or("").trim_end();
-                if card.len() < 8 {
-                    continue;
-                }
-                let key = card[..8].trim().to_string();
-                // Skip structural/commentary records
-                if key.is_empty()
-                    || key == "COMMENT"
-                    || key == "HISTORY"
-                    || key == "END"
-                    || key == "CONTINUE"
-                {
-                    continue;
-                }
-                // Value is after "= " at position 8–9 (if present)
-                let value = if card.len() > 10 && &card[8..10] == "= " {
-                    let val_str = strip_fits_comment(card[10..].trim()).trim();
-                    // Strip surrounding FITS string quotes and inner trailing spaces
-                    if val_str.starts_with('\'') && val_str.ends_with('\'') && val_str.len() >= 2 {
-                        val_str[1..val_str.len() - 1]
-                            .replace("''", "'")
-                            .trim()
-                            .to_string()
-                    } else {
-                        val_str.to_string()
-                    }
-                } else if card.len() > 8 {
-                    card[8..].trim().to_string()
-                } else {
-                    String::new()
-                };
-                headers.push((key, value));
-            }
-            headers.sort_by(|a, b| a.0.cmp(&b.0));
-            return Ok(headers);
-        }
-
-        hdus_seen += 1;
-
-        // Skip the data blocks for this HDU.
-        // Data size comes from NAXIS + NAXISn + BITPIX keywords.
-        let bitpix = find_header_int(&header_bytes, "BITPIX").unwrap_or(8);
-        let naxis = find_header_int(&header_bytes, "NAXIS").unwrap_or(0);
-        let mut data_size: u64 = if naxis == 0 {
+        // Data size = |BITPIX| × GCOUNT × (PCOUNT + NAXIS1 × … × NAXISn) / 8; PCOUNT
+        // covers the heap of binary tables, e.g. the tiles of a compressed image.
+        let bitpix = find_header_int(&header, "BITPIX").unwrap_or(8);
+        let naxis = find_header_int(&header, "NAXIS").unwrap_or(0);
+        let data_len: u64 = if naxis == 0 {
             0
         } else {
             let bits_per_element = bitpix.unsigned_abs() as u64;
             let mut npix: u64 = 1;
             for i in 1..=naxis {
                 let key = format!("NAXIS{i}");
-                npix *= find_header_int(&header_bytes, &key).unwrap_or(0).max(0) as u64;
+                npix *= find_header_int(&header, &key).unwrap_or(0).max(0) as u64;
             }
-            (npix * bits_per_element + 7) / 8
+            let pcount = find_header_int(&header, "PCOUNT").unwrap_or(0).max(0) as u64;
+            let gcount = find_header_int(&header, "GCOUNT").unwrap_or(1).max(1) as u64;
+            (gcount * (pcount + npix) * bits_per_element).div_ceil(8)
         };
-        // Round up to next 2880-byte boundary
-        if data_size % 2880 != 0 {
-            data_size += 2880 - data_size % 2880;
+        if pos + data_len > file_len {
+            return Err(truncated(format!(
+                "HDU {} needs {data_len} data bytes, {} present",
+                hdus.len(),
+                file_len - pos
+            )));
+        }
+        hdus.push(RawHdu { header });
+        if last.is_some_and(|last| hdus.len() > last) {
+            break;
+        }
+
+        // Skip the data blocks, rounded up to the next 2880-byte boundary
+        let padded = data_len.div_ceil(2880) * 2880;
+        if padded > 0 {
+            reader.seek(SeekFrom::Current(padded as i64))
+                .map_err(io_err("seeking past FITS data block"))?;
+        }
+        pos += padded;
+    }
+    Ok(hdus)
+}
+
+/// Read all header records from `hdu_idx` by parsing the raw FITS file (see
+/// [`scan_hdus`]). Structural and commentary cards are skipped and the rest
+/// sorted alphabetically by key name.
+///
+/// For an extension, the primary header's keywords are merged in, since
+/// multi-extension files often keep OBJECT, DATE-OBS etc. only there; the
+/// extension's value wins when both have a keyword.
+fn read_headers(fits_path: &Path, hdu_idx: usize) -> Result<Vec<(String, String)>> {
+    merged_headers(&scan_hdus(fits_path, Some(hdu_idx))?, hdu_idx)
+}
+
+/// The header records of `hdus[hdu_idx]` as returned by [`read_headers`].
+fn merged_headers(hdus: &[RawHdu], hdu_idx: usize) -> Result<Vec<(String, String)>> {
+    let Some(hdu) = hdus.get(hdu_idx) else {
+        return Err(FitsError::Io {
+            context: format!("reading header of HDU {hdu_idx}"),
+            source: std::io::ErrorKind::UnexpectedEof.into(),
+        });
+    };
+    let mut headers = own_headers(hdu);
+    if hdu_idx > 0 {
+        let mut primary = parse_header_cards(&hdus[0].header);
+        primary.retain(|(k, _)| !PRIMARY_STRUCTURAL_KEYS.contains(&k.as_str()) && !k.starts_with("NAXIS"));
+        for (key, value) in primary {
+            if header_value(&headers, &key).is_none() {
+                headers.push((key, value));
+            }
+        }
+    }
+    headers.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(headers)
+}
+
+/// The keywords of one HDU's own header, those of a compressed image presented
+/// as the image's (see [`compressed_image_headers`]); unsorted.
+fn own_headers(hdu: &RawHdu) -> Vec<(String, String)> {
+    let headers = parse_header_cards(&hdu.header);
+    if header_value(&headers, "ZIMAGE") == Some("T") {
+        compressed_image_headers(headers)
+    } else {
+        headers
+    }
+}
+
+/// Colour images written as one single-plane image HDU per channel rather than
+/// a 3-plane cube: the indices of the R, G and B HDUs, given the headers of
+/// every HDU. The planes are the HDUs named R / G / B (or RED / GREEN / BLUE) by
+/// their EXTNAME or, without one, their FILTER keyword; other same-size images
+/// are not taken for colours. All three must have the same size.
+fn channel_hdus(headers: &[Vec<(String, String)>]) -> Option<[usize; 3]> {
+    const NAMES: [[&str; 2]; 3] = [["R", "RED"], ["G", "GREEN"], ["B", "BLUE"]];
+    // Non-empty images: (HDU, [NAXIS1, NAXIS2, planes], EXTNAME or FILTER)
+    let images: Vec<(usize, [usize; 3], Option<&str>)> = headers
+        .iter()
+        .enumerate()
+        .filter(|(_, h)| header_value(h, "XTENSION").is_none_or(|x| x == "IMAGE"))
+        .filter_map(|(i, h)| {
+            let int = |key| header_value(h, key).and_then(|v| v.parse::<usize>().ok());
+            let shape = match int("NAXIS")? {
+                2 => [int("NAXIS1")?, int("NAXIS2")?, 1],
+                3 => [int("NAXIS1")?, int("NAXIS2")?, int("NAXIS3")?],
+                _ => return None,
+            };
+            let name = header_value(h, "EXTNAME").or_else(|| header_value(h, "FILTER"));
+            (!shape.contains(&0)).then_some((i, shape, name))
+        })
+        .collect();
+    let named = NAMES.map(|names| {
+        images.iter().find(|(_, _, name)| name.is_some_and(|x| names.iter().any(|n| x.eq_ignore_ascii_case(n))))
+    });
+    let [Some(r), Some(g), Some(b)] = named else { return None };
+    let planes = [r, g, b];
+    let [width, height, _] = planes[0].1;
+    planes.iter().all(|p| p.1 == [width, height, 1]).then(|| planes.map(|p| p.0))
+}
+
+/// Parse the keyword records of one raw header (a whole number of 80-byte cards),
+/// skipping commentary and END cards; unsorted.
+fn parse_header_cards(header_bytes: &[u8]) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for rec in header_bytes.chunks_exact(80) {
+        let card = std::str::from_utf8(rec).unwrap_or("").trim_end();
+        if card.len() < 8 {
+            continue;
         }
-        if data_size > 0 {
-            reader.seek(SeekFrom::Current(data_size as i64))
-                .context("seeking past FITS data block")?;
+        let key = card[..8].trim().to_string();
+        // Skip structural/commentary records
+        if key.is_empty()
+            || key == "COMMENT"
+            || key == "HISTORY"
+            || key == "END"
+            || key == "CONTINUE"
+        {
+            continue;
         }
+        // Value is after "= " at position 8–9 (if present)
+        let value = if card.len() > 10 && &card[8..10] == "= " {
+            let val_str = strip_fits_comment(card[10..].trim()).trim();
+            // Strip surrounding FITS string quotes and inner trailing spaces
+            if val_str.starts_with('\'') && val_str.ends_with('\'') && val_str.len() >= 2 {
+                val_str[1..val_str.len() - 1]
+                    .replace("''", "'")
+                    .trim()
+                    .to_string()
+            } else {
+                val_str.to_string()
+            }
+        } else if card.len() > 8 {
+            card[8..].trim().to_string()
+        } else {
+            String::new()
+        };
+        headers.push((key, value));
     }
+    headers
+}
+
+/// Present the headers of a tile-compressed image (a `ZIMAGE = T` binary table) as
+/// those of the image it holds, the way cfitsio's image interface sees it:
+/// `ZBITPIX`/`ZNAXISn`/… replace the table's structural keywords, and the keywords
+/// that only describe the compression are dropped.
+fn compressed_image_headers(headers: Vec<(String, String)>) -> Vec<(String, String)> {
+    const RENAMED: [(&str, &str); 6] = [
+        ("ZSIMPLE", "SIMPLE"),
+        ("ZTENSION", "XTENSION"),
+        ("ZBITPIX", "BITPIX"),
+        ("ZEXTEND", "EXTEND"),
+        ("ZPCOUNT", "PCOUNT"),
+        ("ZGCOUNT", "GCOUNT"),
+    ];
+    const TABLE_KEYS: [&str; 6] = ["XTENSION", "BITPIX", "PCOUNT", "GCOUNT", "TFIELDS", "THEAP"];
+    const COLUMN_PREFIXES: [&str; 8] =
+        ["TTYPE", "TFORM", "TUNIT", "TSCAL", "TZERO", "TNULL", "TDISP", "TDIM"];
+    const COMPRESSION_PREFIXES: [&str; 4] = ["ZTILE", "ZNAME", "ZVAL", "ZQUANT"];
+    let is_numbered = |key: &str, prefix: &str| {
+        key.strip_prefix(prefix).is_some_and(|n| n.chars().all(|c| c.is_ascii_digit()))
+    };
+
+    headers
+        .into_iter()
+        .filter_map(|(key, value)| {
+            if let Some((_, orig)) = RENAMED.iter().find(|(z, _)| *z == key) {
+                return Some((orig.to_string(), value));
+            }
+            if let Some(n) = key.strip_prefix("ZNAXIS") {
+                return Some((format!("NAXIS{n}"), value));
+            }
+            let table_only = TABLE_KEYS.contains(&key.as_str())
+                || key.starts_with("NAXIS")
+                || COLUMN_PREFIXES.iter().any(|p| is_numbered(&key, p))
+                || COMPRESSION_PREFIXES.iter().any(|p| key.starts_with(p))
+                || matches!(key.as_str(), "ZIMAGE" | "ZCMPTYPE" | "ZDITHER0");
+            (!table_only).then_some((key, value))
+        })
+        .collect()
 }
 
 /// Remove the ` / comment` part from a FITS value field, respecting quoted strings.
@@ -692,3 +3158,908 @@ fn find_header_int(header_bytes: &[u8], key: &str) -> Option<i64> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Two GZIP_1 tile-compressed int16 images (64×48 and 32×16) behind an empty
+    /// primary HDU; pixel (x, y) holds `(37x + 101y) % 4000 + 100`.
+    fn compressed_sample() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/gzip_tiles.fits.fz")
+    }
+
+    fn sample_pixel(x: usize, y: usize) -> f32 {
+        ((x * 37 + y * 101) % 4000 + 100) as f32
+    }
+
+    /// Multi-extension file whose empty primary HDU holds OBJECT and DATE-OBS,
+    /// with the image (EXTNAME = SCI) in extension 1.
+    fn mef_sample() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/mef_primary_meta.fits")
+    }
+
+    /// 16×8 primary image with a `DQ` extension flagging (3, 2), (10, 5) and (15, 7).
+    fn dq_sample() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/science_dq.fits")
+    }
+
+    /// 16×16 RGGB mosaic stored as BITPIX=16 with BZERO=32768; every photosite
+    /// reads 40000, i.e. above the signed 16-bit range.
+    fn unsigned_bayer_sample() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/bayer_bzero32768.fits")
+    }
+
+    fn headers(cards: &[(&str, &str)]) -> Vec<(String, String)> {
+        cards.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn compressed_headers_describe_the_image() {
+        let headers = read_headers(&compressed_sample(), 1).unwrap();
+        assert_eq!(header_value(&headers, "BITPIX"), Some("16"));
+        assert_eq!(header_value(&headers, "NAXIS"), Some("2"));
+        assert_eq!(header_value(&headers, "NAXIS1"), Some("64"));
+        assert_eq!(header_value(&headers, "NAXIS2"), Some("48"));
+        assert_eq!(header_value(&headers, "OBJECT"), Some("synthetic"));
+        for key in ["ZIMAGE", "ZBITPIX", "ZTILE1", "ZCMPTYPE", "TFORM1", "TFIELDS", "PCOUNT"] {
+            assert_eq!(header_value(&headers, key), None, "{key} should be hidden");
+        }
+    }
+
+    #[test]
+    fn truncated_files_are_reported() {
+        // A 16 × 16 16-bit image cut off in the middle of its pixels
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/truncated.fits");
+        let err = read_headers(&path, 0).unwrap_err();
+        assert!(matches!(&err, FitsError::Truncated { detail, .. } if detail == "HDU 0 needs 512 data bytes, 300 present"), "{err}");
+        let Err(err) = FitsImage::load(&path, LoadOptions::default()) else { panic!("loaded a truncated file") };
+        assert!(err.to_string().contains("file appears truncated"), "{err}");
+
+        // Cut off inside a header, and an empty file
+        let dir = std::env::temp_dir().join(format!("fastfits-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let bytes = std::fs::read(mef_sample()).unwrap();
+        let cut = dir.join("cut_header.fits");
+        std::fs::write(&cut, &bytes[..4000]).unwrap();
+        let err = read_headers(&cut, 1).unwrap_err();
+        assert!(matches!(&err, FitsError::Truncated { detail, .. } if detail == "header of HDU 1 has no END card"), "{err}");
+        let empty = dir.join("empty.fits");
+        std::fs::write(&empty, b"").unwrap();
+        assert!(matches!(read_headers(&empty, 0), Err(FitsError::Truncated { .. })));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_headers_skips_compressed_heap() {
+        // Reaching the second extension requires skipping the first one's tile heap.
+        let headers = read_headers(&compressed_sample(), 2).unwrap();
+        assert_eq!(header_value(&headers, "EXTNAME"), Some("SECOND"));
+        assert_eq!(header_value(&headers, "NAXIS1"), Some("32"));
+        assert_eq!(header_value(&headers, "NAXIS2"), Some("16"));
+    }
+
+    #[test]
+    fn extension_headers_inherit_primary_keywords() {
+        // Empty primary (NAXIS = 0) with the capture metadata, image in extension 1
+        let headers = read_headers(&mef_sample(), 1).unwrap();
+        assert_eq!(header_value(&headers, "OBJECT"), Some("NGC 7293"));
+        assert_eq!(header_value(&headers, "DATE-OBS"), Some("2025-09-14T21:03:00"));
+        assert_eq!(header_value(&headers, "EXTNAME"), Some("SCI"));
+        // The extension wins on conflicts and keeps its own structure
+        assert_eq!(header_value(&headers, "FILTER"), Some("Ha"));
+        assert_eq!(header_value(&headers, "NAXIS"), Some("2"));
+        assert_eq!(headers.iter().filter(|(k, _)| k == "FILTER").count(), 1);
+        for key in ["SIMPLE", "EXTEND", "BZERO"] {
+            assert_eq!(header_value(&headers, key), None, "{key} describes the primary's data");
+        }
+        assert!(headers.windows(2).all(|w| w[0].0 <= w[1].0));
+        // The primary header alone is unchanged
+        let primary = read_headers(&mef_sample(), 0).unwrap();
+        assert_eq!(header_value(&primary, "FILTER"), Some("L"));
+        assert_eq!(header_value(&primary, "EXTNAME"), None);
+    }
+
+    #[test]
+    fn frame_types_from_headers() {
+        let light = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/M_31_Light_023.fits");
+        assert_eq!(FrameType::of(&read_image_headers(&light).unwrap()), FrameType::Light);
+        // Compressed: the image header comes from the first extension
+        let compressed = read_image_headers(&compressed_sample()).unwrap();
+        assert_eq!(header_value(&compressed, "OBJECT"), Some("synthetic"));
+        assert_eq!(FrameType::of(&compressed), FrameType::Unknown);
+        let mef = read_image_headers(&mef_sample()).unwrap();
+        assert_eq!(header_value(&mef, "FILTER"), Some("Ha"));
+
+        for (value, kind) in [
+            ("Light Frame", FrameType::Light),
+            ("OBJECT", FrameType::Light),
+            ("Dark Frame", FrameType::Dark),
+            ("DARKFLAT", FrameType::Dark),
+            ("Flat Field", FrameType::Flat),
+            ("BIAS", FrameType::Bias),
+            ("zero", FrameType::Bias),
+            ("Tricolor", FrameType::Unknown),
+        ] {
+            assert_eq!(FrameType::classify(value), kind, "{value}");
+        }
+        assert_eq!(FrameType::of(&headers(&[("FRAME", "Flat")])), FrameType::Flat);
+        assert_eq!(FrameType::of(&headers(&[("IMAGETYP", "Dark"), ("FRAME", "Light")])), FrameType::Dark);
+    }
+
+    #[test]
+    fn load_mef_reports_the_extension_shown() {
+        let img = FitsImage::load(&mef_sample(), LoadOptions::default()).unwrap();
+        assert_eq!((img.hdu, img.hdu_count, img.extname()), (1, 2, Some("SCI")));
+        assert_eq!((img.width, img.height), (6, 4));
+        assert!(img.summary().ends_with(", HDU 1 (SCI) of 2"), "{}", img.summary());
+    }
+
+    fn rgb_extensions_sample() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/rgb_extensions.fits")
+    }
+
+    #[test]
+    fn channel_hdus_are_detected() {
+        let image = |name: Option<&str>, w: &str, h: &str| {
+            let mut cards = vec![("XTENSION", "IMAGE"), ("NAXIS", "2"), ("NAXIS1", w), ("NAXIS2", h)];
+            cards.extend(name.map(|n| ("EXTNAME", n)));
+            headers(&cards)
+        };
+        let empty = headers(&[("SIMPLE", "T"), ("NAXIS", "0")]);
+        let sample: Vec<_> = scan_hdus(&rgb_extensions_sample(), None).unwrap().iter().map(own_headers).collect();
+        assert_eq!(channel_hdus(&sample), Some([1, 2, 3]));
+        // Named planes in any order
+        let named = [empty.clone(), image(Some("Blue"), "8", "6"), image(Some("red"), "8", "6"), image(Some("G"), "8", "6")];
+        assert_eq!(channel_hdus(&named), Some([2, 3, 1]));
+        // Unnamed images of one size are not colours without a FILTER of each
+        let unnamed = [image(None, "8", "6"), image(None, "8", "6"), image(None, "8", "6")];
+        assert_eq!(channel_hdus(&unnamed), None);
+        let filter = |f: &str| {
+            let mut h = image(None, "8", "6");
+            h.push(("FILTER".to_string(), f.to_string()));
+            h
+        };
+        assert_eq!(channel_hdus(&[filter("Green"), filter("Red"), filter("Blue")]), Some([1, 0, 2]));
+        // Science, error and quality planes are not colours
+        let mef = [empty.clone(), image(Some("SCI"), "8", "6"), image(Some("ERR"), "8", "6"), image(Some("DQ"), "8", "6")];
+        assert_eq!(channel_hdus(&mef), None);
+        let sizes = [image(Some("R"), "8", "6"), image(Some("G"), "8", "6"), image(Some("B"), "4", "3")];
+        assert_eq!(channel_hdus(&sizes), None);
+        assert_eq!(channel_hdus(&unnamed[..2]), None);
+        let table = headers(&[("XTENSION", "BINTABLE"), ("NAXIS", "2"), ("NAXIS1", "8"), ("NAXIS2", "6")]);
+        assert_eq!(channel_hdus(&[empty, image(None, "8", "6"), image(None, "8", "6"), table]), None);
+    }
+
+    #[test]
+    fn load_channel_hdus_as_colour() {
+        let img = FitsImage::load(&rgb_extensions_sample(), LoadOptions::default()).unwrap();
+        assert_eq!((img.width, img.height, img.channels), (4, 3, 3));
+        assert_eq!((img.hdu, img.channel_hdus), (1, Some([1, 2, 3])));
+        assert!(img.summary().ends_with(", HDUs 1, 2, 3 as RGB of 4"), "{}", img.summary());
+        for (c, base) in [1000.0, 2000.0, 3000.0].into_iter().enumerate() {
+            assert_eq!(img.plane(c)[0], base);
+            assert_eq!(img.plane(c)[11], base + 11.0);
+        }
+        assert_eq!(img.header("OBJECT"), Some("M 42"));
+    }
+
+    #[test]
+    fn load_compressed_image() {
+        let img = FitsImage::load(&compressed_sample(), LoadOptions::default()).unwrap();
+        assert_eq!((img.width, img.height, img.channels), (64, 48, 1));
+        assert_eq!(img.bitdepth_max, 65535.0);
+        for (x, y) in [(0, 0), (63, 0), (10, 20), (63, 47)] {
+            assert_eq!(img.data[y * img.width + x], sample_pixel(x, y), "pixel ({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn data_format_describes_scaling() {
+        let format = |cards: &[(&str, &str)]| {
+            FitsImage { headers: headers(cards), ..test_image(1, 1, 1, vec![0.0]) }.data_format()
+        };
+        let unsigned = format(&[("BITPIX", "16"), ("BSCALE", "1"), ("BZERO", "32768")]);
+        assert_eq!(unsigned.storage(), "16-bit signed integer");
+        assert_eq!(unsigned.physical(), "unsigned 16-bit (BZERO offset)");
+        assert!(unsigned.scaled());
+        let float = format(&[("BITPIX", "-32")]);
+        assert_eq!(float.storage(), "32-bit IEEE float");
+        assert_eq!(float.physical(), "as stored");
+        assert!(!float.scaled());
+        let scaled = format(&[("BITPIX", "16"), ("BSCALE", "0.5"), ("BZERO", "100")]);
+        assert_eq!(scaled.physical(), "stored × 0.5 + 100");
+    }
+
+    #[test]
+    fn pixel_scale_from_headers() {
+        let scale = |cards: &[(&str, &str)]| {
+            FitsImage { headers: headers(cards), ..test_image(1, 1, 1, vec![0.0]) }.pixel_scale()
+        };
+        let optics = scale(&[("FOCALLEN", "1000"), ("XPIXSZ", "3.76")]).unwrap();
+        assert!((optics - 0.7755).abs() < 1e-3, "{optics}");
+        assert_eq!(scale(&[("PIXSCALE", "1.5"), ("FOCALLEN", "1000"), ("XPIXSZ", "3.76")]), Some(1.5));
+        let solved = scale(&[
+            ("CTYPE1", "RA---TAN"),
+            ("CRPIX1", "1"),
+            ("CRPIX2", "1"),
+            ("CRVAL1", "10"),
+            ("CRVAL2", "20"),
+            ("CDELT1", "-0.0005"),
+            ("CDELT2", "0.0005"),
+        ])
+        .unwrap();
+        assert!((solved - 1.8).abs() < 1e-9, "{solved}");
+        assert_eq!(scale(&[("FOCALLEN", "1000")]), None);
+        assert_eq!(scale(&[("FOCALLEN", "0"), ("XPIXSZ", "3.76")]), None);
+    }
+
+    #[test]
+    fn mosaic_to_u16_keeps_unsigned_range() {
+        assert_eq!(mosaic_to_u16(&[0.0, 40000.0, 65535.0]), vec![0, 40000, 65535]);
+    }
+
+    #[test]
+    fn mosaic_to_u16_keeps_signed_data() {
+        // 12- or 14-bit data written as signed BITPIX=16 passes through unchanged
+        assert_eq!(mosaic_to_u16(&[0.0, 100.0, 16383.0]), vec![0, 100, 16383]);
+        // Negative values clip to black rather than shifting the frame
+        assert_eq!(mosaic_to_u16(&[-5.0, 100.4]), vec![0, 100]);
+    }
+
+    #[test]
+    fn load_unsigned_bayer_is_not_clipped() {
+        let img = FitsImage::load(&unsigned_bayer_sample(), LoadOptions::default()).unwrap();
+        assert!(img.is_bayer);
+        assert_eq!((img.width, img.height, img.channels), (16, 16, 3));
+        // A flat mosaic debayers to a flat image; clipping would show up as zeros.
+        assert!(img.data.iter().all(|&v| (v - 40000.0).abs() < 1.0));
+    }
+
+    #[test]
+    fn load_8bit_bayer_keeps_its_range() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/bayer_8bit_rggb.fits");
+        let img = FitsImage::load(&path, LoadOptions::default()).unwrap();
+        assert!(img.is_bayer);
+        assert_eq!((img.width, img.height, img.channels, img.bitdepth_max), (8, 8, 3, 255.0));
+        // R = 200, G = 100, B = 50 on every site of the mosaic: a flat colour once debayered
+        for (c, expected) in [200.0, 100.0, 50.0].into_iter().enumerate() {
+            let plane = img.plane(c);
+            for y in 2..6 {
+                for x in 2..6 {
+                    assert!((plane[y * 8 + x] - expected).abs() <= 1.0, "plane {c} at ({x}, {y}): {}", plane[y * 8 + x]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn srgb_output_brightens_midtones_only() {
+        assert_eq!(srgb_encode(0.0), 0.0);
+        assert!((srgb_encode(1.0) - 1.0).abs() < 1e-6);
+        assert!((srgb_encode(0.18) - 0.4614).abs() < 1e-3);
+        let img = star_field(16, 16, &[(8.0, 8.0, 1.5, 5000.0)]);
+        let plain = img.to_rgba(Stretch::Linear, ChannelView::Single(0), ColorOptions::default());
+        let srgb = img.to_rgba(Stretch::Linear, ChannelView::Single(0), ColorOptions { srgb: true, ..Default::default() });
+        assert!(plain.iter().zip(&srgb).all(|(a, b)| b >= a));
+        assert!(plain.iter().zip(&srgb).any(|(a, b)| b > a));
+        let ramp = linear_ramp(true);
+        assert_eq!((ramp[0], ramp[LUT_SIZE - 1]), (0, 255), "black and white stay put");
+        // Encoded before rounding: a level just above black is not lost to 0
+        assert_eq!(linear_ramp(false)[4], 0);
+        assert_eq!(ramp[4], 3);
+    }
+
+    #[test]
+    fn colour_axis_detection() {
+        let plain = headers(&[]);
+        assert!(channels_first(&[3, 640, 480], &plain, ColorAxis::Auto));
+        assert!(!channels_first(&[640, 480, 3], &plain, ColorAxis::Auto));
+        assert!(!channels_first(&[3, 3, 3], &plain, ColorAxis::Auto));
+        let ctype3 = headers(&[("CTYPE3", "RGB")]);
+        assert!(!channels_first(&[3, 640, 480], &ctype3, ColorAxis::Auto));
+        let ctype1 = headers(&[("CTYPE1", "COLOR")]);
+        assert!(channels_first(&[3, 3, 3], &ctype1, ColorAxis::Auto));
+        // The preference overrides both
+        assert!(channels_first(&[640, 480, 3], &plain, ColorAxis::First));
+        assert!(!channels_first(&[3, 640, 480], &ctype1, ColorAxis::Last));
+        assert_eq!(deinterleave(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0], 3), vec![1.0, 4.0, 2.0, 5.0, 3.0, 6.0]);
+    }
+
+    #[test]
+    fn load_channels_first_cube() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/rgb_channels_first.fits");
+        let img = FitsImage::load(&path, LoadOptions::default()).unwrap();
+        assert_eq!((img.width, img.height, img.channels), (5, 4, 3));
+        // Pixel (x, y) holds R = 100 + i, G = 200 + i, B = 300 + i for i = y * 5 + x
+        for (c, base) in [100.0, 200.0, 300.0].into_iter().enumerate() {
+            assert_eq!(img.plane(c)[0], base);
+            assert_eq!(img.plane(c)[19], base + 19.0);
+        }
+        let planar = FitsImage::load(&path, LoadOptions { color_axis: ColorAxis::Last, ..LoadOptions::default() }).unwrap();
+        assert_eq!((planar.width, planar.height, planar.channels), (3, 5, 4));
+    }
+
+    #[test]
+    fn spectrum_axis_from_headers() {
+        let mut img = star_field(4, 1, &[]);
+        img.headers = headers(&[("CTYPE1", "WAVE"), ("CUNIT1", "nm"), ("CRVAL1", "650"), ("CRPIX1", "2"), ("CDELT1", "0.5")]);
+        let spectrum = img.spectrum().unwrap();
+        assert_eq!(spectrum.wavelength, [649.5, 650.0, 650.5, 651.0]);
+        assert_eq!(spectrum.axis, "WAVE (nm)");
+        assert_eq!(spectrum.flux, img.data);
+        // A column uses axis 2; without a WCS the samples are numbered
+        let column = star_field(1, 3, &[]).spectrum().unwrap();
+        assert_eq!((column.wavelength, column.axis.as_str()), (vec![1.0, 2.0, 3.0], "pixel"));
+        assert!(star_image().spectrum().is_none());
+    }
+
+    #[test]
+    fn load_1d_spectrum() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/spectrum_1d.fits");
+        let img = FitsImage::load(&path, LoadOptions::default()).unwrap();
+        assert_eq!((img.width, img.height, img.channels), (50, 1, 1));
+        let spectrum = img.spectrum().unwrap();
+        assert_eq!((spectrum.wavelength[0], spectrum.wavelength[49]), (6500.0, 6598.0));
+        assert_eq!(spectrum.axis, "WAVE (Angstrom)");
+        // Flux 100 + i with an emission line at sample 20
+        assert_eq!((spectrum.flux[0], spectrum.flux[20]), (100.0, 620.0));
+    }
+
+    #[test]
+    fn pedestal_magnitude_is_subtracted() {
+        assert_eq!(pedestal(&headers(&[("PEDESTAL", "100")])), Some(100.0));
+        assert_eq!(pedestal(&headers(&[("PEDESTAL", "-100")])), Some(100.0));
+        assert_eq!(pedestal(&headers(&[("PEDESTAL", "0")])), None);
+        assert_eq!(pedestal(&headers(&[])), None);
+        assert_eq!(subtract_pedestal(vec![150.0, 100.0, 90.0], Some(100.0)), [50.0, 0.0, -10.0]);
+        assert_eq!(subtract_pedestal(vec![150.0], None), [150.0]);
+    }
+
+    #[test]
+    fn load_subtracts_pedestal() {
+        // 8 × 8, values 150 + i, PEDESTAL = -100 (MaxIm DL convention)
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/pedestal.fits");
+        let img = FitsImage::load(&path, LoadOptions::default()).unwrap();
+        assert_eq!(img.pedestal, Some(100.0));
+        assert_eq!((img.data[0], img.data[63]), (50.0, 113.0));
+        assert_eq!(img.pixel(1, 0), Some(vec![51.0]));
+        // Full scale moves down with the data
+        assert_eq!(img.bitdepth_max, 65435.0);
+    }
+
+    #[test]
+    fn only_unscaled_bytes_are_8bit_mosaics() {
+        assert!(is_8bit_mosaic(&headers(&[("BITPIX", "8")])));
+        assert!(!is_8bit_mosaic(&headers(&[("BITPIX", "8"), ("BZERO", "-128")])));
+        assert!(!is_8bit_mosaic(&headers(&[("BITPIX", "16"), ("BZERO", "32768")])));
+    }
+
+    #[test]
+    fn load_mask_by_extname_and_index() {
+        for hdu in ["", "dq", "1"] {
+            let mask = Mask::load(&dq_sample(), hdu).unwrap();
+            assert_eq!((mask.width, mask.height), (16, 8));
+            assert_eq!(mask.count(), 3);
+            assert!(mask.flagged[2 * 16 + 3] && mask.flagged[5 * 16 + 10] && mask.flagged[7 * 16 + 15]);
+        }
+        assert!(Mask::load(&dq_sample(), "MASK").is_err());
+    }
+
+    /// Deterministic 256×256 sky: ~1000 ADU background with noise, a gradient,
+    /// a few saturated stars and some NaNs.
+    fn synthetic_sky() -> Vec<f32> {
+        let mut seed = 12345u32;
+        let mut rand = move || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (seed >> 16) as f32 / 65536.0
+        };
+        (0..256 * 256)
+            .map(|i| {
+                let (x, y) = (i % 256, i / 256);
+                let noise: f32 = (0..4).map(|_| rand()).sum::<f32>() - 2.0;
+                match (x * 7 + y * 13) % 4999 {
+                    0 => 65535.0,
+                    1 => f32::NAN,
+                    _ => 1000.0 + x as f32 * 0.5 + noise * 40.0,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn stretch_luts_are_unchanged() {
+        let data = synthetic_sky();
+        let (hist, min, max) = Histogram::with_range(&data, None);
+        let stf = auto_stf(&hist, min, max, 65535.0, DEFAULT_WHITE_CLIP, DEFAULT_BACKGROUND);
+        // Values produced by the separate-pass implementation this replaced
+        assert_eq!(
+            stf,
+            Stf { shadows: 0.001953602, midtones: 0.017402219, highlights: 0.0036630041 }
+        );
+        let checksum = |lut: Vec<u8>| {
+            lut.iter().enumerate().map(|(i, &v)| (i as u64 + 1) * v as u64).sum::<u64>()
+        };
+        assert_eq!(checksum(autostretch_lut(&data, 65535.0, DEFAULT_WHITE_CLIP).0), 2139587600);
+        assert_eq!(checksum(percentile_lut(&hist, 0.005, 0.995, false)), 2139605480);
+    }
+
+    #[test]
+    fn looser_white_clip_raises_the_white_point() {
+        let data = synthetic_sky();
+        let (hist, min, max) = Histogram::with_range(&data, None);
+        let default = auto_stf(&hist, min, max, 65535.0, DEFAULT_WHITE_CLIP, DEFAULT_BACKGROUND);
+        let loose = auto_stf(&hist, min, max, 65535.0, 1.0, DEFAULT_BACKGROUND);
+        assert!(loose.highlights > default.highlights);
+        assert_eq!((loose.shadows, loose.midtones), (default.shadows, default.midtones));
+    }
+
+    #[test]
+    fn short_data_renders_as_missing_pixels() {
+        assert!(check_data_size(0, 12, 12).is_ok());
+        assert!(matches!(
+            check_data_size(1, 10, 12),
+            Err(FitsError::DataSize { hdu: 1, expected: 12, actual: 10 })
+        ));
+        // Header says 4×3×3, only one and a half planes present
+        let mut img = star_field(4, 3, &[]);
+        img.channels = 3;
+        img.data.extend([100.0; 6]);
+        for view in [ChannelView::Rgb, ChannelView::Single(0), ChannelView::Single(2)] {
+            let rgba = img.to_rgba(Stretch::Linear, view, ColorOptions::default());
+            assert_eq!(rgba.len(), 4 * 3 * 4);
+            // AutoStretch as the viewer renders it: STF first, then applied
+            let stf = img.auto_stf(view, DEFAULT_WHITE_CLIP, ColorOptions::default());
+            let rgba = img.to_rgba_stf(view, &stf, ColorOptions::default());
+            assert_eq!(rgba.len(), 4 * 3 * 4);
+        }
+        let rgba = img.to_rgba(Stretch::Linear, ChannelView::Single(2), ColorOptions::default());
+        assert_eq!(rgba[..3], NON_FINITE_RGB);
+    }
+
+    #[test]
+    fn mtf_fixed_points() {
+        for m in [0.01, 0.1, 0.25, 0.5, 0.75, 0.9, 0.99] {
+            assert_eq!(mtf(0.0, m), 0.0);
+            assert_eq!(mtf(1.0, m), 1.0);
+            assert!((mtf(m, m) - 0.5).abs() < 1e-6, "m = {m}");
+            // Monotonic in between
+            assert!(mtf(0.3, m) < mtf(0.6, m));
+        }
+        assert_eq!((mtf(-1.0, 0.5), mtf(2.0, 0.5)), (0.0, 1.0));
+    }
+
+    #[test]
+    fn histogram_median_and_percentile_match_sorting() {
+        let sky = synthetic_sky();
+        let mut sorted: Vec<f32> = sky.iter().copied().filter(|v| v.is_finite()).collect();
+        sorted.sort_by(f32::total_cmp);
+        let (min, max) = (sorted[0], sorted[sorted.len() - 1]);
+        let bin = (max - min) / (HIST_BINS - 1) as f32;
+        let (median, mad) = median_mad(&sky);
+        assert!((median - sorted[sorted.len() / 2]).abs() <= bin, "median {median}");
+        let mut deviations: Vec<f32> = sorted.iter().map(|v| (v - median).abs()).collect();
+        deviations.sort_by(f32::total_cmp);
+        assert!((mad - deviations[deviations.len() / 2]).abs() <= bin, "MAD {mad}");
+        for pctile in [0.1, 0.5, 0.9, 0.999] {
+            let reference = sorted[((sorted.len() as f64 * pctile).ceil() as usize).min(sorted.len()) - 1];
+            let value = min + percentile_norm(&sky, pctile) * (max - min);
+            assert!((value - reference).abs() <= bin, "{pctile}: {value} vs {reference}");
+        }
+    }
+
+    #[test]
+    fn stretch_plane_matches_the_grey_rendering() {
+        let sky = synthetic_sky();
+        for stretch in [Stretch::AUTO, Stretch::Linear, Stretch::Log { k: 100.0 }] {
+            let bytes = stretch_plane(&sky, stretch, 65535.0);
+            let rgba = to_rgba_gray(&sky, stretch, 65535.0, None, ColorOptions::default());
+            for (i, (&b, v)) in bytes.iter().zip(&sky).enumerate() {
+                let expected = if v.is_finite() { rgba[i * 4] } else { 0 };
+                assert_eq!(b, expected, "{stretch:?} at {i}");
+            }
+        }
+        let (lut, range) = linear_lut(&sky);
+        assert_eq!(lut.len(), LUT_SIZE);
+        assert_eq!((lut[0], lut[LUT_SIZE - 1]), (0, 255));
+        assert_eq!(range, data_min_max(&sky));
+    }
+
+    #[test]
+    fn background_targets_tint_the_sky() {
+        // Without NaNs, which are painted in a fixed colour
+        let sky: Vec<f32> = synthetic_sky().into_iter().map(|v| if v.is_nan() { 1000.0 } else { v }).collect();
+        let mean = |rgba: &[u8], c: usize| rgba.chunks_exact(4).map(|p| p[c] as f64).sum::<f64>() / sky.len() as f64;
+        let neutral = to_rgba_rgb(&sky, &sky, &sky, Stretch::AUTO, 65535.0, None, ColorOptions::default());
+        assert_eq!(mean(&neutral, 0), mean(&neutral, 2));
+        let warm = ColorOptions { background: [0.25, 0.20, 0.15], ..ColorOptions::default() };
+        let rgba = to_rgba_rgb(&sky, &sky, &sky, Stretch::AUTO, 65535.0, None, warm);
+        assert!(mean(&rgba, 0) > mean(&rgba, 1) && mean(&rgba, 1) > mean(&rgba, 2));
+        assert_eq!(mean(&rgba, 1), mean(&neutral, 1));
+    }
+
+    #[test]
+    fn single_pass_histogram_matches_two_pass() {
+        let data: Vec<f32> = synthetic_sky().iter().map(|v| if v.is_finite() { v.round() } else { 0.0 }).collect();
+        assert!(u16_counts(&data).is_some());
+        let (hist, min, max) = Histogram::with_range(&data, None);
+        assert_eq!((min, max), data_min_max(&data));
+        let reference = Histogram::of(&data, min, max);
+        assert_eq!(hist.bins, reference.bins);
+        assert_eq!(hist.count, reference.count);
+
+        assert!(u16_counts(&[1.0, 2.5]).is_none());
+        assert!(u16_counts(&[1.0, f32::NAN]).is_none());
+        assert!(u16_counts(&[1.0, 70000.0]).is_none());
+    }
+
+    #[test]
+    fn non_finite_pixels_get_sentinel_colour() {
+        let plane = [0.0, 1.0, f32::NAN, 2.0, f32::INFINITY];
+        let rgba = to_rgba_gray(&plane, Stretch::Linear, 0.0, None, ColorOptions::default());
+        let px = |i: usize| &rgba[i * 4..i * 4 + 3];
+        assert_eq!(px(0), [0, 0, 0]);
+        assert_eq!(px(2), NON_FINITE_RGB);
+        assert_eq!(px(3), [255, 255, 255]);
+        assert_eq!(px(4), NON_FINITE_RGB);
+
+        // A flat plane (max == min) must not hide the NaN either
+        let flat = [5.0, f32::NAN, 5.0];
+        let rgba = to_rgba_gray(&flat, Stretch::Linear, 0.0, None, ColorOptions::default());
+        assert_eq!(&rgba[4..7], NON_FINITE_RGB);
+        assert_ne!(&rgba[..3], NON_FINITE_RGB);
+
+        let (r, g, b) = ([0.0, 1.0], [0.0, f32::NAN], [0.0, 1.0]);
+        let rgba = to_rgba_rgb(&r, &g, &b, Stretch::Linear, 0.0, None, ColorOptions::default());
+        assert_eq!(&rgba[4..7], NON_FINITE_RGB);
+        assert_eq!(&rgba[..3], [0, 0, 0]);
+    }
+
+    #[test]
+    fn bin_averages_blocks_per_plane() {
+        // 5×2, two planes; the fifth column is a partial block and is dropped.
+        let plane0 = [1.0, 3.0, 5.0, 7.0, 100.0, 1.0, 3.0, 5.0, 7.0, 100.0];
+        let plane1 = plane0.map(|v| v * 10.0);
+        let img = test_image(5, 2, 2, [plane0, plane1].concat());
+        let binned = img.bin(2);
+        assert_eq!((binned.width, binned.height, binned.binning), (2, 1, 2));
+        assert_eq!(binned.data, vec![2.0, 6.0, 20.0, 60.0]);
+    }
+
+    #[test]
+    fn raw_pixel_reports_bayer_site() {
+        let raw = RawMosaic { data: vec![10, 20, 30, 40, 11, 21, 31, 41], pattern: *b"GRBG" };
+        let img = FitsImage {
+            is_bayer: true,
+            raw_mosaic: Some(raw),
+            ..test_image(4, 2, 3, vec![0.0; 24])
+        };
+        assert_eq!(img.raw_pixel(2, 0), Some((30.0, "G1".to_string())));
+        assert_eq!(img.raw_pixel(3, 0), Some((40.0, "R".to_string())));
+        assert_eq!(img.raw_pixel(0, 1), Some((11.0, "B".to_string())));
+        assert_eq!(img.raw_pixel(1, 1), Some((21.0, "G2".to_string())));
+        assert_eq!(img.raw_pixel(4, 0), None);
+        // Same scale as the debayered data
+        let img = FitsImage { pedestal: Some(10.0), ..img };
+        assert_eq!(img.raw_pixel(2, 0), Some((20.0, "G1".to_string())));
+        assert_eq!(img.cfa_stats().unwrap()[0].1.min, 0.0);
+    }
+
+    #[test]
+    fn log_stretch_lifts_midtones_and_handles_edges() {
+        let lut = log_lut(100.0, false);
+        assert_eq!((lut[0], lut[LUT_SIZE - 1]), (0, 255));
+        // ln(51) / ln(101) ≈ 0.852
+        assert_eq!(lut[(LUT_SIZE - 1) / 2], 217);
+        assert!(lut.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(log_lut(0.0, false), linear_ramp(false));
+        assert_eq!(log_lut(f32::NAN, false), linear_ramp(false));
+
+        // Flat plane: every pixel black, no NaN from the zero range
+        let rgba = to_rgba_gray(&[7.0; 4], Stretch::Log { k: 100.0 }, 0.0, None, ColorOptions::default());
+        assert!(rgba.chunks_exact(4).all(|p| p == [0, 0, 0, 255]));
+        // Below the header minimum clamps to black
+        let range = Some((0.0, 10.0));
+        let rgba = to_rgba_gray(&[-5.0, 10.0], Stretch::Log { k: 100.0 }, 0.0, range, ColorOptions::default());
+        assert_eq!((rgba[0], rgba[4]), (0, 255));
+    }
+
+    #[test]
+    fn raw_linear_maps_against_full_scale() {
+        let plane = [1000.0, 2000.0, 4095.0, 5000.0];
+        let rgba = to_rgba_gray(&plane, Stretch::RawLinear, 4095.0, Some((1000.0, 2000.0)), ColorOptions::default());
+        let grey: Vec<u8> = rgba.chunks_exact(4).map(|p| p[0]).collect();
+        // Independent of the data and header ranges; above full scale clamps to white
+        assert_eq!(grey, vec![62, 125, 255, 255]);
+        // Unknown full scale: data range
+        let rgba = to_rgba_gray(&[0.5, 1.5], Stretch::RawLinear, 0.0, None, ColorOptions::default());
+        assert_eq!((rgba[0], rgba[4]), (0, 255));
+    }
+
+    #[test]
+    fn histeq_spreads_levels_by_pixel_share() {
+        // Three quarters of the pixels at the bottom, one quarter at the top
+        let plane = [0.0, 0.0, 0.0, 10.0];
+        let rgba = to_rgba_gray(&plane, Stretch::HistEq { shared: false }, 0.0, None, ColorOptions::default());
+        assert_eq!((rgba[0], rgba[12]), (0, 255));
+        let plane = [0.0, 5.0, 10.0, 10.0];
+        let rgba = to_rgba_gray(&plane, Stretch::HistEq { shared: false }, 0.0, None, ColorOptions::default());
+        assert_eq!((rgba[0], rgba[4], rgba[8]), (0, 85, 255));
+        // Flat plane does not divide by zero
+        let rgba = to_rgba_gray(&[3.0; 4], Stretch::HistEq { shared: false }, 0.0, None, ColorOptions::default());
+        assert!(rgba.chunks_exact(4).all(|p| p == [0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn shared_histeq_keeps_channels_in_step() {
+        let r = [0.0, 4.0, 8.0, 12.0];
+        let g = [0.0, 4.0, 8.0, 12.0];
+        let b = [0.0, 2.0, 4.0, 6.0];
+        let stretch = Stretch::HistEq { shared: true };
+        let rgba = to_rgba_rgb(&r, &g, &b, stretch, 0.0, None, ColorOptions::default());
+        // One LUT over one range: equal values give equal output in every channel
+        assert_eq!(rgba[4 * 2 + 2], rgba[4]);
+        assert!(rgba.chunks_exact(4).all(|p| p[0] == p[1] && p[2] <= p[0]));
+    }
+
+    #[test]
+    fn linked_stretch_keeps_colour_ratios() {
+        // A blue plane at half the level of red and green
+        let (r, g, b) = ([0.0, 10.0], [0.0, 10.0], [0.0, 5.0]);
+        let independent = to_rgba_rgb(&r, &g, &b, Stretch::Linear, 0.0, None, ColorOptions::default());
+        assert_eq!(&independent[4..7], &[255, 255, 255]);
+        let linked = ColorOptions { linked: true, ..ColorOptions::default() };
+        let rgba = to_rgba_rgb(&r, &g, &b, Stretch::Linear, 0.0, None, linked);
+        assert_eq!(&rgba[4..7], &[255, 255, 128]);
+    }
+
+    #[test]
+    fn night_mode_keeps_only_red_luminance() {
+        let night = ColorOptions { night: true, ..ColorOptions::default() };
+        let grey = to_rgba_gray(&[0.0, 1.0], Stretch::Linear, 0.0, None, night);
+        assert_eq!(grey, [0, 0, 0, 255, 255, 0, 0, 255]);
+        // A pure-blue pixel stays visible, at its luminance
+        let rgba = to_rgba_rgb(&[0.0, 1.0], &[0.0, 1.0], &[1.0, 0.0], Stretch::Linear, 0.0, None, night);
+        assert_eq!(&rgba[..4], &[18, 0, 0, 255]);
+        assert_eq!(&rgba[4..], &[237, 0, 0, 255]);
+    }
+
+    #[test]
+    fn neutralize_background_aligns_channel_medians() {
+        // Blue sky 20 above red, green 5 above; one star in every channel
+        let plane = |bg: f32| -> Vec<f32> {
+            (0..16).map(|i| if i == 5 { bg + 500.0 } else { bg + (i % 3) as f32 }).collect()
+        };
+        let data = [plane(100.0), plane(105.0), plane(120.0)].concat();
+        let img = FitsImage {
+            datamin: Some(90.0),
+            ..test_image(4, 4, 3, data)
+        };
+        let out = img.neutralize_background();
+        let median = |c: usize| {
+            let mut p = out.plane(c).to_vec();
+            p.sort_by(f32::total_cmp);
+            p[p.len() / 2]
+        };
+        assert_eq!(median(0), median(1));
+        assert_eq!(median(0), median(2));
+        // Offsets only: the star keeps its height above the sky
+        assert_eq!(out.plane(2)[5] - out.plane(2)[0], img.plane(2)[5] - img.plane(2)[0]);
+        assert_eq!(out.plane(0), img.plane(0));
+        assert_eq!(out.datamin, Some(70.0));
+    }
+
+    /// Gaussian star (σ = 1.5 px, total 10000) at (20.3, 18.7) on a sky of 100 ± 2
+    const STAR: (f32, f32, f32, f32) = (20.3, 18.7, 1.5, 10000.0);
+
+    fn star_image() -> FitsImage {
+        star_field(40, 40, &[STAR])
+    }
+
+    /// Single-HDU 16-bit image of `channels` planes of `data`; tests override
+    /// the fields they care about with struct update syntax.
+    fn test_image(width: usize, height: usize, channels: usize, data: Vec<f32>) -> FitsImage {
+        FitsImage {
+            width,
+            height,
+            channels,
+            data,
+            headers: Vec::new(),
+            bitdepth_max: 65535.0,
+            is_bayer: false,
+            datamin: None,
+            datamax: None,
+            hot_pixels_fixed: None,
+            decimation: 1,
+            binning: 1,
+            raw_mosaic: None,
+            hdu: 0,
+            hdu_count: 1,
+            channel_hdus: None,
+            pedestal: None,
+        }
+    }
+
+    /// Gaussian stars (x, y, σ, total) on a sky of 100 ± 2
+    fn star_field(w: usize, h: usize, stars: &[(f32, f32, f32, f32)]) -> FitsImage {
+        let data: Vec<f32> = (0..w * h)
+            .map(|i| {
+                let (x, y) = ((i % w) as f32 + 0.5, (i / w) as f32 + 0.5);
+                let star: f32 = stars
+                    .iter()
+                    .map(|&(sx, sy, sigma, total)| {
+                        let r2 = (x - sx).powi(2) + (y - sy).powi(2);
+                        total / (2.0 * std::f32::consts::PI * sigma * sigma) * (-r2 / (2.0 * sigma * sigma)).exp()
+                    })
+                    .sum();
+                100.0 + [-2.0, 0.0, 2.0][(i * 7) % 3] + star
+            })
+            .collect();
+        FitsImage {
+            bitdepth_max: 0.0,
+            ..test_image(w, h, 1, data)
+        }
+    }
+
+    #[test]
+    fn aperture_photometry_recovers_star_flux() {
+        let img = star_image();
+        let (sx, sy, _, total) = STAR;
+        // Clicked a couple of pixels off the star
+        let p = img.aperture_photometry(22.0, 17.0, 6.0, (9.0, 14.0)).unwrap();
+        assert!((p.x - sx).abs() < 0.1 && (p.y - sy).abs() < 0.1, "{} {}", p.x, p.y);
+        assert!((p.sky.level - 100.0).abs() <= 2.0);
+        assert!((p.flux - total as f64).abs() / (total as f64) < 0.02, "{}", p.flux);
+        assert!((p.magnitude().unwrap() + 10.0).abs() < 0.03);
+        assert!(p.snr() > 100.0);
+        // Nowhere near the image
+        assert!(img.aperture_photometry(-50.0, -50.0, 6.0, (9.0, 14.0)).is_none());
+    }
+
+    #[test]
+    fn radial_profile_measures_fwhm_and_hfr() {
+        let img = star_image();
+        let sigma = STAR.2;
+        let profile = img.radial_profile(22.0, 17.0, 6.0, (9.0, 14.0)).unwrap();
+        let fwhm = profile.fwhm.unwrap();
+        assert!((fwhm - 2.3548 * sigma).abs() < 0.15, "{fwhm}");
+        // A Gaussian encloses half its flux within σ √(2 ln 2)
+        let hfr = profile.hfr.unwrap();
+        assert!((hfr - 1.1774 * sigma).abs() < 0.15, "{hfr}");
+        // Rings fall off from the centre out to the sky
+        let (first, last) = (profile.bins[0].1, profile.bins.last().unwrap().1);
+        assert!(first > 500.0 && last.abs() < 5.0, "{first} {last}");
+    }
+
+    #[test]
+    fn detected_stars_bin_into_fwhm_grid() {
+        // One star per cell of a 3 × 3 grid, wider towards the right
+        let stars: Vec<_> = (0..9)
+            .map(|i| (15.3 + 30.0 * (i % 3) as f32, 14.8 + 30.0 * (i / 3) as f32, 1.2 + 0.4 * (i % 3) as f32, 8000.0))
+            .collect();
+        let img = star_field(90, 90, &stars);
+        let found = img.detect_stars();
+        assert_eq!(found.len(), 9);
+        let grid = fwhm_grid(&found, 90, 90);
+        for (row, cells) in grid.iter().enumerate() {
+            for (col, fwhm) in cells.iter().enumerate() {
+                let expected = 2.3548 * stars[row * 3 + col].2;
+                assert!((fwhm.unwrap() - expected).abs() < 0.2, "{row},{col}: {fwhm:?} vs {expected}");
+            }
+        }
+    }
+
+    #[test]
+    fn wcs_measures_separation_and_position_angle() {
+        let card = |k: &str, v: &str| (k.to_string(), v.to_string());
+        // 1″ pixels, north up (+y), east left (−x), as from a plate solve
+        let headers = [
+            card("CTYPE1", "RA---TAN"),
+            card("CTYPE2", "DEC--TAN"),
+            card("CRPIX1", "50.5"),
+            card("CRPIX2", "50.5"),
+            card("CRVAL1", "83.8"),
+            card("CRVAL2", "-5.4"),
+            card("CDELT1", &format!("{}", -1.0 / 3600.0)),
+            card("CDELT2", &format!("{}", 1.0 / 3600.0)),
+        ];
+        let wcs = Wcs::from_headers(&headers).unwrap();
+        let (ra, dec) = wcs.pixel_to_world(49.5, 49.5);
+        assert!((ra - 83.8).abs() < 1e-9 && (dec + 5.4).abs() < 1e-9);
+        // 100 px north of the reference pixel
+        let (sep, pa) = separation(wcs.pixel_to_world(49.5, 49.5), wcs.pixel_to_world(49.5, 149.5));
+        assert!((sep * 3600.0 - 100.0).abs() < 0.01, "{}", sep * 3600.0);
+        assert!(!(0.01..=359.99).contains(&pa), "{pa}");
+        // 30 px towards −x is east
+        let (sep, pa) = separation(wcs.pixel_to_world(49.5, 49.5), wcs.pixel_to_world(19.5, 49.5));
+        assert!((sep * 3600.0 - 30.0).abs() < 0.01);
+        assert!((pa - 90.0).abs() < 0.01, "{pa}");
+        // Back from the sky to the pixel
+        for (x, y) in [(49.5, 49.5), (10.0, 90.0), (0.0, 0.0)] {
+            let (ra, dec) = wcs.pixel_to_world(x, y);
+            let (px, py) = wcs.world_to_pixel(ra, dec).unwrap();
+            assert!((px - x).abs() < 1e-6 && (py - y).abs() < 1e-6, "{x}, {y}: {px}, {py}");
+        }
+        assert!(wcs.world_to_pixel(263.8, 5.4).is_none());
+        // No projection keywords, no WCS
+        assert!(Wcs::from_headers(&headers[2..]).is_none());
+    }
+
+    #[test]
+    fn background_noise_matches_gaussian_sigma() {
+        // Normal noise (Box–Muller) around 1000 with σ = 20, plus saturated stars
+        let mut seed = 7u32;
+        let mut uniform = move || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            ((seed >> 8) as f32 + 0.5) / (1u32 << 24) as f32
+        };
+        let mut plane: Vec<f32> = (0..200_000)
+            .map(|_| {
+                let (u1, u2) = (uniform(), uniform());
+                1000.0 + 20.0 * (-2.0 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos()
+            })
+            .collect();
+        plane.iter_mut().step_by(500).for_each(|v| *v = 65535.0);
+        let (level, mad) = median_mad(&plane);
+        assert!((level - 1000.0).abs() < 0.5, "level {level}");
+        assert!((1.4826 * mad - 20.0).abs() < 0.5, "sigma {}", 1.4826 * mad);
+        // Flat data has no noise
+        assert_eq!(median_mad(&[5.0; 10]), (5.0, 0.0));
+        let bg = BackgroundNoise { level: 1000.0, sigma: 20.0 };
+        assert_eq!(bg.snr(1100.0), 5.0);
+    }
+
+    #[test]
+    fn difference_stats_of_a_shifted_frame() {
+        let previous = star_image();
+        let mut current = star_image();
+        for (i, v) in current.data.iter_mut().enumerate() {
+            *v += if i % 2 == 0 { 7.0 } else { 3.0 };
+        }
+        let [stats] = current.difference_stats(&previous).unwrap()[..] else { panic!("one plane") };
+        assert!((stats.mean - 5.0).abs() < 1e-4);
+        assert!((stats.stddev - 2.0).abs() < 1e-3);
+        assert!((stats.min - 3.0).abs() < 1e-3 && (stats.max - 7.0).abs() < 1e-3);
+        assert!(current.difference_stats(&star_field(20, 20, &[])).is_none());
+    }
+
+    #[test]
+    fn combine_difference_and_ratio() {
+        let mut a = star_field(2, 1, &[]);
+        let mut b = a.clone();
+        (a.data, b.data) = (vec![10.0, 6.0], vec![4.0, 0.0]);
+        assert_eq!(a.combine(&b, Combine::Difference).unwrap().data, [6.0, 6.0]);
+        let ratio = a.combine(&b, Combine::Ratio).unwrap().data;
+        assert_eq!(ratio[0], 2.5);
+        assert!(ratio[1].is_nan());
+        assert!(a.combine(&star_field(1, 2, &[]), Combine::Ratio).is_none());
+    }
+
+    #[test]
+    fn saturated_fraction_counts_near_full_scale() {
+        let mut img = test_image(4, 1, 1, vec![100.0, 64300.0, 65535.0, 64000.0]);
+        // 98 % of 65535 is 64224.3
+        assert_eq!(img.saturated_fraction(), Some(0.5));
+        img.raw_mosaic = Some(RawMosaic { data: vec![65535, 0, 0, 0], pattern: *b"RGGB" });
+        assert_eq!(img.saturated_fraction(), Some(0.25));
+        img.bitdepth_max = 0.0;
+        assert_eq!(img.saturated_fraction(), None);
+    }
+
+    #[test]
+    fn render_png_decodes_to_native_size() {
+        let img = FitsImage {
+            bitdepth_max: 0.0,
+            ..test_image(5, 3, 1, (0..15).map(|v| v as f32).collect())
+        };
+        let bytes = img.render_png(Stretch::Linear, ChannelView::Rgb).unwrap();
+        let mut reader = png::Decoder::new(std::io::Cursor::new(bytes)).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!((info.width, info.height, info.color_type), (5, 3, png::ColorType::Rgb));
+        let rgba = img.to_rgba(Stretch::Linear, ChannelView::Rgb, ColorOptions::default());
+        let rgb: Vec<u8> = rgba.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect();
+        assert_eq!(&buf[..info.buffer_size()], &rgb[..]);
+    }
+}
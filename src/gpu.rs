@@ -0,0 +1,215 @@
+use eframe::egui_glow;
+use eframe::glow::{self, HasContext};
+use std::sync::{Arc, Mutex};
+
+use crate::fits::{FitsImage, StfCurve, NON_FINITE_RGB};
+
+/// Screen transfer function applied on the GPU: the planes of the displayed image
+/// are uploaded once as float textures and a fragment shader maps them through the
+/// STF, so dragging the STF sliders only changes a few uniforms instead of
+/// re-rendering the image on the CPU. Needs the OpenGL (glow) painter and GLSL 1.40
+/// or ES 3.00; otherwise the CPU LUT path is used.
+pub struct GpuStretch {
+    program: glow::Program,
+    vertex_array: glow::VertexArray,
+    /// One R32F texture (with mipmaps) per plane of the uploaded image
+    textures: Vec<glow::Texture>,
+    /// Caller-supplied generation of the image in `textures`
+    uploaded: Option<u64>,
+    max_texture_size: usize,
+}
+
+/// What to draw for one frame: which uploaded planes feed R, G and B (the same
+/// plane three times for greyscale views) and their curves (None = flat, mid-grey).
+#[derive(Clone, Copy)]
+pub struct GpuView {
+    pub planes: [usize; 3],
+    pub curves: [Option<StfCurve>; 3],
+}
+
+const VERTEX_SHADER: &str = r#"
+const vec2 corners[4] = vec2[4](vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(-1.0, 1.0), vec2(1.0, 1.0));
+out vec2 v_uv;
+void main() {
+    vec2 p = corners[gl_VertexID];
+    // Texture row 0 is the top row of the image
+    v_uv = vec2(p.x * 0.5 + 0.5, 0.5 - p.y * 0.5);
+    gl_Position = vec4(p, 0.0, 1.0);
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+uniform sampler2D u_r;
+uniform sampler2D u_g;
+uniform sampler2D u_b;
+// black, white, scale, midtones; scale <= 0 marks a flat plane
+uniform vec4 u_curve[3];
+uniform vec3 u_non_finite;
+in vec2 v_uv;
+out vec4 out_color;
+
+float mtf(float x, float m) {
+    float den = (2.0 * m - 1.0) * x - m;
+    if (abs(den) < 1e-9) return 0.5;
+    return clamp((m - 1.0) * x / den, 0.0, 1.0);
+}
+
+float stretch(float v, vec4 c) {
+    if (c.z <= 0.0) return 128.0 / 255.0;
+    if (v <= c.x) return 0.0;
+    if (v >= c.y) return 1.0;
+    return mtf(clamp((v - c.x) / c.z, 0.0, 1.0), c.w);
+}
+
+bool bad(float v) {
+    return isnan(v) || isinf(v);
+}
+
+void main() {
+    float r = texture(u_r, v_uv).r;
+    float g = texture(u_g, v_uv).r;
+    float b = texture(u_b, v_uv).r;
+    if (bad(r) || bad(g) || bad(b)) {
+        out_color = vec4(u_non_finite, 1.0);
+        return;
+    }
+    out_color = vec4(stretch(r, u_curve[0]), stretch(g, u_curve[1]), stretch(b, u_curve[2]), 1.0);
+}
+"#;
+
+impl GpuStretch {
+    /// Compile the stretch shader. None, with the reason on stderr, when the GL
+    /// version is too old or compilation fails.
+    pub fn new(gl: &glow::Context) -> Option<Self> {
+        let version = egui_glow::ShaderVersion::get(gl);
+        if !version.is_new_shader_interface() {
+            eprintln!("GPU stretch unavailable: needs GLSL 1.40 / ES 3.00");
+            return None;
+        }
+        let header = format!("{}precision highp float;\n", version.version_declaration());
+        unsafe {
+            let program = gl.create_program().ok()?;
+            let mut shaders = Vec::new();
+            for (kind, source) in [(glow::VERTEX_SHADER, VERTEX_SHADER), (glow::FRAGMENT_SHADER, FRAGMENT_SHADER)] {
+                let shader = gl.create_shader(kind).ok()?;
+                gl.shader_source(shader, &format!("{header}{source}"));
+                gl.compile_shader(shader);
+                if !gl.get_shader_compile_status(shader) {
+                    eprintln!("GPU stretch shader: {}", gl.get_shader_info_log(shader));
+                    gl.delete_shader(shader);
+                    shaders.into_iter().for_each(|s| gl.delete_shader(s));
+                    gl.delete_program(program);
+                    return None;
+                }
+                gl.attach_shader(program, shader);
+                shaders.push(shader);
+            }
+            gl.link_program(program);
+            let linked = gl.get_program_link_status(program);
+            for shader in shaders {
+                gl.detach_shader(program, shader);
+                gl.delete_shader(shader);
+            }
+            if !linked {
+                eprintln!("GPU stretch program: {}", gl.get_program_info_log(program));
+                gl.delete_program(program);
+                return None;
+            }
+            let vertex_array = gl.create_vertex_array().ok()?;
+            let max_texture_size = gl.get_parameter_i32(glow::MAX_TEXTURE_SIZE).max(0) as usize;
+            Some(GpuStretch { program, vertex_array, textures: Vec::new(), uploaded: None, max_texture_size })
+        }
+    }
+
+    /// Whether `img` fits in a texture on this GPU.
+    pub fn supports(&self, img: &FitsImage) -> bool {
+        img.width.max(img.height) <= self.max_texture_size
+    }
+
+    /// Upload every plane of `img`, unless `generation` is already on the GPU.
+    pub fn upload(&mut self, gl: &glow::Context, img: &FitsImage, generation: u64) {
+        if self.uploaded == Some(generation) {
+            return;
+        }
+        unsafe {
+            for texture in self.textures.drain(..) {
+                gl.delete_texture(texture);
+            }
+            for c in 0..img.channels {
+                let Ok(texture) = gl.create_texture() else { break };
+                // The texture is width × height: never hand GL a shorter buffer
+                let plane = img.full_plane(c);
+                let bytes = std::slice::from_raw_parts(plane.as_ptr().cast::<u8>(), std::mem::size_of_val(&*plane));
+                gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+                gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 4);
+                gl.tex_image_2d(
+                    glow::TEXTURE_2D,
+                    0,
+                    glow::R32F as i32,
+                    img.width as i32,
+                    img.height as i32,
+                    0,
+                    glow::RED,
+                    glow::FLOAT,
+                    Some(bytes),
+                );
+                // Mipmaps average the raw values, so zoomed-out views don't alias
+                gl.generate_mipmap(glow::TEXTURE_2D);
+                gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, glow::LINEAR_MIPMAP_LINEAR as i32);
+                gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, glow::LINEAR as i32);
+                gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE as i32);
+                gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE as i32);
+                self.textures.push(texture);
+            }
+        }
+        self.uploaded = Some(generation);
+    }
+
+    fn paint(&self, gl: &glow::Context, view: GpuView) {
+        if view.planes.iter().any(|&p| p >= self.textures.len()) {
+            return;
+        }
+        unsafe {
+            gl.use_program(Some(self.program));
+            for (unit, (name, &plane)) in ["u_r", "u_g", "u_b"].iter().zip(&view.planes).enumerate() {
+                gl.active_texture(glow::TEXTURE0 + unit as u32);
+                gl.bind_texture(glow::TEXTURE_2D, Some(self.textures[plane]));
+                gl.uniform_1_i32(gl.get_uniform_location(self.program, name).as_ref(), unit as i32);
+            }
+            gl.active_texture(glow::TEXTURE0);
+            let curves: Vec<f32> = view
+                .curves
+                .iter()
+                .flat_map(|c| c.map_or([0.0; 4], |c| [c.black, c.white, c.scale, c.midtones]))
+                .collect();
+            gl.uniform_4_f32_slice(gl.get_uniform_location(self.program, "u_curve").as_ref(), &curves);
+            let [r, g, b] = NON_FINITE_RGB.map(|v| v as f32 / 255.0);
+            gl.uniform_3_f32(gl.get_uniform_location(self.program, "u_non_finite").as_ref(), r, g, b);
+            gl.bind_vertex_array(Some(self.vertex_array));
+            gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
+        }
+    }
+
+    /// Free the GL objects.
+    pub fn destroy(&mut self, gl: &glow::Context) {
+        unsafe {
+            for texture in self.textures.drain(..) {
+                gl.delete_texture(texture);
+            }
+            gl.delete_program(self.program);
+            gl.delete_vertex_array(self.vertex_array);
+        }
+        self.uploaded = None;
+    }
+}
+
+/// Paint callback drawing the uploaded image into `rect` through `view`.
+pub fn paint_callback(gpu: &Arc<Mutex<GpuStretch>>, rect: egui::Rect, view: GpuView) -> egui::PaintCallback {
+    let gpu = Arc::clone(gpu);
+    let callback = egui_glow::CallbackFn::new(move |_info, painter| {
+        if let Ok(gpu) = gpu.lock() {
+            gpu.paint(painter.gl(), view);
+        }
+    });
+    egui::PaintCallback { rect, callback: Arc::new(callback) }
+}
@@ -0,0 +1,321 @@
+use egui::Key;
+use std::collections::BTreeMap;
+
+/// User actions that can be bound to keys. Ctrl+C (copy) and Escape (close popups)
+/// are fixed and not part of the keymap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Action {
+    NextFile,
+    PrevFile,
+    FirstFile,
+    LastFile,
+    GoTo,
+    Delete,
+    CycleStretch,
+    CycleChannel,
+    ZoomIn,
+    ZoomOut,
+    ZoomReset,
+    ZoomFit,
+    Help,
+    Prefs,
+    Stf,
+    Analysis,
+    Mask,
+    Export,
+    CycleBinning,
+    Fullscreen,
+    Watch,
+    Follow,
+    NightVision,
+    Measure,
+    Photometry,
+    Profile,
+    TiltMap,
+    FileInfo,
+    Reload,
+    ScaleBar,
+    ParentDir,
+    Folders,
+    Navigator,
+    HeadersPanel,
+    FilesPanel,
+    Difference,
+    Catalog,
+    FrameFilter,
+    GroupBy,
+    Hud,
+    NextPreset,
+}
+
+impl Action {
+    pub const ALL: [Action; 41] = [
+        Action::NextFile,
+        Action::PrevFile,
+        Action::FirstFile,
+        Action::LastFile,
+        Action::GoTo,
+        Action::Delete,
+        Action::CycleStretch,
+        Action::CycleChannel,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::ZoomReset,
+        Action::ZoomFit,
+        Action::Help,
+        Action::Prefs,
+        Action::Stf,
+        Action::Analysis,
+        Action::Mask,
+        Action::Export,
+        Action::CycleBinning,
+        Action::Fullscreen,
+        Action::Watch,
+        Action::Follow,
+        Action::NightVision,
+        Action::Measure,
+        Action::Photometry,
+        Action::Profile,
+        Action::TiltMap,
+        Action::FileInfo,
+        Action::Reload,
+        Action::ScaleBar,
+        Action::ParentDir,
+        Action::Folders,
+        Action::Navigator,
+        Action::HeadersPanel,
+        Action::FilesPanel,
+        Action::Difference,
+        Action::Catalog,
+        Action::FrameFilter,
+        Action::GroupBy,
+        Action::Hud,
+        Action::NextPreset,
+    ];
+
+    /// Name used in the config file.
+    pub fn id(self) -> &'static str {
+        match self {
+            Action::NextFile => "next_file",
+            Action::PrevFile => "prev_file",
+            Action::FirstFile => "first_file",
+            Action::LastFile => "last_file",
+            Action::GoTo => "go_to",
+            Action::Delete => "delete",
+            Action::CycleStretch => "cycle_stretch",
+            Action::CycleChannel => "cycle_channel",
+            Action::ZoomIn => "zoom_in",
+            Action::ZoomOut => "zoom_out",
+            Action::ZoomReset => "zoom_reset",
+            Action::ZoomFit => "zoom_fit",
+            Action::Help => "help",
+            Action::Prefs => "prefs",
+            Action::Stf => "stf",
+            Action::Analysis => "analysis",
+            Action::Mask => "mask",
+            Action::Export => "export",
+            Action::CycleBinning => "cycle_binning",
+            Action::Fullscreen => "fullscreen",
+            Action::Watch => "watch",
+            Action::Follow => "follow",
+            Action::NightVision => "night_vision",
+            Action::Measure => "measure",
+            Action::Photometry => "photometry",
+            Action::Profile => "profile",
+            Action::TiltMap => "tilt_map",
+            Action::FileInfo => "file_info",
+            Action::Reload => "reload",
+            Action::ScaleBar => "scale_bar",
+            Action::ParentDir => "parent_dir",
+            Action::Folders => "folders",
+            Action::Navigator => "navigator",
+            Action::HeadersPanel => "headers_panel",
+            Action::FilesPanel => "files_panel",
+            Action::Difference => "difference",
+            Action::Catalog => "catalog",
+            Action::FrameFilter => "frame_filter",
+            Action::GroupBy => "group_by",
+            Action::Hud => "hud",
+            Action::NextPreset => "next_preset",
+        }
+    }
+
+    /// Description shown in the help popup and the Preferences keyboard tab.
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::NextFile => "Next file",
+            Action::PrevFile => "Previous file",
+            Action::FirstFile => "First file",
+            Action::LastFile => "Last file",
+            Action::GoTo => "Go to file by number or name",
+            Action::Delete => "Move current file (or all selected files) to trash",
+            Action::CycleStretch => "Cycle stretch (Auto → Percentile → Linear → Raw → Log → Equalised)",
+            Action::CycleChannel => "Cycle channel view (R → G → B → RGB)",
+            Action::ZoomIn => "Zoom in",
+            Action::ZoomOut => "Zoom out",
+            Action::ZoomReset => "Zoom to 1:1 (100 %)",
+            Action::ZoomFit => "Zoom to fit",
+            Action::Help => "Show / hide this help",
+            Action::Prefs => "Show / hide Preferences",
+            Action::Stf => "Show / hide screen transfer function (STF)",
+            Action::Analysis => "Show / hide image analysis (Bayer channel statistics)",
+            Action::Mask => "Show / hide the data-quality mask overlay",
+            Action::Export => "Export the displayed image as PNG",
+            Action::CycleBinning => "Cycle binning (off → 2×2 → 3×3)",
+            Action::Fullscreen => "Toggle full-screen mode",
+            Action::Watch => "Toggle watch mode (auto-reload)",
+            Action::Follow => "Toggle live-follow of the newest file",
+            Action::NightVision => "Toggle night vision (red-only display)",
+            Action::Measure => "Measure distance and angle between two clicked points",
+            Action::Photometry => "Aperture photometry of clicked stars",
+            Action::Profile => "Radial profile, FWHM and HFR of a clicked star",
+            Action::TiltMap => "Show / hide the FWHM tilt map (3 × 3 grid)",
+            Action::FileInfo => "Show / hide file info (BITPIX, BSCALE, BZERO, byte order)",
+            Action::Reload => "Reload the current file from disk",
+            Action::ScaleBar => "Show / hide the scale bar",
+            Action::ParentDir => "Browse the parent directory",
+            Action::Folders => "Show / hide the folder tree",
+            Action::Navigator => "Show / hide the navigator when zoomed in",
+            Action::HeadersPanel => "Show / hide the headers panel",
+            Action::FilesPanel => "Show / hide the file browser panel",
+            Action::Difference => "Compare with the previous file: difference, ratio, off",
+            Action::Catalog => "Show / hide catalogue stars on plate-solved images",
+            Action::FrameFilter => "Cycle the browser's frame-type filter (all, light, dark, flat, bias)",
+            Action::GroupBy => "Group the file list by name, filter or exposure",
+            Action::Hud => "Show / hide the information HUD over the image",
+            Action::NextPreset => "Apply the next saved stretch preset",
+        }
+    }
+
+    fn default_keys(self) -> &'static [Key] {
+        match self {
+            Action::NextFile => &[Key::ArrowRight, Key::ArrowDown],
+            Action::PrevFile => &[Key::ArrowLeft, Key::ArrowUp],
+            Action::FirstFile => &[Key::Home],
+            Action::LastFile => &[Key::End],
+            Action::GoTo => &[Key::Slash, Key::Colon],
+            Action::Delete => &[Key::Delete],
+            Action::CycleStretch => &[Key::S],
+            Action::CycleChannel => &[Key::C],
+            Action::ZoomIn => &[Key::Plus, Key::Equals],
+            Action::ZoomOut => &[Key::Minus],
+            Action::ZoomReset => &[Key::Num0],
+            Action::ZoomFit => &[Key::F],
+            Action::Help => &[Key::Questionmark],
+            Action::Prefs => &[Key::Comma],
+            Action::Stf => &[Key::T],
+            Action::Analysis => &[Key::A],
+            Action::Mask => &[Key::M],
+            Action::Export => &[Key::E],
+            Action::CycleBinning => &[Key::B],
+            Action::Fullscreen => &[Key::F11],
+            Action::Watch => &[Key::W],
+            Action::Follow => &[Key::L],
+            Action::NightVision => &[Key::N],
+            Action::Measure => &[Key::D],
+            Action::Photometry => &[Key::P],
+            Action::Profile => &[Key::R],
+            Action::TiltMap => &[Key::I],
+            Action::FileInfo => &[Key::O],
+            Action::Reload => &[Key::F5],
+            Action::ScaleBar => &[Key::X],
+            Action::ParentDir => &[Key::Backspace],
+            Action::Folders => &[Key::V],
+            Action::Navigator => &[Key::U],
+            Action::HeadersPanel => &[Key::OpenBracket],
+            Action::FilesPanel => &[Key::CloseBracket],
+            Action::Difference => &[Key::Y],
+            Action::Catalog => &[Key::Q],
+            Action::FrameFilter => &[Key::Z],
+            Action::GroupBy => &[Key::Semicolon],
+            Action::Hud => &[Key::Period],
+            Action::NextPreset => &[Key::Quote],
+        }
+    }
+}
+
+/// Keys taken over by the optional vim-style navigation (`h`/`k` previous, `j`/`l`
+/// next, `gg` first, `G` last); bindings on them are ignored while it is on.
+pub const VIM_KEYS: [Key; 5] = [Key::H, Key::J, Key::K, Key::L, Key::G];
+
+/// Key bindings for every [`Action`]; an action may have several keys.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    keys: BTreeMap<Action, Vec<Key>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let keys = Action::ALL.iter().map(|&a| (a, a.default_keys().to_vec())).collect();
+        Keymap { keys }
+    }
+}
+
+impl Keymap {
+    /// Whether any key bound to `action`, other than those in `reserved`, was
+    /// pressed this frame. Bindings are plain keys: with Ctrl / ⌘ held they are left
+    /// to fixed shortcuts such as Ctrl+C.
+    pub fn pressed(&self, input: &egui::InputState, action: Action, reserved: &[Key]) -> bool {
+        !input.modifiers.command && self.keys(action).iter().any(|&k| !reserved.contains(&k) && input.key_pressed(k))
+    }
+
+    pub fn keys(&self, action: Action) -> &[Key] {
+        self.keys.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn set(&mut self, action: Action, keys: Vec<Key>) {
+        self.keys.insert(action, keys);
+    }
+
+    /// Bound keys for display, e.g. `→ / ↓`.
+    pub fn describe(&self, action: Action) -> String {
+        let keys = self.keys(action);
+        if keys.is_empty() {
+            return "(none)".to_string();
+        }
+        keys.iter().map(|k| k.symbol_or_name()).collect::<Vec<_>>().join(" / ")
+    }
+
+    /// Config-file form: action id → key names.
+    pub fn to_names(&self) -> BTreeMap<String, Vec<String>> {
+        self.keys
+            .iter()
+            .map(|(a, keys)| (a.id().to_string(), keys.iter().map(|k| k.name().to_string()).collect()))
+            .collect()
+    }
+
+    /// Parse the config-file form. Actions missing from `names` keep their default
+    /// keys; unknown actions and key names are ignored.
+    pub fn from_names(names: &BTreeMap<String, Vec<String>>) -> Self {
+        let mut map = Keymap::default();
+        for action in Action::ALL {
+            if let Some(keys) = names.get(action.id()) {
+                map.set(action, keys.iter().filter_map(|k| Key::from_name(k)).collect());
+            }
+        }
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        let mut map = Keymap::default();
+        map.set(Action::ZoomIn, vec![Key::K]);
+        assert_eq!(Keymap::from_names(&map.to_names()), map);
+    }
+
+    #[test]
+    fn unknown_names_are_ignored() {
+        let names = BTreeMap::from([
+            ("next_file".to_string(), vec!["N".to_string(), "NoSuchKey".to_string()]),
+            ("no_such_action".to_string(), vec!["X".to_string()]),
+        ]);
+        let map = Keymap::from_names(&names);
+        assert_eq!(map.keys(Action::NextFile), &[Key::N]);
+        assert_eq!(map.keys(Action::PrevFile), Action::PrevFile.default_keys());
+    }
+}
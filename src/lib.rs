@@ -0,0 +1,36 @@
+//! FITS loading and display stretching, as used by the fastfits viewer.
+//!
+//! [`FitsImage::load`] reads the first image HDU of a FITS file (including
+//! tile-compressed `.fz` files) into planar `f32` data, debayering colour sensor
+//! frames. [`FitsImage::to_rgba`] stretches it for display into an RGBA8 buffer,
+//! and [`FitsImage::render_png`] encodes the same rendering as an in-memory PNG.
+//!
+//! ```no_run
+//! use fastfits::{ChannelView, ColorOptions, FitsImage, LoadOptions, Stretch};
+//!
+//! let img = FitsImage::load("light.fits".as_ref(), LoadOptions::default())?;
+//! let rgba = img.to_rgba(Stretch::AUTO, ChannelView::Rgb, ColorOptions::default());
+//! assert_eq!(rgba.len(), img.width * img.height * 4);
+//! # Ok::<(), fastfits::FitsError>(())
+//! ```
+//!
+//! The stretches also work on a bare plane of values: [`stretch_plane`] turns a
+//! `&[f32]` into display bytes, and [`autostretch_lut`] / [`linear_lut`] return
+//! the lookup tables themselves. The statistics behind AutoStretch are public
+//! too: the midtone transfer function [`mtf`], [`median_mad`] and [`percentile_norm`].
+//!
+//! Finer control of AutoStretch goes through [`FitsImage::auto_stf`], whose
+//! [`Stf`] parameters can be adjusted and applied with [`FitsImage::to_rgba_stf`].
+//!
+//! [`read_image_headers`] reads just the header of a file, cheaply enough to
+//! scan a whole directory, [`header_value`] / [`header_f64`] look up its
+//! keywords, and [`FrameType`] classifies it as a light, dark, flat or bias frame.
+//!
+//! Loading fails with a [`FitsError`], so callers can tell an unreadable file
+//! apart from one that simply holds no displayable image.
+#![warn(missing_docs)]
+
+/// FITS reading, the image model and the stretch functions.
+pub mod fits;
+
+pub use fits::{autostretch_lut, header_f64, header_value, linear_lut, median_mad, mtf, percentile_norm, read_image_headers, stretch_plane, ChannelView, ColorOptions, DataFormat, FitsError, FitsImage, FrameType, LoadOptions, Photometry, RadialProfile, Star, Stf, Stretch, Wcs};
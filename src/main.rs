@@ -1,22 +1,148 @@
 mod app;
-mod fits;
+mod cache;
+mod catalog;
+mod config;
+mod export;
+mod gpu;
+mod keymap;
+mod scan;
+mod watch;
 
-use clap::Parser;
+// The FITS code lives in the library crate (src/lib.rs); importing it here keeps
+// `crate::fits` paths working in the app modules.
+use fastfits::fits;
+
+use anyhow::Context;
+use clap::{Parser, ValueEnum};
+use fastfits::{ChannelView, Stretch};
 use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(name = "fastfits", about = "Fast FITS file viewer")]
 struct Args {
-    /// FITS file or directory to open (defaults to current directory)
-    path: Option<PathBuf>,
+    /// FITS file or directory to open (defaults to current directory); several
+    /// files are browsed as a list, in the order given. `-` reads a FITS file from stdin
+    paths: Vec<PathBuf>,
+
+    /// Write a contact sheet of every FITS file in the directory to this PNG and exit
+    #[arg(long, value_name = "OUT.png")]
+    montage: Option<PathBuf>,
+    /// Thumbnails per row in the montage
+    #[arg(long, default_value_t = 6)]
+    columns: usize,
+    /// Montage thumbnail size in pixels
+    #[arg(long, default_value_t = 256)]
+    cell: usize,
+
+    /// Measure every FITS file in the directory (star count, FWHM, HFR, SNR,
+    /// background) into this CSV and exit; rerunning resumes an interrupted run
+    #[arg(long, value_name = "OUT.csv")]
+    stats: Option<PathBuf>,
+
+    /// Stretch to start in (overrides the one remembered for the directory)
+    #[arg(long, value_enum)]
+    stretch: Option<StretchArg>,
+    /// Channel view to start colour images in
+    #[arg(long, value_enum)]
+    channel: Option<ChannelArg>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum StretchArg {
+    /// Histogram-based autostretch
+    Auto,
+    /// Linear between percentiles
+    Percentile,
+    /// Linear over the data range
+    Linear,
+    /// Linear against full scale
+    Raw,
+    /// Logarithmic
+    Log,
+    /// Histogram equalisation
+    Equalised,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ChannelArg {
+    /// Composite colour
+    Rgb,
+    /// Red plane
+    R,
+    /// Green plane
+    G,
+    /// Blue plane
+    B,
 }
 
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
-    let start_path = args
-        .path
-        .unwrap_or_else(|| std::env::current_dir().expect("cannot determine current directory"));
+    let mut paths = args.paths;
+    if paths.is_empty() {
+        paths.push(std::env::current_dir().expect("cannot determine current directory"));
+    }
+    let stdin = paths.iter().filter(|p| p.as_os_str() == "-").count();
+    if stdin > 1 {
+        anyhow::bail!("`-` (stdin) can be given only once");
+    }
+    // Kept until main returns: dropping it removes the copy of stdin
+    let stdin_copy = if stdin == 1 { Some(StdinCopy::read()?) } else { None };
+    if let Some(copy) = &stdin_copy {
+        for p in paths.iter_mut().filter(|p| p.as_os_str() == "-") {
+            *p = copy.path();
+        }
+    }
+    // A file from stdin is shown on its own, not alongside the rest of the temp directory
+    let file_list = paths.len() > 1 || stdin_copy.is_some();
+    if paths.len() > 1 {
+        if let Some(bad) = paths.iter().find(|p| !p.is_file()) {
+            anyhow::bail!("{}: not a file (a list of paths must name FITS files)", bad.display());
+        }
+    }
+
+    // Headless modes take the files of the directory the browser would list, or the list given
+    let batch_files = |paths: &[PathBuf]| -> anyhow::Result<Vec<PathBuf>> {
+        Ok(match paths {
+            [start_path] if !file_list => {
+                let dir = if start_path.is_file() { start_path.parent().unwrap_or(start_path) } else { start_path };
+                app::collect_fits_files(dir, &config::Config::load()?.file_filter)
+            }
+            _ => paths.to_vec(),
+        })
+    };
+    if let Some(out) = args.stats {
+        export::write_stats_csv(&batch_files(&paths)?, &out)?;
+        eprintln!("wrote {}", out.display());
+        return Ok(());
+    }
+    if let Some(out) = args.montage {
+        let files = batch_files(&paths)?;
+        let opts = export::MontageOptions { columns: args.columns, cell: args.cell };
+        export::write_montage(&files, opts, &out)?;
+        eprintln!("wrote {}", out.display());
+        return Ok(());
+    }
+
+    let start_view = app::StartView {
+        stretch: args.stretch.map(|s| match s {
+            StretchArg::Auto => Stretch::AUTO,
+            StretchArg::Percentile => {
+                let (low, high) = app::DEFAULT_LINEAR_CLIP;
+                Stretch::Percentile { low, high }
+            }
+            StretchArg::Linear => Stretch::Linear,
+            StretchArg::Raw => Stretch::RawLinear,
+            StretchArg::Log => Stretch::Log { k: app::DEFAULT_LOG_KNEE },
+            StretchArg::Equalised => Stretch::HistEq { shared: true },
+        }),
+        channel: args.channel.map(|c| match c {
+            ChannelArg::Rgb => ChannelView::Rgb,
+            ChannelArg::R => ChannelView::Single(0),
+            ChannelArg::G => ChannelView::Single(1),
+            ChannelArg::B => ChannelView::Single(2),
+        }),
+    };
 
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
@@ -28,7 +154,38 @@ fn main() -> anyhow::Result<()> {
     eframe::run_native(
         "fastfits",
         options,
-        Box::new(|cc| Ok(Box::new(app::FastFitsApp::new(cc, start_path)))),
+        Box::new(move |cc| Ok(Box::new(app::FastFitsApp::new(cc, paths, file_list, start_view)))),
     )
     .map_err(|e| anyhow::anyhow!("eframe error: {e}"))
 }
+
+/// FITS data piped on stdin, copied to a temporary file because cfitsio needs a
+/// seekable file. The copy is removed when this is dropped.
+struct StdinCopy {
+    dir: PathBuf,
+}
+
+impl StdinCopy {
+    fn read() -> anyhow::Result<Self> {
+        let dir = std::env::temp_dir().join(format!("fastfits-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).with_context(|| format!("creating {}", dir.display()))?;
+        let copy = StdinCopy { dir };
+        let path = copy.path();
+        let mut file = std::fs::File::create(&path).with_context(|| format!("creating {}", path.display()))?;
+        let bytes = std::io::copy(&mut std::io::stdin().lock(), &mut file).context("reading stdin")?;
+        if bytes == 0 {
+            anyhow::bail!("no data on stdin");
+        }
+        Ok(copy)
+    }
+
+    fn path(&self) -> PathBuf {
+        self.dir.join("stdin.fits")
+    }
+}
+
+impl Drop for StdinCopy {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
@@ -0,0 +1,96 @@
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::fits::{header_f64, header_value, read_image_headers, FrameType};
+
+/// Time the scan rests after each file, as a multiple of the time reading it
+/// took: at 1 the disk is left to image loads at least half of the time, so a
+/// directory of thousands of files does not make browsing sluggish.
+const IDLE_RATIO: u32 = 1;
+
+/// Longest delay before the UI shows newly scanned files. Results are batched
+/// into one repaint per interval rather than one per file.
+const REPAINT_INTERVAL: Duration = Duration::from_millis(200);
+
+/// What the browser shows of a file without loading it, from its header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileMeta {
+    /// Light, dark, flat or bias, from `IMAGETYP` / `FRAME`
+    pub frame_type: FrameType,
+    /// Exposure in seconds (`EXPTIME` or `EXPOSURE`)
+    pub exptime: Option<f64>,
+    /// Filter name (`FILTER`)
+    pub filter: Option<String>,
+}
+
+impl FileMeta {
+    /// Metadata of a file whose header could not be read.
+    pub const UNREADABLE: FileMeta = FileMeta { frame_type: FrameType::Unknown, exptime: None, filter: None };
+
+    /// Read from a file's image header (see [`read_image_headers`]).
+    pub fn from_headers(headers: &[(String, String)]) -> Self {
+        FileMeta {
+            frame_type: FrameType::of(headers),
+            exptime: ["EXPTIME", "EXPOSURE"].iter().find_map(|k| header_f64(headers, k)),
+            filter: header_value(headers, "FILTER").filter(|v| !v.is_empty()).map(str::to_string),
+        }
+    }
+
+    /// Exposure and filter for the browser, e.g. `300 s · Ha`; empty without either.
+    pub fn summary(&self) -> String {
+        let exposure = self.exptime.map(|t| format!("{} s", (t * 100.0).round() / 100.0));
+        exposure.into_iter().chain(self.filter.clone()).collect::<Vec<_>>().join(" · ")
+    }
+}
+
+/// Reads the headers of a list of files on a background thread, without decoding
+/// any pixels, and reports each file's metadata as it comes in. Dropping the
+/// scan stops the thread after the file it is reading.
+pub struct HeaderScan {
+    rx: mpsc::Receiver<(PathBuf, FileMeta)>,
+}
+
+impl HeaderScan {
+    /// Start scanning `paths` in order, throttled (see [`IDLE_RATIO`]). Results
+    /// wake the UI via `ctx`.
+    pub fn start(paths: Vec<PathBuf>, ctx: egui::Context) -> Self {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for path in paths {
+                let started = Instant::now();
+                // Unreadable files are listed like files without the keywords
+                let meta = read_image_headers(&path).map_or(FileMeta::UNREADABLE, |h| FileMeta::from_headers(&h));
+                if tx.send((path, meta)).is_err() {
+                    return;
+                }
+                ctx.request_repaint_after(REPAINT_INTERVAL);
+                std::thread::sleep(started.elapsed() * IDLE_RATIO);
+            }
+        });
+        HeaderScan { rx }
+    }
+
+    /// Results that arrived since the last call.
+    pub fn poll(&self) -> Vec<(PathBuf, FileMeta)> {
+        self.rx.try_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metadata_from_headers() {
+        let headers: Vec<(String, String)> = [("IMAGETYP", "Light Frame"), ("EXPOSURE", "300.000"), ("FILTER", "Ha")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let meta = FileMeta::from_headers(&headers);
+        assert_eq!(meta, FileMeta { frame_type: FrameType::Light, exptime: Some(300.0), filter: Some("Ha".into()) });
+        assert_eq!(meta.summary(), "300 s · Ha");
+        assert_eq!(FileMeta::from_headers(&[]), FileMeta::UNREADABLE);
+        assert_eq!(FileMeta::UNREADABLE.summary(), "");
+    }
+}
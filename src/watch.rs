@@ -0,0 +1,80 @@
+use anyhow::{Context, Result};
+use notify::event::{AccessKind, AccessMode, EventKind, ModifyKind};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::config::FileFilter;
+
+/// Quiet period after the last filesystem event before a batch of changes is reported.
+/// Capture programs write a frame in several chunks; waiting for the writes to settle
+/// avoids loading a half-written file.
+pub const DEBOUNCE: Duration = Duration::from_millis(750);
+
+/// Watches a single directory (non-recursively) for browsable files being
+/// created, rewritten or removed, and reports debounced batches of changed paths.
+pub struct DirWatcher {
+    /// Kept alive for as long as the directory should be watched.
+    _watcher: RecommendedWatcher,
+    rx: mpsc::Receiver<notify::Result<notify::Event>>,
+    /// Paths passing the filter touched since the last reported batch
+    pending: BTreeSet<PathBuf>,
+    /// Time of the most recent relevant event
+    last_event: Option<Instant>,
+}
+
+impl DirWatcher {
+    /// Start watching `dir`. Every filesystem event wakes the UI via `ctx`.
+    pub fn new(dir: &Path, ctx: egui::Context) -> Result<Self> {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = tx.send(event);
+            ctx.request_repaint();
+        })
+        .context("creating file watcher")?;
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .with_context(|| format!("watching {}", dir.display()))?;
+        Ok(DirWatcher {
+            _watcher: watcher,
+            rx,
+            pending: BTreeSet::new(),
+            last_event: None,
+        })
+    }
+
+    /// Drain queued events. Returns the changed paths that pass `filter` once no
+    /// new event has arrived for `DEBOUNCE`; returns None while idle or still settling.
+    pub fn poll(&mut self, filter: &FileFilter) -> Option<BTreeSet<PathBuf>> {
+        while let Ok(event) = self.rx.try_recv() {
+            let Ok(event) = event else { continue };
+            // Reads (including our own loads) and metadata updates are not content changes.
+            let relevant = match event.kind {
+                EventKind::Access(AccessKind::Close(AccessMode::Write)) => true,
+                EventKind::Access(_) | EventKind::Modify(ModifyKind::Metadata(_)) => false,
+                _ => true,
+            };
+            if !relevant {
+                continue;
+            }
+            for path in event.paths.into_iter().filter(|p| filter.matches(p)) {
+                self.pending.insert(path);
+                self.last_event = Some(Instant::now());
+            }
+        }
+
+        let last = self.last_event?;
+        if last.elapsed() < DEBOUNCE {
+            return None;
+        }
+        self.last_event = None;
+        Some(std::mem::take(&mut self.pending))
+    }
+
+    /// True while changes have been seen but not yet reported by `poll`.
+    pub fn is_settling(&self) -> bool {
+        self.last_event.is_some()
+    }
+}